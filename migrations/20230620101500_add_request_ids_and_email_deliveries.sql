@@ -0,0 +1,11 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN request_id TEXT;
+
+CREATE TABLE email_deliveries (
+    delivery_id uuid PRIMARY KEY,
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    request_id TEXT,
+    sent_at timestamptz NOT NULL
+);
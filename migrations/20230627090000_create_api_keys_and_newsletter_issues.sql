@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE api_keys (
+    key_id uuid PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- The first characters of the key, to tell keys apart in the dashboard
+    key_prefix TEXT NOT NULL,
+    -- SHA-256 of the whole key: keys are random enough not to need a slow hash
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_by uuid NOT NULL REFERENCES users(user_id),
+    created_at timestamptz NOT NULL,
+    revoked_at timestamptz
+);
+
+CREATE TABLE newsletter_issues (
+    newsletter_issue_id uuid PRIMARY KEY,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    published_at timestamptz
+);
+
+ALTER TABLE email_deliveries
+    ADD COLUMN newsletter_issue_id uuid REFERENCES newsletter_issues(newsletter_issue_id);
@@ -0,0 +1,11 @@
+-- Issues go through `draft`, `scheduled` (optional), `sending` and `sent`
+ALTER TABLE newsletter_issues
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'draft'
+        CHECK (status IN ('draft', 'scheduled', 'sending', 'sent')),
+    ADD COLUMN updated_at timestamptz;
+UPDATE newsletter_issues SET status = 'sent' WHERE published_at IS NOT NULL;
+UPDATE newsletter_issues SET updated_at = created_at;
+ALTER TABLE newsletter_issues ALTER COLUMN updated_at SET NOT NULL;
+
+-- Where admins get the test copies of their drafts
+ALTER TABLE users ADD COLUMN email TEXT;
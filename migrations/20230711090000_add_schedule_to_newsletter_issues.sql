@@ -0,0 +1,10 @@
+-- Set together when an issue is `scheduled`, cleared when it is cancelled.
+-- The timezone is the one the editor picked the time in, e.g. `Europe/Paris`.
+ALTER TABLE newsletter_issues
+    ADD COLUMN scheduled_for timestamptz,
+    ADD COLUMN schedule_timezone TEXT;
+
+-- What the scheduler of every replica looks up
+CREATE INDEX newsletter_issues_due_idx
+    ON newsletter_issues (scheduled_for)
+    WHERE status = 'scheduled';
@@ -0,0 +1,3 @@
+-- The Markdown source of issues written in Markdown, kept to edit their drafts.
+-- `text_content` and `html_content` are generated from it.
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT;
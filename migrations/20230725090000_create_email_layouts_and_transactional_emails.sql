@@ -0,0 +1,32 @@
+-- Layouts wrap the body of newsletter issues: header, footer, branding, address...
+-- Every edit adds a version: past versions are never updated.
+CREATE TABLE email_layouts(
+    layout_id uuid PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE TABLE email_layout_versions(
+    layout_id uuid NOT NULL REFERENCES email_layouts (layout_id),
+    version INT NOT NULL,
+    html_template TEXT NOT NULL,
+    text_template TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (layout_id, version)
+);
+-- The version is pinned when the issue starts being sent,
+-- so that it keeps looking the way it did for its recipients.
+ALTER TABLE newsletter_issues
+    ADD COLUMN layout_id uuid REFERENCES email_layouts (layout_id),
+    ADD COLUMN layout_version INT;
+
+-- Emails sent by the application itself. Built-in defaults are used
+-- until a first version is saved.
+CREATE TABLE transactional_email_versions(
+    kind TEXT NOT NULL CHECK (kind IN ('confirmation')),
+    version INT NOT NULL,
+    subject TEXT NOT NULL,
+    html_template TEXT NOT NULL,
+    text_template TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (kind, version)
+);
@@ -0,0 +1,8 @@
+-- Sent issues are listed in the public archive and its feeds, unless they opt out.
+ALTER TABLE newsletter_issues
+    ADD COLUMN hidden_from_archive BOOLEAN NOT NULL DEFAULT false;
+
+-- What `/archive` and the feeds look up, most recent first
+CREATE INDEX newsletter_issues_archive_idx
+    ON newsletter_issues (published_at DESC)
+    WHERE status = 'sent' AND NOT hidden_from_archive;
@@ -0,0 +1,17 @@
+-- Opens and clicks are only recorded for the issues that opt in,
+-- and only while `tracking.enabled` is set.
+ALTER TABLE newsletter_issues ADD COLUMN tracked BOOLEAN NOT NULL DEFAULT false;
+
+-- What the recipients of an issue did with it.
+-- Deliveries are in `email_deliveries`.
+CREATE TABLE newsletter_issue_events(
+    event_id uuid PRIMARY KEY,
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    kind TEXT NOT NULL CHECK (kind IN ('open', 'click', 'unsubscribe')),
+    -- The destination of clicks
+    url TEXT,
+    occurred_at timestamptz NOT NULL
+);
+CREATE INDEX newsletter_issue_events_issue_idx
+    ON newsletter_issue_events (newsletter_issue_id, occurred_at);
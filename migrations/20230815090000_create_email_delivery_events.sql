@@ -0,0 +1,17 @@
+-- Add migration script here
+-- The id Postmark gave the message: its webhooks refer to deliveries by it
+ALTER TABLE email_deliveries ADD COLUMN message_id TEXT;
+CREATE UNIQUE INDEX email_deliveries_message_id ON email_deliveries (message_id);
+
+-- What the email provider reports about a delivery, through its webhooks
+CREATE TABLE email_delivery_events (
+    event_id uuid PRIMARY KEY,
+    delivery_id uuid NOT NULL REFERENCES email_deliveries(delivery_id),
+    kind TEXT NOT NULL CHECK (kind IN ('delivery', 'bounce', 'spam_complaint', 'open')),
+    -- e.g. the type of a bounce, `HardBounce`, `SoftBounce`...
+    details TEXT,
+    occurred_at timestamptz NOT NULL,
+    received_at timestamptz NOT NULL,
+    -- Webhooks are retried until they get a 200: the same event can come twice
+    UNIQUE (delivery_id, kind, occurred_at)
+);
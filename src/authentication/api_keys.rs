@@ -0,0 +1,194 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "z2p_";
+// Characters of the key shown in the dashboard, `z2p_` included
+const DISPLAYED_PREFIX_LENGTH: usize = 12;
+
+/// What an API key is allowed to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiScope {
+    SubscribersRead,
+    SubscribersWrite,
+    IssuesRead,
+    IssuesWrite,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [
+        ApiScope::SubscribersRead,
+        ApiScope::SubscribersWrite,
+        ApiScope::IssuesRead,
+        ApiScope::IssuesWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::SubscribersRead => "subscribers:read",
+            ApiScope::SubscribersWrite => "subscribers:write",
+            ApiScope::IssuesRead => "issues:read",
+            ApiScope::IssuesWrite => "issues:write",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == s)
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+/// The API key a request has been authenticated with.
+#[derive(Clone, Debug)]
+pub struct ApiKey {
+    pub key_id: Uuid,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiKeyRecord {
+    pub key_id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Store a new API key and return it.
+/// Only its hash is stored: this is the only time the key can be shown.
+#[tracing::instrument(name = "Create API key", skip(pool))]
+pub async fn create_api_key(
+    name: &str,
+    scopes: &[ApiScope],
+    created_by: Uuid,
+    pool: &PgPool,
+) -> Result<Secret<String>, anyhow::Error> {
+    let key = generate_api_key();
+    let scopes: Vec<&str> = scopes.iter().map(ApiScope::as_str).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_keys (key_id, name, key_prefix, key_hash, scopes, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        "#,
+        Uuid::new_v4(),
+        name,
+        &key[..DISPLAYED_PREFIX_LENGTH],
+        hash_api_key(&key),
+        &scopes as &[&str],
+        created_by,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert a new API key in the database.")?;
+    Ok(Secret::new(key))
+}
+
+/// Returns `false` if no active key matches `key_id`.
+#[tracing::instrument(name = "Revoke API key", skip(pool))]
+pub async fn revoke_api_key(key_id: Uuid, pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE api_keys
+        SET revoked_at = now()
+        WHERE key_id = $1 AND revoked_at IS NULL
+        "#,
+        key_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke an API key.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(name = "List API keys", skip(pool))]
+pub async fn list_api_keys(pool: &PgPool) -> Result<Vec<ApiKeyRecord>, anyhow::Error> {
+    let keys = sqlx::query_as!(
+        ApiKeyRecord,
+        r#"
+        SELECT key_id, name, key_prefix, scopes, created_at, revoked_at
+        FROM api_keys
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list API keys.")?;
+    Ok(keys)
+}
+
+/// Look up an active API key. Returns `None` if it is unknown or revoked.
+#[tracing::instrument(name = "Authenticate API key", skip(key, pool))]
+pub async fn authenticate_api_key(
+    key: &Secret<String>,
+    pool: &PgPool,
+) -> Result<Option<ApiKey>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT key_id, scopes
+        FROM api_keys
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        "#,
+        hash_api_key(key.expose_secret()),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve an API key.")?;
+    Ok(row.map(|r| ApiKey {
+        key_id: r.key_id,
+        // Scopes we no longer know about are ignored
+        scopes: r.scopes.iter().filter_map(|s| ApiScope::parse(s)).collect(),
+    }))
+}
+
+// `z2p_` followed by 40 random alphanumeric characters (~238 bits of entropy).
+fn generate_api_key() -> String {
+    let mut rng = thread_rng();
+    let random: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(40)
+        .collect();
+    format!("{}{}", KEY_PREFIX, random)
+}
+
+fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_api_key, ApiScope};
+
+    #[test]
+    fn scopes_round_trip_through_their_string_representation() {
+        for scope in ApiScope::ALL {
+            assert_eq!(ApiScope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(ApiScope::parse("admin"), None);
+    }
+
+    #[test]
+    fn generated_keys_are_prefixed_and_unique() {
+        let first = generate_api_key();
+        let second = generate_api_key();
+        assert!(first.starts_with("z2p_"));
+        assert_eq!(first.len(), 44);
+        assert_ne!(first, second);
+    }
+}
@@ -1,11 +1,15 @@
+use super::users::is_user_enabled;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
+use actix_web::web::Data;
 use actix_web::FromRequest;
 use actix_web::HttpMessage;
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -28,7 +32,7 @@ impl Deref for UserId {
 
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
-    next: Next<impl MessageBody>,
+    next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
     let session = {
         let (http_request, payload) = req.parts_mut();
@@ -36,8 +40,20 @@ pub async fn reject_anonymous_users(
     }?;
     match session.get_user_id().map_err(e500)? {
         Some(user_id) => {
+            let pool = req
+                .app_data::<Data<PgPool>>()
+                .context("The database pool is not registered.")
+                .map_err(e500)?;
+            // Disabling a user must also end the sessions they already have.
+            // The session middleware only saves the changes of successful responses.
+            if !is_user_enabled(user_id, pool).await.map_err(e500)? {
+                session.log_out();
+                return Ok(req.into_response(see_other("/login")).map_into_right_body());
+            }
             req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
         }
         None => {
             let response = see_other("/login");
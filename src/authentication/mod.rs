@@ -1,6 +1,16 @@
+mod api_keys;
 mod middleware;
 mod password;
+mod users;
 
+pub use api_keys::{
+    authenticate_api_key, create_api_key, list_api_keys, revoke_api_key, ApiKey, ApiKeyRecord,
+    ApiScope,
+};
 pub use middleware::reject_anonymous_users;
 pub use middleware::UserId;
 pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use users::{
+    create_user, default_credentials_active, get_user_email, get_user_id, is_user_enabled,
+    list_users, set_user_disabled, set_user_email, UserRecord,
+};
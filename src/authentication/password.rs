@@ -1,3 +1,4 @@
+use crate::metrics::record_password_verification;
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::{
@@ -48,12 +49,14 @@ pub async fn validate_credentials(
         // we then pass ownership to it into the closure
         // and explicitly executes all our computation
         // within its scope.
-        verify_password_hash(expected_password_hash, credentials.password)
+        let start = std::time::Instant::now();
+        let outcome = verify_password_hash(expected_password_hash, credentials.password);
+        record_password_verification(outcome.is_ok(), start.elapsed());
+        outcome
     })
     .await
     .context("Failed to spawn blocking task.")
-    .map_err(AuthError::InvalidCredentials)?
-    .await?;
+    .map_err(AuthError::InvalidCredentials)??;
 
     // This is only set to `Some()` if we found credentials in the store
     // so, even if the default password ends up matching (somewhow)
@@ -69,7 +72,7 @@ pub async fn validate_credentials(
     name = "Verify password hash",
     skip(expected_password_hash, password_candidate)
 )]
-async fn verify_password_hash(
+fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
 ) -> Result<(), AuthError> {
@@ -85,6 +88,7 @@ async fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials)
 }
 
+// Disabled users are treated as if they did not exist.
 #[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
 async fn get_stored_credentials(
     username: &str,
@@ -94,7 +98,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND NOT disabled
         "#,
         username,
     )
@@ -131,7 +135,9 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub(crate) fn compute_password_hash(
+    password: Secret<String>,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         Algorithm::Argon2id,
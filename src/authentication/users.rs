@@ -0,0 +1,173 @@
+use super::password::compute_password_hash;
+use crate::domain::SubscriberEmail;
+use crate::telemetry::spawn_blocking_with_tracing;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Hash inserted for `admin` by the `seed_user` migration.
+// Its password is public knowledge, so it must be rotated before going live.
+const SEED_USER_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+    bObqtzVmenZti94hpirc9Q$\
+    V6qrUfY/dRvcYexHILF4oLKZSzibM/py2FbVaaMYm2s";
+
+#[derive(Debug)]
+pub struct UserRecord {
+    pub user_id: Uuid,
+    pub username: String,
+    pub disabled: bool,
+}
+
+#[tracing::instrument(name = "Create user", skip(password, pool))]
+pub async fn create_user(
+    username: &str,
+    password: Secret<String>,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await?
+        .context("Failed to hash password")?;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash)
+        VALUES ($1, $2, $3)
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert a new user in the database.")?;
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Get user id", skip(pool))]
+pub async fn get_user_id(username: &str, pool: &PgPool) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a user id.")?;
+    Ok(row.map(|r| r.user_id))
+}
+
+#[tracing::instrument(name = "List users", skip(pool))]
+pub async fn list_users(pool: &PgPool) -> Result<Vec<UserRecord>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        UserRecord,
+        r#"
+        SELECT user_id, username, disabled
+        FROM users
+        ORDER BY username
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list users.")?;
+    Ok(users)
+}
+
+/// Flip the `disabled` flag of a user.
+/// Returns `false` if no user matches `username`.
+#[tracing::instrument(name = "Set user disabled", skip(pool))]
+pub async fn set_user_disabled(
+    username: &str,
+    disabled: bool,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET disabled = $2
+        WHERE username = $1
+        "#,
+        username,
+        disabled,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the disabled flag of a user.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Returns `false` if the user has been disabled, or does not exist anymore.
+#[tracing::instrument(name = "Check whether a user is enabled", skip(pool))]
+pub async fn is_user_enabled(user_id: Uuid, pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT disabled
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to check whether a user is enabled.")?;
+    Ok(row.is_some_and(|r| !r.disabled))
+}
+
+#[tracing::instrument(name = "Get user email", skip(pool))]
+pub async fn get_user_email(user_id: Uuid, pool: &PgPool) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to retrieve the email of a user.")?;
+    Ok(row.email)
+}
+
+#[tracing::instrument(name = "Set user email", skip(email, pool))]
+pub async fn set_user_email(
+    user_id: Uuid,
+    email: &SubscriberEmail,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $2
+        WHERE user_id = $1
+        "#,
+        user_id,
+        email.as_ref(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the email of a user.")?;
+    Ok(())
+}
+
+/// Returns `true` if an enabled user still logs in with the seeded password.
+#[tracing::instrument(name = "Check for default credentials", skip(pool))]
+pub async fn default_credentials_active(pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1
+            FROM users
+            WHERE password_hash = $1 AND NOT disabled
+        ) AS "active!"
+        "#,
+        SEED_USER_PASSWORD_HASH,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check for the seeded default credentials.")?;
+    Ok(row.active)
+}
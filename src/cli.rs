@@ -0,0 +1,129 @@
+use crate::authentication::{
+    change_password, create_user, get_user_id, list_users, set_user_disabled,
+};
+use crate::configuration::{get_configuration, Settings};
+use crate::startup::get_connection_pool;
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use secrecy::Secret;
+use std::io::IsTerminal;
+
+#[derive(Parser)]
+#[command(name = "zero2prod", about = "Newsletter delivery service")]
+pub struct Cli {
+    /// Defaults to `serve` when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the HTTP server.
+    Serve,
+    /// Validate the configuration and print it with secrets redacted.
+    CheckConfig,
+    /// Manage admin accounts.
+    #[command(subcommand)]
+    Admin(AdminCommand),
+}
+
+#[derive(Subcommand)]
+pub enum AdminCommand {
+    /// Create a new admin user.
+    Create {
+        #[arg(long)]
+        username: String,
+    },
+    /// Set a new password for an existing user.
+    ResetPassword {
+        #[arg(long)]
+        username: String,
+    },
+    /// List all users.
+    List,
+    /// Prevent a user from logging in.
+    Disable {
+        #[arg(long)]
+        username: String,
+    },
+    /// Allow a disabled user to log in again.
+    Enable {
+        #[arg(long)]
+        username: String,
+    },
+}
+
+/// Print the effective configuration, then report every invalid value.
+pub fn check_config() -> Result<(), anyhow::Error> {
+    let configuration = get_configuration().context("Failed to read configuration.")?;
+    println!("{}", serde_json::to_string_pretty(&configuration)?);
+    configuration.validate()?;
+    Ok(())
+}
+
+pub async fn run_admin_command(
+    command: AdminCommand,
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    match command {
+        AdminCommand::Create { username } => {
+            if get_user_id(&username, &pool).await?.is_some() {
+                anyhow::bail!("A user named `{}` already exists.", username);
+            }
+            let password = read_password()?;
+            let user_id = create_user(&username, password, &pool).await?;
+            println!("Created user `{}` ({}).", username, user_id);
+        }
+        AdminCommand::ResetPassword { username } => {
+            let user_id = get_user_id(&username, &pool)
+                .await?
+                .with_context(|| format!("There is no user named `{}`.", username))?;
+            let password = read_password()?;
+            change_password(user_id, password, &pool).await?;
+            println!("Changed the password of `{}`.", username);
+        }
+        AdminCommand::List => {
+            for user in list_users(&pool).await? {
+                let status = if user.disabled { "disabled" } else { "active" };
+                println!("{}\t{}\t{}", user.user_id, user.username, status);
+            }
+        }
+        AdminCommand::Disable { username } => {
+            if !set_user_disabled(&username, true, &pool).await? {
+                anyhow::bail!("There is no user named `{}`.", username);
+            }
+            println!("Disabled `{}`.", username);
+        }
+        AdminCommand::Enable { username } => {
+            if !set_user_disabled(&username, false, &pool).await? {
+                anyhow::bail!("There is no user named `{}`.", username);
+            }
+            println!("Enabled `{}`.", username);
+        }
+    }
+    Ok(())
+}
+
+// Prompt twice on a terminal, read a single line otherwise
+// so the commands can be scripted (e.g. `echo $PASSWORD | zero2prod admin create ...`).
+fn read_password() -> Result<Secret<String>, anyhow::Error> {
+    let password = if std::io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password: ")?;
+        let password_check = rpassword::prompt_password("Confirm password: ")?;
+        if password != password_check {
+            anyhow::bail!("You entered two different passwords - the values must match.");
+        }
+        password
+    } else {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .context("Failed to read the password from stdin.")?;
+        line.trim_end_matches(['\r', '\n']).to_owned()
+    };
+    if password.is_empty() {
+        anyhow::bail!("The password cannot be empty.");
+    }
+    Ok(Secret::new(password))
+}
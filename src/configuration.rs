@@ -1,22 +1,36 @@
 use crate::domain::SubscriberEmail;
 use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Deserializer, Serializer};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::{
     postgres::{PgConnectOptions, PgSslMode},
     ConnectOptions,
 };
+use std::collections::BTreeMap;
+use tracing_subscriber::EnvFilter;
 
-#[derive(serde::Deserialize, Clone)]
+// `Serialize` is only used to print the effective configuration:
+// every secret goes through `redact` so it never ends up in the output.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    #[serde(serialize_with = "redact")]
     pub redis_uri: Secret<String>,
+    pub metrics: MetricsSettings,
+    pub opentelemetry: OpenTelemetrySettings,
+    pub health_check: HealthCheckSettings,
+    pub telemetry: TelemetrySettings,
+    pub signup_protection: SignupProtectionSettings,
+    pub security_headers: SecurityHeadersSettings,
+    pub tracking: TrackingSettings,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct DatabaseSettings {
     pub username: String,
+    #[serde(serialize_with = "redact")]
     pub password: Secret<String>,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
@@ -26,7 +40,7 @@ pub struct DatabaseSettings {
     pub require_ssl: bool,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
@@ -35,19 +49,470 @@ pub struct ApplicationSettings {
     // it will be http://127.0.0.1 for our tests and a proper DNS record with HTTPS for prod
     pub base_url: String,
     // Use to verify response to avoid XSS attacks (when API redirects and injects error data to URL)
+    #[serde(serialize_with = "redact")]
     pub hmac_secret: Secret<String>,
+    // Keep the `X-Request-Id` sent by clients instead of generating one.
+    // Only enable it behind a proxy that sets or sanitises the header.
+    pub trust_request_id_header: bool,
+    // How long in-flight requests and background workers get to finish on shutdown
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub shutdown_grace_period_seconds: u64,
+    // How often each replica looks for scheduled newsletter issues that are due
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub scheduler_interval_seconds: u64,
+    // Partner sites calling the public subscription routes from their own pages.
+    // It never applies to `/admin`.
+    pub cors: CorsSettings,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct CorsSettings {
+    // e.g. `https://partner.example.com`, or `*` for any origin.
+    // Lists can also be set as comma-separated strings,
+    // e.g. `APP_APPLICATION__CORS__ALLOWED_ORIGINS=https://a.com,https://b.com`
+    #[serde(deserialize_with = "deserialize_list")]
+    pub allowed_origins: Vec<String>,
+    #[serde(deserialize_with = "deserialize_list")]
+    pub allowed_methods: Vec<String>,
+    #[serde(deserialize_with = "deserialize_list")]
+    pub allowed_headers: Vec<String>,
+    // How long browsers can cache a preflight response
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_seconds: usize,
+}
+
+impl ApplicationSettings {
+    pub fn shutdown_grace_period(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_grace_period_seconds)
+    }
+
+    pub fn scheduler_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.scheduler_interval_seconds)
+    }
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct EmailClientSettings {
     pub base_url: String,
     pub sender_email: String,
     // New (secret) configuration value!
+    #[serde(serialize_with = "redact")]
     pub authorization_token: Secret<String>,
     // New timeout configuration value!
     pub timeout_milliseconds: u64,
+    // The Basic credentials Postmark sends its webhooks with, see `routes::postmark_webhook`
+    pub webhook_username: String,
+    #[serde(serialize_with = "redact")]
+    pub webhook_password: Secret<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct MetricsSettings {
+    // Serve `/metrics` from its own port, out of reach of the public traffic.
+    // When unset, `/metrics` is served by the main application.
+    #[serde(default, deserialize_with = "deserialize_optional_port")]
+    pub port: Option<u16>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct OpenTelemetrySettings {
+    // Export spans to an OpenTelemetry collector, on top of the bunyan logs
+    pub enabled: bool,
+    // Base URL of the collector OTLP/HTTP receiver: spans are sent to `<endpoint>/v1/traces`
+    pub endpoint: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct HealthCheckSettings {
+    // Report whether the email provider is reachable in `/health/ready`.
+    // It is not required for readiness: we can still take subscriptions while it is down.
+    pub probe_email_provider: bool,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct TrackingSettings {
+    // Record the opens and clicks of the newsletter issues that opt in.
+    // When unset, no issue is tracked, whatever it was written with.
+    pub enabled: bool,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct TelemetrySettings {
+    pub format: LogFormat,
+    // Applies to every module without a more specific entry in `filters`
+    pub level: String,
+    // Per-module levels, e.g. `sqlx: debug` to trace every statement
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+}
+
+impl TelemetrySettings {
+    /// `EnvFilter` directives, e.g. `info,sqlx=debug`.
+    /// `RUST_LOG` still takes precedence when set.
+    pub fn env_filter(&self) -> String {
+        std::iter::once(self.level.clone())
+            .chain(
+                self.filters
+                    .iter()
+                    .map(|(module, level)| format!("{}={}", module, level)),
+            )
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, to be shipped to a log aggregator.
+    Bunyan,
+    /// Multi-line and colored, for humans.
+    Pretty,
+    /// One line per event.
+    Compact,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct SignupProtectionSettings {
+    // Prepended to the rate limiting keys stored in Redis
+    pub key_prefix: String,
+    // Rate limit the address our proxy appended to `X-Forwarded-For` rather than the peer one.
+    // Only enable it behind a proxy that sets the header.
+    pub trust_forwarded_for: bool,
+    pub per_ip: RateLimit,
+    pub per_email_domain: RateLimit,
+    pub challenge: ChallengeSettings,
+}
+
+/// At most `max_requests` in every `window_seconds`-long window.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug)]
+pub struct RateLimit {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl RateLimit {
+    pub fn window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.window_seconds)
+    }
+}
+
+// Any provider implementing the `siteverify` protocol shared by
+// Cloudflare Turnstile, hCaptcha and reCAPTCHA.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct ChallengeSettings {
+    pub enabled: bool,
+    pub verify_url: String,
+    #[serde(serialize_with = "redact")]
+    pub secret: Secret<String>,
+    // Rendered in the home page form
+    pub script_url: String,
+    pub widget_class: String,
+    pub site_key: String,
+}
+
+// Sent with every HTML page. An empty value turns the header off.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct SecurityHeadersSettings {
+    // `{nonce}` is replaced by a value unique to each request,
+    // for pages to mark their inline `<script>` and `<style>` tags with
+    pub content_security_policy: String,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+    // `Strict-Transport-Security` is only sent in production
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub hsts_max_age_seconds: u64,
+}
+
+// `serde_aux`'s optional counterpart of `deserialize_number_from_string` cannot handle
+// the owned strings coming from environment variables (e.g. `APP_METRICS__PORT=9000`)
+fn deserialize_optional_port<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u16>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum PortOrString {
+        Port(u16),
+        String(String),
+    }
+
+    match Option::<PortOrString>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(PortOrString::Port(port)) => Ok(Some(port)),
+        Some(PortOrString::String(s)) if s.is_empty() => Ok(None),
+        Some(PortOrString::String(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+// Environment variables can only hold strings: `a,b` is read as `["a", "b"]`
+fn deserialize_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum ListOrString {
+        List(Vec<String>),
+        String(String),
+    }
+
+    match ListOrString::deserialize(deserializer)? {
+        ListOrString::List(list) => Ok(list),
+        ListOrString::String(s) => Ok(s
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()),
+    }
+}
+
+fn redact<S: Serializer>(_: &Secret<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("[REDACTED]")
+}
+
+// `actix_web::cookie::Key::from` panics on anything shorter.
+const MIN_HMAC_SECRET_LENGTH: usize = 64;
+
+impl Settings {
+    /// Check every value that would otherwise only fail at runtime.
+    /// All the problems are reported at once, each one with its config key path.
+    pub fn validate(&self) -> Result<(), InvalidSettings> {
+        let mut errors = Vec::new();
+        let mut check = |key: &'static str, outcome: Result<(), String>| {
+            if let Err(message) = outcome {
+                errors.push(InvalidSetting { key, message });
+            }
+        };
+
+        check(
+            "application.base_url",
+            parse_url(&self.application.base_url),
+        );
+        check(
+            "application.hmac_secret",
+            if self.application.hmac_secret.expose_secret().len() < MIN_HMAC_SECRET_LENGTH {
+                Err(format!(
+                    "must be at least {} bytes long",
+                    MIN_HMAC_SECRET_LENGTH
+                ))
+            } else {
+                Ok(())
+            },
+        );
+        check(
+            "application.scheduler_interval_seconds",
+            if self.application.scheduler_interval_seconds == 0 {
+                Err("must be greater than zero".into())
+            } else {
+                Ok(())
+            },
+        );
+        check(
+            "application.cors.allowed_origins",
+            self.application
+                .cors
+                .allowed_origins
+                .iter()
+                .filter(|origin| origin.as_str() != "*")
+                .try_for_each(|origin| parse_origin(origin)),
+        );
+        check(
+            "application.cors.allowed_methods",
+            self.application
+                .cors
+                .allowed_methods
+                .iter()
+                .try_for_each(|method| {
+                    actix_web::http::Method::from_bytes(method.as_bytes())
+                        .map(|_| ())
+                        .map_err(|_| format!("`{}` is not a valid HTTP method", method))
+                }),
+        );
+        check(
+            "application.cors.allowed_headers",
+            self.application
+                .cors
+                .allowed_headers
+                .iter()
+                .try_for_each(|header| {
+                    actix_web::http::header::HeaderName::from_bytes(header.as_bytes())
+                        .map(|_| ())
+                        .map_err(|_| format!("`{}` is not a valid header name", header))
+                }),
+        );
+        check(
+            "email_client.base_url",
+            parse_url(&self.email_client.base_url),
+        );
+        check(
+            "email_client.sender_email",
+            self.email_client.sender().map(|_| ()),
+        );
+        check(
+            "email_client.timeout_milliseconds",
+            if self.email_client.timeout_milliseconds == 0 {
+                Err("must be greater than zero".into())
+            } else {
+                Ok(())
+            },
+        );
+        check(
+            "email_client.webhook_password",
+            if self
+                .email_client
+                .webhook_password
+                .expose_secret()
+                .is_empty()
+            {
+                Err("must be set: anyone could post events otherwise".into())
+            } else {
+                Ok(())
+            },
+        );
+        check("redis_uri", parse_url(self.redis_uri.expose_secret()));
+        if self.opentelemetry.enabled {
+            check(
+                "opentelemetry.endpoint",
+                parse_url(&self.opentelemetry.endpoint),
+            );
+        }
+        check(
+            "telemetry",
+            EnvFilter::try_new(self.telemetry.env_filter())
+                .map(|_| ())
+                .map_err(|e| format!("is not a valid log filter ({})", e)),
+        );
+        for (key, limit) in [
+            ("signup_protection.per_ip", &self.signup_protection.per_ip),
+            (
+                "signup_protection.per_email_domain",
+                &self.signup_protection.per_email_domain,
+            ),
+        ] {
+            check(
+                key,
+                if limit.max_requests == 0 || limit.window_seconds == 0 {
+                    Err("`max_requests` and `window_seconds` must be greater than zero".into())
+                } else {
+                    Ok(())
+                },
+            );
+        }
+        let challenge = &self.signup_protection.challenge;
+        if challenge.enabled {
+            check(
+                "signup_protection.challenge.verify_url",
+                parse_url(&challenge.verify_url),
+            );
+            check(
+                "signup_protection.challenge.script_url",
+                parse_url(&challenge.script_url),
+            );
+            check(
+                "signup_protection.challenge.secret",
+                if challenge.secret.expose_secret().is_empty() {
+                    Err("must be set when the challenge is enabled".into())
+                } else {
+                    Ok(())
+                },
+            );
+            check(
+                "signup_protection.challenge.site_key",
+                if challenge.site_key.is_empty() {
+                    Err("must be set when the challenge is enabled".into())
+                } else {
+                    Ok(())
+                },
+            );
+        }
+        for (key, value) in [
+            (
+                "security_headers.content_security_policy",
+                &self.security_headers.content_security_policy,
+            ),
+            (
+                "security_headers.frame_options",
+                &self.security_headers.frame_options,
+            ),
+            (
+                "security_headers.referrer_policy",
+                &self.security_headers.referrer_policy,
+            ),
+            (
+                "security_headers.permissions_policy",
+                &self.security_headers.permissions_policy,
+            ),
+        ] {
+            check(
+                key,
+                actix_web::http::header::HeaderValue::from_str(value)
+                    .map(|_| ())
+                    .map_err(|_| "is not a valid header value".into()),
+            );
+        }
+        check(
+            "metrics.port",
+            match self.metrics.port {
+                // `0` asks the OS for a random port, it can't clash
+                Some(port) if port != 0 && port == self.application.port => {
+                    Err("must be different from `application.port`".into())
+                }
+                _ => Ok(()),
+            },
+        );
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidSettings(errors))
+        }
+    }
 }
 
+fn parse_url(s: &str) -> Result<(), String> {
+    reqwest::Url::parse(s)
+        .map(|_| ())
+        .map_err(|e| format!("is not a valid URL ({})", e))
+}
+
+// Browsers send `scheme://host[:port]` in the `Origin` header, without a path
+fn parse_origin(s: &str) -> Result<(), String> {
+    match reqwest::Url::parse(s) {
+        Ok(url)
+            if url.has_host() && s.trim_end_matches('/') == url.origin().ascii_serialization() =>
+        {
+            Ok(())
+        }
+        _ => Err(format!(
+            "`{}` is not a valid origin, e.g. `https://example.com`",
+            s
+        )),
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidSetting {
+    pub key: &'static str,
+    pub message: String,
+}
+
+/// Every problem found by `Settings::validate`.
+#[derive(Debug)]
+pub struct InvalidSettings(pub Vec<InvalidSetting>);
+
+impl std::fmt::Display for InvalidSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for e in &self.0 {
+            write!(f, "\n  - {}: {}", e.key, e.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidSettings {}
+
 impl EmailClientSettings {
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
@@ -81,21 +546,32 @@ impl DatabaseSettings {
     }
 }
 
+// Config key of every `Secret<String>` field.
+// Each of them can also be provided through a `<key>_file` variant,
+// e.g. `APP_DATABASE__PASSWORD_FILE=/run/secrets/db_password`.
+const SECRET_KEYS: &[&str] = &[
+    "database.password",
+    "application.hmac_secret",
+    "email_client.authorization_token",
+    "email_client.webhook_password",
+    "redis_uri",
+    "signup_protection.challenge.secret",
+];
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
-    let base_path = std::env::current_dir().expect("Failed to determine the current directory");
-    let configuration_directory = base_path.join("configuration");
+    // `APP_CONFIG_DIR` lets us mount the configuration files anywhere (e.g. a Kubernetes ConfigMap)
+    let configuration_directory = match std::env::var("APP_CONFIG_DIR") {
+        Ok(directory) => std::path::PathBuf::from(directory),
+        Err(_) => std::env::current_dir()
+            .expect("Failed to determine the current directory")
+            .join("configuration"),
+    };
 
-    // Detects the running environment
-    // Since docker doesn't see localhost as coming from the same machine we need different local and prod host address
-    // Default to `local` if unspecified.
-    let environment: Environment = std::env::var("APP_ENVIRONMENT")
-        .unwrap_or_else(|_| "local".into())
-        .try_into()
-        .expect("Failed to parse APP_ENVIRONMENT");
+    let environment = get_environment();
     let environment_filename = format!("{}.yaml", environment.as_str());
     // Initialize configuration reader
     let settings = config::Config::builder()
-        // Add configuration values from a file called either `base, local or production.yml'
+        // Add configuration values from `base.yaml` and the file named after the environment
         .add_source(config::File::from(
             configuration_directory.join("base.yaml"),
         ))
@@ -110,14 +586,51 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
                 .separator("__"),
         )
         .build()?;
+    let settings = load_secret_files(settings)?;
 
     // Try to convert the configuration values into our Settings type
     settings.try_deserialize::<Settings>()
 }
 
+// Docker and Kubernetes mount secrets as files: read the content of every `<key>_file`
+// we find and use it as the value of `<key>`, overriding any other source.
+fn load_secret_files(settings: config::Config) -> Result<config::Config, config::ConfigError> {
+    let mut builder = config::Config::builder().add_source(settings.clone());
+    for key in SECRET_KEYS {
+        let file_key = format!("{}_file", key);
+        let path = match settings.get_string(&file_key) {
+            Ok(path) => path,
+            Err(config::ConfigError::NotFound(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        let value = std::fs::read_to_string(&path).map_err(|e| {
+            config::ConfigError::Message(format!(
+                "Failed to read `{}` from {}: {}",
+                file_key, path, e
+            ))
+        })?;
+        // Editors and `echo` leave a trailing newline behind
+        builder = builder.set_override(*key, value.trim_end_matches(['\r', '\n']))?;
+    }
+    builder.build()
+}
+
+/// Detects the running environment from `APP_ENVIRONMENT`.
+pub fn get_environment() -> Environment {
+    // Since docker doesn't see localhost as coming from the same machine we need different local and prod host address
+    // Default to `local` if unspecified.
+    std::env::var("APP_ENVIRONMENT")
+        .unwrap_or_else(|_| "local".into())
+        .try_into()
+        .expect("Failed to parse APP_ENVIRONMENT")
+}
+
 /// The possible runtime environment for our application.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Environment {
     Local,
+    Test,
+    Staging,
     Production,
 }
 
@@ -125,6 +638,8 @@ impl Environment {
     pub fn as_str(&self) -> &'static str {
         match self {
             Environment::Local => "local",
+            Environment::Test => "test",
+            Environment::Staging => "staging",
             Environment::Production => "production",
         }
     }
@@ -136,11 +651,339 @@ impl TryFrom<String> for Environment {
     fn try_from(s: String) -> Result<Self, Self::Error> {
         match s.to_lowercase().as_str() {
             "local" => Ok(Self::Local),
+            "test" => Ok(Self::Test),
+            "staging" => Ok(Self::Staging),
             "production" => Ok(Self::Production),
             other => Err(format!(
-                "{} is not a supported environment. Use either `local` or `production`.",
+                "{} is not a supported environment. \
+                Use either `local`, `test`, `staging` or `production`.",
                 other
             )),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        load_secret_files, ApplicationSettings, ChallengeSettings, CorsSettings, DatabaseSettings,
+        EmailClientSettings, Environment, HealthCheckSettings, LogFormat, MetricsSettings,
+        OpenTelemetrySettings, RateLimit, SecurityHeadersSettings, Settings,
+        SignupProtectionSettings, TelemetrySettings, TrackingSettings,
+    };
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn valid_settings() -> Settings {
+        Settings {
+            database: DatabaseSettings {
+                username: "postgres".into(),
+                password: Secret::new("password".into()),
+                port: 5432,
+                host: "localhost".into(),
+                database_name: "newsletter".into(),
+                require_ssl: false,
+            },
+            application: ApplicationSettings {
+                port: 8000,
+                host: "127.0.0.1".into(),
+                base_url: "http://127.0.0.1".into(),
+                hmac_secret: Secret::new("a".repeat(64)),
+                trust_request_id_header: false,
+                shutdown_grace_period_seconds: 30,
+                scheduler_interval_seconds: 30,
+                cors: CorsSettings {
+                    allowed_origins: vec!["https://partner.example.com".into()],
+                    allowed_methods: vec!["GET".into(), "POST".into()],
+                    allowed_headers: vec!["Content-Type".into(), "Accept".into()],
+                    max_age_seconds: 3600,
+                },
+            },
+            email_client: EmailClientSettings {
+                base_url: "http://localhost".into(),
+                sender_email: "test@gmail.com".into(),
+                authorization_token: Secret::new("my-secret-token".into()),
+                timeout_milliseconds: 10000,
+                webhook_username: "postmark".into(),
+                webhook_password: Secret::new("my-webhook-password".into()),
+            },
+            redis_uri: Secret::new("redis://127.0.0.1:6379".into()),
+            metrics: MetricsSettings { port: None },
+            opentelemetry: OpenTelemetrySettings {
+                enabled: false,
+                endpoint: "http://localhost:4318".into(),
+            },
+            health_check: HealthCheckSettings {
+                probe_email_provider: false,
+            },
+            telemetry: TelemetrySettings {
+                format: LogFormat::Bunyan,
+                level: "info".into(),
+                filters: Default::default(),
+            },
+            signup_protection: SignupProtectionSettings {
+                key_prefix: "signup".into(),
+                trust_forwarded_for: false,
+                per_ip: RateLimit {
+                    max_requests: 10,
+                    window_seconds: 600,
+                },
+                per_email_domain: RateLimit {
+                    max_requests: 100,
+                    window_seconds: 3600,
+                },
+                challenge: ChallengeSettings {
+                    enabled: false,
+                    verify_url: "https://challenges.cloudflare.com/turnstile/v0/siteverify".into(),
+                    secret: Secret::new("".into()),
+                    script_url: "https://challenges.cloudflare.com/turnstile/v0/api.js".into(),
+                    widget_class: "cf-turnstile".into(),
+                    site_key: "".into(),
+                },
+            },
+            security_headers: SecurityHeadersSettings {
+                content_security_policy: "default-src 'self'; script-src 'self' 'nonce-{nonce}'"
+                    .into(),
+                frame_options: "DENY".into(),
+                referrer_policy: "strict-origin-when-cross-origin".into(),
+                permissions_policy: "camera=(), microphone=(), geolocation=()".into(),
+                hsts_max_age_seconds: 63072000,
+            },
+            tracking: TrackingSettings { enabled: true },
+        }
+    }
+
+    #[test]
+    fn valid_settings_are_accepted() {
+        assert_ok!(valid_settings().validate());
+    }
+
+    #[test]
+    fn a_63_bytes_long_hmac_secret_is_rejected() {
+        let mut settings = valid_settings();
+        settings.application.hmac_secret = Secret::new("a".repeat(63));
+        assert_err!(settings.validate());
+    }
+
+    #[test]
+    fn the_scheduler_interval_cannot_be_zero() {
+        let mut settings = valid_settings();
+        settings.application.scheduler_interval_seconds = 0;
+        assert_err!(settings.validate());
+    }
+
+    #[test]
+    fn every_invalid_value_is_reported_with_its_key() {
+        let mut settings = valid_settings();
+        settings.application.base_url = "127.0.0.1".into();
+        settings.email_client.base_url = "localhost".into();
+        settings.email_client.sender_email = "not-an-email".into();
+        settings.email_client.timeout_milliseconds = 0;
+        settings.email_client.webhook_password = Secret::new("".into());
+
+        let errors = settings.validate().unwrap_err();
+
+        let keys: Vec<_> = errors.0.iter().map(|e| e.key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "application.base_url",
+                "email_client.base_url",
+                "email_client.sender_email",
+                "email_client.timeout_milliseconds",
+                "email_client.webhook_password",
+            ]
+        );
+    }
+
+    #[test]
+    fn metrics_cannot_share_the_application_port() {
+        let mut settings = valid_settings();
+        settings.metrics.port = Some(settings.application.port);
+        assert_err!(settings.validate());
+    }
+
+    #[test]
+    fn cors_origins_must_be_bare_origins() {
+        for origin in [
+            "partner.example.com",
+            "https://partner.example.com/signup",
+            "not a url",
+        ] {
+            let mut settings = valid_settings();
+            settings.application.cors.allowed_origins = vec![origin.into()];
+            let errors = settings.validate().unwrap_err();
+            assert_eq!(errors.0[0].key, "application.cors.allowed_origins");
+        }
+        for origin in [
+            "*",
+            "https://partner.example.com:8443",
+            "http://localhost:3000/",
+        ] {
+            let mut settings = valid_settings();
+            settings.application.cors.allowed_origins = vec![origin.into()];
+            assert_ok!(settings.validate());
+        }
+    }
+
+    #[test]
+    fn invalid_cors_methods_and_headers_are_rejected() {
+        let mut settings = valid_settings();
+        settings.application.cors.allowed_methods = vec!["GET POST".into()];
+        settings.application.cors.allowed_headers = vec!["Content Type".into()];
+
+        let errors = settings.validate().unwrap_err();
+
+        let keys: Vec<_> = errors.0.iter().map(|e| e.key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "application.cors.allowed_methods",
+                "application.cors.allowed_headers"
+            ]
+        );
+    }
+
+    #[test]
+    fn cors_lists_can_be_comma_separated() {
+        let cors: CorsSettings = serde_json::from_value(serde_json::json!({
+            "allowed_origins": "https://a.example.com, https://b.example.com",
+            "allowed_methods": ["POST"],
+            "allowed_headers": "",
+            "max_age_seconds": "60",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            cors.allowed_origins,
+            vec!["https://a.example.com", "https://b.example.com"]
+        );
+        assert_eq!(cors.allowed_methods, vec!["POST"]);
+        assert!(cors.allowed_headers.is_empty());
+    }
+
+    #[test]
+    fn security_headers_must_be_valid_header_values() {
+        let mut settings = valid_settings();
+        settings.security_headers.frame_options = "DENY\r\nSet-Cookie: a=b".into();
+
+        let errors = settings.validate().unwrap_err();
+
+        assert_eq!(errors.0[0].key, "security_headers.frame_options");
+    }
+
+    #[test]
+    fn rate_limits_cannot_be_zero() {
+        let mut settings = valid_settings();
+        settings.signup_protection.per_ip.max_requests = 0;
+        settings.signup_protection.per_email_domain.window_seconds = 0;
+
+        let errors = settings.validate().unwrap_err();
+
+        let keys: Vec<_> = errors.0.iter().map(|e| e.key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "signup_protection.per_ip",
+                "signup_protection.per_email_domain"
+            ]
+        );
+    }
+
+    #[test]
+    fn an_enabled_challenge_requires_its_keys() {
+        let mut settings = valid_settings();
+        settings.signup_protection.challenge.enabled = true;
+
+        let errors = settings.validate().unwrap_err();
+
+        let keys: Vec<_> = errors.0.iter().map(|e| e.key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "signup_protection.challenge.secret",
+                "signup_protection.challenge.site_key"
+            ]
+        );
+    }
+
+    #[test]
+    fn per_module_filters_are_appended_to_the_default_level() {
+        let mut settings = valid_settings();
+        settings
+            .telemetry
+            .filters
+            .insert("sqlx".into(), "debug".into());
+        settings
+            .telemetry
+            .filters
+            .insert("zero2prod::email_client".into(), "trace".into());
+
+        assert_eq!(
+            settings.telemetry.env_filter(),
+            "info,sqlx=debug,zero2prod::email_client=trace"
+        );
+        assert_ok!(settings.validate());
+    }
+
+    #[test]
+    fn an_invalid_log_filter_is_rejected() {
+        let mut settings = valid_settings();
+        settings
+            .telemetry
+            .filters
+            .insert("sqlx".into(), "loud".into());
+
+        let errors = settings.validate().unwrap_err();
+
+        assert_eq!(errors.0[0].key, "telemetry");
+    }
+
+    #[test]
+    fn secrets_are_redacted_when_serialized() {
+        let output = serde_json::to_string(&valid_settings()).unwrap();
+        assert!(!output.contains("my-secret-token"));
+        assert!(!output.contains("my-webhook-password"));
+        assert!(!output.contains("redis://"));
+        assert!(output.contains("[REDACTED]"));
+    }
+
+    #[test]
+    fn staging_and_test_environments_are_supported() {
+        for name in ["local", "test", "staging", "production"] {
+            let environment: Environment = name.to_string().try_into().unwrap();
+            assert_eq!(environment.as_str(), name);
+        }
+    }
+
+    #[test]
+    fn secrets_are_read_from_file_variants() {
+        let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::write(&path, "from-a-file\n").unwrap();
+        let settings = config::Config::builder()
+            .set_override("database.password", "from-the-environment")
+            .unwrap()
+            .set_override("database.password_file", path.to_str().unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let settings = load_secret_files(settings).unwrap();
+
+        assert_eq!(
+            settings.get_string("database.password").unwrap(),
+            "from-a-file"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_secret_file_is_an_error() {
+        let settings = config::Config::builder()
+            .set_override("redis_uri_file", "/this/file/does/not/exist")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_err!(load_secret_files(settings));
+    }
+}
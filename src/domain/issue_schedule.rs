@@ -0,0 +1,147 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// When a newsletter issue goes out, along with the timezone the editor picked it in:
+/// "Tuesday 09:00 in Europe/Paris" stays 09:00 in Paris across daylight saving time changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IssueSchedule {
+    send_at: DateTime<Utc>,
+    timezone: Tz,
+}
+
+impl IssueSchedule {
+    /// `local_time` is the value of an `<input type="datetime-local">`, e.g. `2023-07-11T09:00`,
+    /// and `timezone` an IANA name, e.g. `Europe/Paris`.
+    /// The send time must be after `now`.
+    pub fn parse(local_time: &str, timezone: &str, now: DateTime<Utc>) -> Result<Self, String> {
+        let timezone: Tz = timezone
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} is not a known timezone, e.g. Europe/Paris", timezone))?;
+        let local_time = NaiveDateTime::parse_from_str(local_time, "%Y-%m-%dT%H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(local_time, "%Y-%m-%dT%H:%M:%S"))
+            .map_err(|_| format!("{} is not a valid date and time", local_time))?;
+        // Ambiguous when clocks go back: we take the first of the two
+        let send_at = timezone
+            .from_local_datetime(&local_time)
+            .earliest()
+            .ok_or_else(|| {
+                format!(
+                    "{} does not exist in {}: the clocks skip it",
+                    local_time.format("%Y-%m-%d %H:%M"),
+                    timezone.name()
+                )
+            })?
+            .with_timezone(&Utc);
+        if send_at <= now {
+            return Err("The send time must be in the future.".into());
+        }
+        Ok(Self { send_at, timezone })
+    }
+
+    /// Back from the database, where the timezone is stored by name.
+    /// An unknown name falls back to UTC: it only changes how the time is shown.
+    pub fn from_stored(send_at: DateTime<Utc>, timezone: &str) -> Self {
+        Self {
+            send_at,
+            timezone: timezone.parse().unwrap_or(Tz::UTC),
+        }
+    }
+
+    pub fn send_at(&self) -> DateTime<Utc> {
+        self.send_at
+    }
+
+    pub fn timezone(&self) -> &'static str {
+        self.timezone.name()
+    }
+
+    /// The value of an `<input type="datetime-local">` to reschedule it.
+    pub fn local_time(&self) -> String {
+        self.send_at
+            .with_timezone(&self.timezone)
+            .format("%Y-%m-%dT%H:%M")
+            .to_string()
+    }
+}
+
+impl std::fmt::Display for IssueSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.send_at
+                .with_timezone(&self.timezone)
+                .format("%Y-%m-%d %H:%M"),
+            self.timezone.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueSchedule;
+    use chrono::{DateTime, TimeZone, Utc};
+    use claims::{assert_err, assert_ok};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn the_local_time_is_converted_from_its_timezone() {
+        let schedule = IssueSchedule::parse("2023-07-11T09:00", "Europe/Paris", now()).unwrap();
+
+        // Paris is at UTC+2 in summer
+        assert_eq!(
+            schedule.send_at(),
+            Utc.with_ymd_and_hms(2023, 7, 11, 7, 0, 0).unwrap()
+        );
+        assert_eq!(schedule.local_time(), "2023-07-11T09:00");
+        assert_eq!(schedule.to_string(), "2023-07-11 09:00 Europe/Paris");
+    }
+
+    #[test]
+    fn seconds_are_accepted() {
+        assert_ok!(IssueSchedule::parse("2023-07-11T09:00:30", "UTC", now()));
+    }
+
+    #[test]
+    fn unknown_timezones_are_rejected() {
+        assert_err!(IssueSchedule::parse(
+            "2023-07-11T09:00",
+            "Europe/Atlantis",
+            now()
+        ));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        for local_time in ["", "tuesday", "2023-07-11", "2023-02-30T09:00"] {
+            assert_err!(IssueSchedule::parse(local_time, "UTC", now()));
+        }
+    }
+
+    #[test]
+    fn times_skipped_by_daylight_saving_are_rejected() {
+        // Clocks went from 02:00 to 03:00 in Paris on 2024-03-31
+        assert_err!(IssueSchedule::parse(
+            "2024-03-31T02:30",
+            "Europe/Paris",
+            now()
+        ));
+    }
+
+    #[test]
+    fn past_times_are_rejected() {
+        assert_err!(IssueSchedule::parse("2023-07-01T12:00", "UTC", now()));
+        assert_err!(IssueSchedule::parse("2023-06-30T09:00", "UTC", now()));
+    }
+
+    #[test]
+    fn unknown_stored_timezones_fall_back_to_utc() {
+        let schedule = IssueSchedule::from_stored(now(), "Mars/Olympus_Mons");
+
+        assert_eq!(schedule.timezone(), "UTC");
+    }
+}
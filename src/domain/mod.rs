@@ -1,7 +1,9 @@
+mod issue_schedule;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
 
-pub use new_subscriber::NewSubscriber;
+pub use issue_schedule::IssueSchedule;
+pub use new_subscriber::{FieldErrors, NewSubscriber};
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
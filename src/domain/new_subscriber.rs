@@ -1,7 +1,58 @@
 use crate::domain::SubscriberEmail;
 use crate::domain::SubscriberName;
+use std::collections::BTreeMap;
+
+/// Validation failures, keyed by the name of the offending field.
+pub type FieldErrors = BTreeMap<&'static str, String>;
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
 }
+
+impl NewSubscriber {
+    /// Validates every field instead of stopping at the first failure,
+    /// so that forms and widgets can flag all the invalid inputs at once.
+    pub fn parse(email: String, name: String) -> Result<Self, FieldErrors> {
+        let mut errors = FieldErrors::new();
+        let email = SubscriberEmail::parse(email).map_err(|e| errors.insert("email", e));
+        let name = SubscriberName::parse(name).map_err(|e| errors.insert("name", e));
+        match (email, name) {
+            (Ok(email), Ok(name)) => Ok(Self { email, name }),
+            _ => Err(errors),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NewSubscriber;
+    use claims::assert_ok;
+
+    #[test]
+    fn valid_fields_are_accepted() {
+        assert_ok!(NewSubscriber::parse(
+            "ursula@domain.com".into(),
+            "Ursula Le Guin".into()
+        ));
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let errors = NewSubscriber::parse("ursula".into(), "".into())
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors.keys().copied().collect::<Vec<_>>(),
+            ["email", "name"]
+        );
+    }
+
+    #[test]
+    fn only_the_invalid_field_is_reported() {
+        let errors = NewSubscriber::parse("ursula@domain.com".into(), "(Ursula)".into())
+            .err()
+            .unwrap();
+        assert_eq!(errors.keys().copied().collect::<Vec<_>>(), ["name"]);
+    }
+}
@@ -11,6 +11,13 @@ impl SubscriberEmail {
             Err(format!("{} is not a valid subscriber email", s))
         }
     }
+
+    /// What comes after the `@`, lowercased.
+    pub fn domain(&self) -> String {
+        // `parse` guarantees there is an `@`
+        let (_, domain) = self.0.rsplit_once('@').unwrap();
+        domain.to_lowercase()
+    }
 }
 
 impl std::fmt::Display for SubscriberEmail {
@@ -66,6 +73,12 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn the_domain_is_lowercased() {
+        let email = SubscriberEmail::parse("ursula@Domain.COM".into()).unwrap();
+        assert_eq!(email.domain(), "domain.com");
+    }
+
     // Fails with type error ="the trait `rand_core::RngCore` is not implemented for `Gen`
     // Leave singular email generation test instead above
 
@@ -12,7 +12,7 @@ impl SubscriberName {
     /// Returns an instance of `SubscriberName` if the input satisfies all
     /// our validation constrains on subscriber names.
     /// It panics otherwise.
-
+    //
     // `parse` is the only way to build an instance of `SubscriberName` outside of the domain module.
     // We can therefore assert that any instance of `SubscriberName` will satisfy all our validation constrains.
     // We have made it impossible for an instance of `SubscriberName` to violate those constrains.
@@ -1,4 +1,8 @@
 use crate::domain::SubscriberEmail;
+use crate::metrics::record_email_sent;
+use crate::request_id::RequestId;
+use crate::telemetry::inject_trace_context;
+use reqwest::header::HeaderMap;
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
 
@@ -28,13 +32,25 @@ impl EmailClient {
         }
     }
 
+    /// Succeeds if the email provider answers, whatever the status code.
+    pub async fn check_reachable(&self) -> Result<(), reqwest::Error> {
+        self.http_client.get(&self.base_url).send().await?;
+        Ok(())
+    }
+
+    /// Returns the id the provider gave the message, if it answered with one:
+    /// its webhooks refer to the message by it.
+    #[tracing::instrument(
+        name = "Send an email",
+        skip(self, recipient, subject, html_content, text_content)
+    )]
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<Option<String>, reqwest::Error> {
         // You can do better using `reqwest::Url::join` if you change
         // `base_url`'s type from `String` to `reqwest::Url`.
         let url = format!("{}/email", self.base_url);
@@ -44,18 +60,33 @@ impl EmailClient {
             subject,
             html_body: html_content,
             text_body: text_content,
+            // Shows up in Postmark's activity feed and in its webhook payloads
+            metadata: RequestId::current().map(|request_id| Metadata { request_id }),
         };
-        self.http_client
+        // Let the collector link the provider call to the span that triggered it
+        let mut trace_headers = HeaderMap::new();
+        inject_trace_context(&mut trace_headers);
+        let start = std::time::Instant::now();
+        let outcome = self
+            .http_client
             .post(&url)
             .header(
                 "X-Postmark-Server-Token",
                 self.authorization_token.expose_secret(),
             )
+            .headers(trace_headers)
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
-        Ok(())
+            .await
+            .and_then(|response| response.error_for_status());
+        record_email_sent(outcome.is_ok(), start.elapsed());
+        // The email is sent even if the answer cannot be read
+        let message_id = outcome?
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .map(|response| response.message_id);
+        Ok(message_id)
     }
 }
 
@@ -68,6 +99,19 @@ struct SendEmailRequest<'a> {
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+#[derive(serde::Serialize)]
+struct Metadata {
+    request_id: RequestId,
+}
+
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: String,
 }
 
 #[cfg(test)]
@@ -78,7 +122,11 @@ mod test {
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::{Fake, Faker};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::TracerProvider;
     use secrecy::Secret;
+    use tracing_subscriber::layer::SubscriberExt;
     use wiremock::matchers::{any, header, header_exists, method, path};
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
 
@@ -154,6 +202,34 @@ mod test {
         // Mock expectations are checked on drop
     }
 
+    #[tokio::test]
+    async fn send_email_propagates_the_trace_context() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let subscriber_email = email();
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        // No exporter: we only need spans to carry a trace context
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        Mock::given(header_exists("traceparent"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&subscriber_email, &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
     #[tokio::test]
     async fn send_email_succeeds_if_the_server_returns_200() {
         // Arrange
@@ -181,6 +257,38 @@ mod test {
         assert_ok!(outcome);
     }
 
+    #[tokio::test]
+    async fn send_email_returns_the_message_id_given_by_the_server() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let subscriber_email = email();
+
+        // As documented by Postmark
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "To": subscriber_email.as_ref(),
+                "SubmittedAt": "2023-08-15T09:00:00.0000000Z",
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                "ErrorCode": 0,
+                "Message": "OK"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&subscriber_email, &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_eq!(
+            outcome.unwrap().as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+    }
+
     #[tokio::test]
     async fn send_email_fails_if_the_server_returns_500() {
         // Arrange
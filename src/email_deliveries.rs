@@ -0,0 +1,159 @@
+use crate::domain::SubscriberEmail;
+use crate::request_id::RequestId;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct EmailDelivery {
+    pub recipient: String,
+    pub succeeded: bool,
+    pub request_id: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DeliverySummary {
+    pub succeeded: i64,
+    pub failed: i64,
+}
+
+/// Keep a record of every email handed over to the email provider,
+/// along with the request that triggered it.
+/// `message_id` is the id the provider gave it, see `EmailClient::send_email`.
+#[tracing::instrument(
+    name = "Record an email delivery",
+    skip(pool, recipient, subject, request_id)
+)]
+pub async fn record_email_delivery(
+    pool: &PgPool,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    newsletter_issue_id: Option<Uuid>,
+    request_id: Option<&RequestId>,
+    succeeded: bool,
+    message_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_deliveries (
+            delivery_id, recipient, subject, newsletter_issue_id, succeeded, request_id, sent_at,
+            message_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        Uuid::new_v4(),
+        recipient.as_ref(),
+        subject,
+        newsletter_issue_id,
+        succeeded,
+        request_id.map(RequestId::as_str),
+        Utc::now(),
+        message_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get the deliveries of a newsletter issue", skip(pool))]
+pub async fn get_issue_deliveries(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<EmailDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailDelivery,
+        r#"
+        SELECT recipient, succeeded, request_id, sent_at
+        FROM email_deliveries
+        WHERE newsletter_issue_id = $1
+        ORDER BY sent_at
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Summarise the deliveries of a newsletter issue", skip(pool))]
+pub async fn get_issue_delivery_summary(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<DeliverySummary, sqlx::Error> {
+    sqlx::query_as!(
+        DeliverySummary,
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE succeeded) AS "succeeded!",
+            COUNT(*) FILTER (WHERE NOT succeeded) AS "failed!"
+        FROM email_deliveries
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// What the email provider reported about a delivery, through its webhooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryEventKind {
+    Delivery,
+    Bounce,
+    SpamComplaint,
+    Open,
+}
+
+impl DeliveryEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryEventKind::Delivery => "delivery",
+            DeliveryEventKind::Bounce => "bounce",
+            DeliveryEventKind::SpamComplaint => "spam_complaint",
+            DeliveryEventKind::Open => "open",
+        }
+    }
+}
+
+/// Attach an event to the delivery of the message the provider gave `message_id` to.
+/// Returns `false` if there is no such delivery, e.g. for a message sent before
+/// message ids were recorded. An event that is already stored is not stored twice.
+#[tracing::instrument(
+    name = "Record an email delivery event",
+    skip(transaction, details, occurred_at)
+)]
+pub async fn record_delivery_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    message_id: &str,
+    kind: DeliveryEventKind,
+    details: Option<&str>,
+    occurred_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let delivery = sqlx::query!(
+        r#"SELECT delivery_id FROM email_deliveries WHERE message_id = $1"#,
+        message_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(delivery) = delivery else {
+        return Ok(false);
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO email_delivery_events (
+            event_id, delivery_id, kind, details, occurred_at, received_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        delivery.delivery_id,
+        kind.as_str(),
+        details,
+        occurred_at,
+        Utc::now()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(true)
+}
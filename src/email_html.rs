@@ -0,0 +1,296 @@
+use ammonia::url::{ParseError, Url};
+use ammonia::{Builder, UrlRelative, UrlRelativeEvaluate};
+use kuchikiki::iter::NodeIterator;
+use kuchikiki::traits::TendrilSink;
+use kuchikiki::{Selector, Selectors, Specificity};
+use std::borrow::Cow;
+
+/// Gmail hides the end of messages whose HTML is bigger than this,
+/// behind a "View entire message" link.
+pub const GMAIL_CLIPPING_BYTES: usize = 102 * 1024;
+
+/// Prepares the HTML of newsletter issues for mail clients, before the merge tags are filled in:
+/// - `<style>` rules are inlined into `style` attributes, since many clients drop stylesheets;
+/// - scripts, forms, external stylesheets, event handlers and dangerous URLs are removed;
+/// - relative URLs are made absolute against the base URL of the application.
+pub struct EmailHtml {
+    sanitizer: Builder<'static>,
+}
+
+impl EmailHtml {
+    pub fn new(base_url: &str) -> Result<Self, ParseError> {
+        let base_url = Url::parse(base_url)?;
+        let mut sanitizer = Builder::default();
+        sanitizer
+            // Still common in emails, for clients that ignore CSS
+            .add_tags(["font"])
+            .add_generic_attributes([
+                "style", "align", "valign", "width", "height", "bgcolor", "border", "dir",
+            ])
+            .add_tag_attributes("table", ["cellpadding", "cellspacing"])
+            .add_tag_attributes("font", ["color", "face", "size"])
+            .add_clean_content_tags(["title"])
+            .url_relative(UrlRelative::Custom(Box::new(AbsoluteUrls { base_url })))
+            .attribute_filter(|_element, attribute, value| {
+                if attribute == "style" && is_dangerous_css(value) {
+                    None
+                } else {
+                    Some(Cow::Borrowed(value))
+                }
+            });
+        Ok(Self { sanitizer })
+    }
+
+    pub fn prepare(&self, html: &str) -> String {
+        let html = if html.to_ascii_lowercase().contains("<style") {
+            Cow::Owned(inline_styles(html))
+        } else {
+            Cow::Borrowed(html)
+        };
+        self.sanitizer.clean(&html).to_string()
+    }
+}
+
+struct AbsoluteUrls {
+    base_url: Url,
+}
+
+impl UrlRelativeEvaluate for AbsoluteUrls {
+    fn evaluate<'a>(&self, url: &'a str) -> Option<Cow<'a, str>> {
+        // Anchors within the email, and merge tags, e.g. `{{ unsubscribe_url }}`
+        if url.starts_with('#') || url.contains("{{") {
+            return Some(Cow::Borrowed(url));
+        }
+        self.base_url
+            .join(url)
+            .ok()
+            .map(|url| Cow::Owned(url.to_string()))
+    }
+}
+
+/// Shown to admins: the rest of the issue would be one click away for Gmail users.
+pub fn clipping_warning(prepared_html: &str) -> Option<String> {
+    let size = prepared_html.len();
+    (size > GMAIL_CLIPPING_BYTES).then(|| {
+        format!(
+            "The HTML body weighs {} KB: Gmail clips messages over {} KB, hiding the rest behind a link.",
+            size.div_ceil(1024),
+            GMAIL_CLIPPING_BYTES / 1024
+        )
+    })
+}
+
+// Old Internet Explorer and Firefox ran code from CSS
+fn is_dangerous_css(value: &str) -> bool {
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    [
+        "expression(",
+        "javascript:",
+        "behavior:",
+        "-moz-binding",
+        "@import",
+    ]
+    .iter()
+    .any(|pattern| value.contains(pattern))
+}
+
+struct CssRule {
+    selector: Selector,
+    specificity: Specificity,
+    declarations: String,
+}
+
+// Applies the rules of every `<style>` element, then removes them.
+// Rules that cannot be inlined, e.g. `@media` queries, are dropped:
+// the elements they target keep their other styles.
+// Like in browsers, the most specific rules win, then the last ones;
+// `style` attributes win over every rule. `!important` is not taken into account.
+fn inline_styles(html: &str) -> String {
+    let document = kuchikiki::parse_html().one(html);
+    let mut css = String::new();
+    if let Ok(styles) = document.select("style") {
+        for style in styles.collect::<Vec<_>>() {
+            css.push_str(&style.text_contents());
+            css.push('\n');
+            style.as_node().detach();
+        }
+    }
+    let mut rules = parse_css(&css);
+    // Stable: rules of the same specificity keep their source order
+    rules.sort_by_key(|rule| rule.specificity);
+    for element in document.descendants().elements() {
+        let mut declarations: Vec<&str> = rules
+            .iter()
+            .filter(|rule| rule.selector.matches(&element))
+            .map(|rule| rule.declarations.as_str())
+            .collect();
+        if declarations.is_empty() {
+            continue;
+        }
+        let mut attributes = element.attributes.borrow_mut();
+        if let Some(style) = attributes.get("style") {
+            declarations.push(style.trim().trim_end_matches(';'));
+        }
+        let style = declarations.join("; ");
+        attributes.insert("style", style);
+    }
+    document.to_string()
+}
+
+fn parse_css(css: &str) -> Vec<CssRule> {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let body = &rest[open + 1..];
+        if prelude.starts_with('@') {
+            // Skip the whole block, along with the rules nested in it
+            let mut depth = 1;
+            let mut end = body.len();
+            for (i, c) in body.char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    end = i + 1;
+                    break;
+                }
+            }
+            rest = &body[end..];
+            continue;
+        }
+        let close = body.find('}').unwrap_or(body.len());
+        let declarations = body[..close]
+            .split(';')
+            .map(str::trim)
+            .filter(|declaration| !declaration.is_empty())
+            .collect::<Vec<_>>()
+            .join("; ");
+        // Unsupported selectors, e.g. `a:hover`, are ignored
+        if let Ok(selectors) = Selectors::compile(prelude) {
+            for selector in selectors.0 {
+                rules.push(CssRule {
+                    specificity: selector.specificity(),
+                    selector,
+                    declarations: declarations.clone(),
+                });
+            }
+        }
+        rest = body.get(close + 1..).unwrap_or_default();
+    }
+    rules
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clipping_warning, EmailHtml, GMAIL_CLIPPING_BYTES};
+
+    fn email_html() -> EmailHtml {
+        EmailHtml::new("https://newsletter.example.com").unwrap()
+    }
+
+    #[test]
+    fn dangerous_elements_and_attributes_are_removed() {
+        let html = r#"<link rel="stylesheet" href="https://example.com/style.css"><script>alert(1)</script><p onclick="alert(2)">Hi <a href="javascript:alert(3)">there</a></p><form action="/subscriptions"><input name="email"></form>"#;
+
+        assert_eq!(
+            email_html().prepare(html),
+            r#"<p>Hi <a rel="noopener noreferrer">there</a></p>"#
+        );
+    }
+
+    #[test]
+    fn style_rules_are_inlined() {
+        let html = r#"<html><head><title>Issue</title><style>
+            /* Brand colours */
+            p { color: #333; margin: 0 }
+            .intro { color: teal; }
+            p.intro { font-weight: bold }
+            #main, h1 { font-size: 20px; }
+            a:hover { color: red }
+            @media (max-width: 600px) { p { font-size: 12px } }
+            </style></head><body><h1>Title</h1><p class="intro" style="margin: 4px;">Intro</p><p>Body</p><div id="main">Main</div></body></html>"#;
+
+        assert_eq!(
+            email_html().prepare(html),
+            "<h1 style=\"font-size: 20px\">Title</h1>\
+            <p style=\"color: #333; margin: 0; color: teal; font-weight: bold; margin: 4px\">Intro</p>\
+            <p style=\"color: #333; margin: 0\">Body</p>\
+            <div style=\"font-size: 20px\">Main</div>"
+        );
+    }
+
+    #[test]
+    fn styles_that_run_code_are_removed() {
+        let html =
+            r#"<p style="width: expression(alert(1))">Hi</p><p style="color: red">there</p>"#;
+
+        assert_eq!(
+            email_html().prepare(html),
+            r#"<p>Hi</p><p style="color: red">there</p>"#
+        );
+    }
+
+    #[test]
+    fn relative_urls_are_made_absolute() {
+        let html = r##"<a href="/archive">Archive</a><img src="images/logo.png"><a href="#top">Top</a><a href="https://example.com/">Elsewhere</a>"##;
+
+        assert_eq!(
+            email_html().prepare(html),
+            "<a href=\"https://newsletter.example.com/archive\" rel=\"noopener noreferrer\">Archive</a>\
+            <img src=\"https://newsletter.example.com/images/logo.png\">\
+            <a href=\"#top\" rel=\"noopener noreferrer\">Top</a>\
+            <a href=\"https://example.com/\" rel=\"noopener noreferrer\">Elsewhere</a>"
+        );
+    }
+
+    #[test]
+    fn merge_tags_are_kept() {
+        let html = r#"<p>Hi {{ name | default: there }}</p><a href="{{ unsubscribe_url }}">Unsubscribe</a>"#;
+
+        assert_eq!(
+            email_html().prepare(html),
+            r#"<p>Hi {{ name | default: there }}</p><a href="{{ unsubscribe_url }}" rel="noopener noreferrer">Unsubscribe</a>"#
+        );
+    }
+
+    #[test]
+    fn email_layouts_are_kept() {
+        let html = r##"<table width="100%" cellpadding="0" bgcolor="#ffffff"><tr><td align="center" valign="top"><font color="#333333">Hi</font></td></tr></table>"##;
+
+        assert_eq!(
+            email_html().prepare(html),
+            r##"<table width="100%" cellpadding="0" bgcolor="#ffffff"><tbody><tr><td align="center" valign="top"><font color="#333333">Hi</font></td></tr></tbody></table>"##
+        );
+    }
+
+    #[test]
+    fn bodies_over_the_gmail_clipping_size_get_a_warning() {
+        assert_eq!(clipping_warning(&"a".repeat(GMAIL_CLIPPING_BYTES)), None);
+        assert_eq!(
+            clipping_warning(&"a".repeat(GMAIL_CLIPPING_BYTES + 1)).unwrap(),
+            "The HTML body weighs 103 KB: Gmail clips messages over 102 KB, hiding the rest behind a link."
+        );
+    }
+}
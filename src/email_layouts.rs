@@ -0,0 +1,391 @@
+use crate::merge_tags::{MergeField, MergeTemplate};
+use crate::routes::error_chain_fmt;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Where the body of an issue goes in a layout.
+pub const CONTENT_TAG: &str = "{{ content }}";
+
+/// What the form to create a layout starts from.
+pub const DEFAULT_HTML_TEMPLATE: &str = r#"<html>
+<head>
+<style>
+  body { font-family: Helvetica, Arial, sans-serif; color: #333333; }
+  .footer { font-size: 12px; color: #888888; }
+</style>
+</head>
+<body>
+<h1>Our newsletter</h1>
+{{ content }}
+<div class="footer">
+  <p>Our newsletter, 1 Example Street, Springfield</p>
+  <p>You are receiving this email as {{ email }}. <a href="{{ unsubscribe_url }}">Unsubscribe</a></p>
+</div>
+</body>
+</html>"#;
+
+pub const DEFAULT_TEXT_TEMPLATE: &str = "Our newsletter
+
+{{ content }}
+
+--
+Our newsletter, 1 Example Street, Springfield
+You are receiving this email as {{ email }}. Unsubscribe: {{ unsubscribe_url }}";
+
+/// One version of a layout: the header, footer and branding every issue using it is wrapped in.
+/// Versions are never edited, so that past issues keep looking the way they were sent.
+#[derive(Debug)]
+pub struct EmailLayout {
+    pub layout_id: Uuid,
+    pub name: String,
+    pub version: i32,
+    pub html_template: String,
+    pub text_template: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailLayout {
+    pub fn wrap_html(&self, html_content: &str) -> String {
+        wrap(&self.html_template, html_content)
+    }
+
+    pub fn wrap_text(&self, text_content: &str) -> String {
+        wrap(&self.text_template, text_content)
+    }
+}
+
+/// For the list of layouts.
+pub struct LayoutSummary {
+    pub layout_id: Uuid,
+    pub name: String,
+    pub latest_version: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// For the history of a layout.
+pub struct LayoutVersionSummary {
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    /// How many issues were sent with it.
+    pub issue_count: i64,
+}
+
+/// Both templates need exactly one `{{ content }}`, and a `{{ unsubscribe_url }}`
+/// since every issue sent with the layout relies on it for its unsubscribe link.
+/// The other tags are the ones of the issues, e.g. `{{ name }}`.
+pub fn check_layout(html_template: &str, text_template: &str) -> Result<(), String> {
+    for (template, version) in [(html_template, "HTML"), (text_template, "plain text")] {
+        let content_tags = find_content_tags(template);
+        if content_tags.len() != 1 {
+            return Err(format!(
+                "The {} version of the layout must contain `{}` exactly once.",
+                version, CONTENT_TAG
+            ));
+        }
+        let parsed = MergeTemplate::parse(&wrap(template, "")).map_err(|e| e.to_string())?;
+        if !parsed.uses(MergeField::UnsubscribeUrl) {
+            return Err(format!(
+                "The {} version of the layout must contain {}.",
+                version,
+                MergeField::UnsubscribeUrl
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Replaces the first `{{ content }}`, however it is spaced, e.g. `{{content}}`.
+fn wrap(template: &str, content: &str) -> String {
+    match find_content_tags(template).first() {
+        Some(&(start, end)) => format!("{}{}{}", &template[..start], content, &template[end..]),
+        None => template.to_owned(),
+    }
+}
+
+// The byte ranges of every `{{ content }}`
+fn find_content_tags(template: &str) -> Vec<(usize, usize)> {
+    let mut tags = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = template[offset..].find("{{") {
+        let start = offset + start;
+        let Some(length) = template[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + length + 2;
+        if template[start + 2..end - 2].trim() == "content" {
+            tags.push((start, end));
+        }
+        offset = end;
+    }
+    tags
+}
+
+#[tracing::instrument(
+    name = "Create an email layout",
+    skip(pool, html_template, text_template)
+)]
+pub async fn create_layout(
+    pool: &PgPool,
+    name: &str,
+    html_template: &str,
+    text_template: &str,
+) -> Result<Uuid, anyhow::Error> {
+    let layout_id = Uuid::new_v4();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO email_layouts (layout_id, name, created_at)
+        VALUES ($1, $2, now())
+        "#,
+        layout_id,
+        name,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store a new email layout.")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO email_layout_versions (layout_id, version, html_template, text_template, created_at)
+        VALUES ($1, 1, $2, $3, now())
+        "#,
+        layout_id,
+        html_template,
+        text_template,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the first version of an email layout.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new email layout.")?;
+    Ok(layout_id)
+}
+
+/// Returns the number of the new version.
+#[tracing::instrument(
+    name = "Add a version to an email layout",
+    skip(pool, html_template, text_template)
+)]
+pub async fn add_layout_version(
+    pool: &PgPool,
+    layout_id: Uuid,
+    html_template: &str,
+    text_template: &str,
+) -> Result<i32, anyhow::Error> {
+    let version = sqlx::query_scalar!(
+        r#"
+        INSERT INTO email_layout_versions (layout_id, version, html_template, text_template, created_at)
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, now()
+        FROM email_layout_versions
+        WHERE layout_id = $1
+        RETURNING version
+        "#,
+        layout_id,
+        html_template,
+        text_template,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to store a new version of an email layout.")?;
+    Ok(version)
+}
+
+/// Most recently edited first.
+#[tracing::instrument(name = "List email layouts", skip(pool))]
+pub async fn list_layouts(pool: &PgPool) -> Result<Vec<LayoutSummary>, anyhow::Error> {
+    let layouts = sqlx::query_as!(
+        LayoutSummary,
+        r#"
+        SELECT l.layout_id, l.name,
+            MAX(v.version) AS "latest_version!", MAX(v.created_at) AS "updated_at!"
+        FROM email_layouts l
+        JOIN email_layout_versions v ON v.layout_id = l.layout_id
+        GROUP BY l.layout_id, l.name
+        ORDER BY MAX(v.created_at) DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the email layouts.")?;
+    Ok(layouts)
+}
+
+/// The latest version, unless `version` is set.
+#[tracing::instrument(name = "Get an email layout", skip(pool))]
+pub async fn get_layout(
+    pool: &PgPool,
+    layout_id: Uuid,
+    version: Option<i32>,
+) -> Result<Option<EmailLayout>, anyhow::Error> {
+    let layout = sqlx::query_as!(
+        EmailLayout,
+        r#"
+        SELECT l.layout_id, l.name, v.version, v.html_template, v.text_template, v.created_at
+        FROM email_layouts l
+        JOIN email_layout_versions v ON v.layout_id = l.layout_id
+        WHERE l.layout_id = $1 AND ($2::INT IS NULL OR v.version = $2)
+        ORDER BY v.version DESC
+        LIMIT 1
+        "#,
+        layout_id,
+        version,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve an email layout.")?;
+    Ok(layout)
+}
+
+/// Most recent first.
+#[tracing::instrument(name = "List the versions of an email layout", skip(pool))]
+pub async fn list_layout_versions(
+    pool: &PgPool,
+    layout_id: Uuid,
+) -> Result<Vec<LayoutVersionSummary>, anyhow::Error> {
+    let versions = sqlx::query_as!(
+        LayoutVersionSummary,
+        r#"
+        SELECT v.version, v.created_at, COUNT(i.newsletter_issue_id) AS "issue_count!"
+        FROM email_layout_versions v
+        LEFT JOIN newsletter_issues i
+            ON i.layout_id = v.layout_id AND i.layout_version = v.version
+        WHERE v.layout_id = $1
+        GROUP BY v.version, v.created_at
+        ORDER BY v.version DESC
+        "#,
+        layout_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the versions of an email layout.")?;
+    Ok(versions)
+}
+
+#[derive(thiserror::Error)]
+pub enum LayoutChoiceError {
+    #[error("There is no such layout.")]
+    UnknownLayout,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for LayoutChoiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// The value of the layout `<select>` of the issue forms: empty for no layout.
+pub async fn parse_layout_choice(
+    pool: &PgPool,
+    value: &str,
+) -> Result<Option<Uuid>, LayoutChoiceError> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    let layout_id = Uuid::parse_str(value.trim()).map_err(|_| LayoutChoiceError::UnknownLayout)?;
+    if layout_exists(pool, layout_id).await? {
+        Ok(Some(layout_id))
+    } else {
+        Err(LayoutChoiceError::UnknownLayout)
+    }
+}
+
+#[tracing::instrument(name = "Check that an email layout exists", skip(pool))]
+pub async fn layout_exists(pool: &PgPool, layout_id: Uuid) -> Result<bool, anyhow::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM email_layouts WHERE layout_id = $1) AS "exists!""#,
+        layout_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up an email layout.")?;
+    Ok(exists)
+}
+
+/// The version the issue was sent with, or the latest one if it has not been sent yet.
+#[tracing::instrument(name = "Get the layout of a newsletter issue", skip(pool))]
+pub async fn get_issue_layout(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<EmailLayout>, anyhow::Error> {
+    let layout = sqlx::query_as!(
+        EmailLayout,
+        r#"
+        SELECT l.layout_id, l.name, v.version, v.html_template, v.text_template, v.created_at
+        FROM newsletter_issues i
+        JOIN email_layouts l ON l.layout_id = i.layout_id
+        JOIN email_layout_versions v ON v.layout_id = l.layout_id
+        WHERE i.newsletter_issue_id = $1 AND v.version = COALESCE(
+            i.layout_version,
+            (SELECT MAX(version) FROM email_layout_versions WHERE layout_id = i.layout_id)
+        )
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the layout of a newsletter issue.")?;
+    Ok(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_layout, wrap, DEFAULT_HTML_TEMPLATE, DEFAULT_TEXT_TEMPLATE};
+
+    #[test]
+    fn the_default_layout_is_valid() {
+        assert_eq!(
+            check_layout(DEFAULT_HTML_TEMPLATE, DEFAULT_TEXT_TEMPLATE),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn the_content_goes_in_place_of_its_tag() {
+        let template = "<header>Hi</header>{{content}}<footer>{{ unsubscribe_url }}</footer>";
+
+        assert_eq!(
+            wrap(template, "<p>Body</p>"),
+            "<header>Hi</header><p>Body</p><footer>{{ unsubscribe_url }}</footer>"
+        );
+    }
+
+    #[test]
+    fn layouts_need_exactly_one_content_tag() {
+        let text = "{{ content }} {{ unsubscribe_url }}";
+
+        assert_eq!(
+            check_layout("<a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>", text),
+            Err("The HTML version of the layout must contain `{{ content }}` exactly once.".into())
+        );
+        assert_eq!(
+            check_layout("{{ content }}{{ content }}{{ unsubscribe_url }}", text),
+            Err("The HTML version of the layout must contain `{{ content }}` exactly once.".into())
+        );
+    }
+
+    #[test]
+    fn layouts_need_an_unsubscribe_link() {
+        assert_eq!(
+            check_layout("{{ content }} {{ unsubscribe_url }}", "{{ content }}"),
+            Err(
+                "The plain text version of the layout must contain `{{ unsubscribe_url }}`.".into()
+            )
+        );
+    }
+
+    #[test]
+    fn layouts_only_know_the_tags_of_issues() {
+        assert_eq!(
+            check_layout("{{ content }} {{ unsubscribe_url }} {{ adress }}", "{{ content }}"),
+            Err("`{{ adress }}` is not a known merge tag: use `{{ name }}`, `{{ email }}`, `{{ unsubscribe_url }}` or `{{ view_in_browser_url }}`".into())
+        );
+    }
+}
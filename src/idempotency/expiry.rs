@@ -0,0 +1,40 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// Clients retry within seconds or minutes: two days is plenty.
+const SAVED_RESPONSE_TTL_HOURS: i64 = 48;
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Delete expired saved responses every hour until `shutdown` is triggered.
+/// A deletion in progress is never interrupted.
+pub async fn run_expiry_worker_until_stopped(pool: PgPool, shutdown: CancellationToken) {
+    loop {
+        if let Err(e) = delete_expired_responses(&pool).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to delete expired idempotency records",
+            );
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(EXPIRY_INTERVAL) => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Delete expired idempotency records", skip(pool))]
+async fn delete_expired_responses(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM idempotency
+        WHERE created_at < $1
+        "#,
+        Utc::now() - chrono::Duration::hours(SAVED_RESPONSE_TTL_HOURS)
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
@@ -1,6 +1,8 @@
+mod expiry;
 mod key;
 mod persistence;
 
+pub use expiry::run_expiry_worker_until_stopped;
 pub use key::IdempotencyKey;
 pub use persistence::get_saved_response;
 pub use persistence::save_response;
@@ -76,14 +76,15 @@ pub async fn save_response(
 
     sqlx::query_unchecked!(
         r#"
-        UPDATE idempotency
-        SET 
-            response_status_code = $3, 
-            response_headers = $4,
-            response_body = $5
-        WHERE
-            user_id = $1 AND
-            idempotency_key = $2
+        INSERT INTO idempotency (
+            user_id,
+            idempotency_key,
+            response_status_code,
+            response_headers,
+            response_body,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now())
         "#,
         user_id,
         idempotency_key.as_ref(),
@@ -0,0 +1,155 @@
+use chrono::{NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What a recipient did with a tracked newsletter issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueEventKind {
+    Open,
+    Click,
+    Unsubscribe,
+}
+
+impl IssueEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueEventKind::Open => "open",
+            IssueEventKind::Click => "click",
+            IssueEventKind::Unsubscribe => "unsubscribe",
+        }
+    }
+}
+
+/// The recipients who did something with an issue for the first time on `day` (UTC).
+/// Summed over the days, they are the totals of the issue.
+#[derive(Debug)]
+pub struct DailyIssueStats {
+    pub day: NaiveDate,
+    pub delivered: i64,
+    pub opened: i64,
+    pub clicked: i64,
+    pub unsubscribed: i64,
+}
+
+#[derive(Debug)]
+pub struct LinkClicks {
+    pub url: String,
+    pub subscribers: i64,
+    pub clicks: i64,
+}
+
+pub struct IssueStats {
+    pub days: Vec<DailyIssueStats>,
+    /// Most clicked first.
+    pub links: Vec<LinkClicks>,
+}
+
+impl IssueStats {
+    pub fn delivered(&self) -> i64 {
+        self.days.iter().map(|day| day.delivered).sum()
+    }
+
+    pub fn opened(&self) -> i64 {
+        self.days.iter().map(|day| day.opened).sum()
+    }
+
+    pub fn clicked(&self) -> i64 {
+        self.days.iter().map(|day| day.clicked).sum()
+    }
+
+    pub fn unsubscribed(&self) -> i64 {
+        self.days.iter().map(|day| day.unsubscribed).sum()
+    }
+}
+
+/// Unknown subscribers and issues are ignored, e.g. subscribers that have been deleted
+/// since: there is nothing to record it against.
+#[tracing::instrument(name = "Record a newsletter issue event", skip(pool, url))]
+pub async fn record_issue_event(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    kind: IssueEventKind,
+    url: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_events (
+            event_id, newsletter_issue_id, subscriber_id, kind, url, occurred_at
+        )
+        SELECT $1, $2, id, $4, $5, $6
+        FROM subscriptions
+        WHERE id = $3
+            AND EXISTS (SELECT 1 FROM newsletter_issues WHERE newsletter_issue_id = $2)
+        "#,
+        Uuid::new_v4(),
+        newsletter_issue_id,
+        subscriber_id,
+        kind.as_str(),
+        url,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get the stats of a newsletter issue", skip(pool))]
+pub async fn get_issue_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<IssueStats, sqlx::Error> {
+    // A click counts as an open too: many clients do not load the pixel
+    let days = sqlx::query_as!(
+        DailyIssueStats,
+        r#"
+        WITH activity AS (
+            SELECT 'delivered' AS kind, recipient AS who, sent_at AS occurred_at
+            FROM email_deliveries
+            WHERE newsletter_issue_id = $1 AND succeeded
+            UNION ALL
+            SELECT CASE WHEN kind = 'click' THEN 'open' ELSE kind END,
+                subscriber_id::text, occurred_at
+            FROM newsletter_issue_events
+            WHERE newsletter_issue_id = $1
+            UNION ALL
+            SELECT kind, subscriber_id::text, occurred_at
+            FROM newsletter_issue_events
+            WHERE newsletter_issue_id = $1 AND kind = 'click'
+        ), firsts AS (
+            SELECT kind, who, MIN(occurred_at) AS occurred_at
+            FROM activity
+            GROUP BY kind, who
+        )
+        SELECT
+            (occurred_at AT TIME ZONE 'UTC')::date AS "day!",
+            COUNT(*) FILTER (WHERE kind = 'delivered') AS "delivered!",
+            COUNT(*) FILTER (WHERE kind = 'open') AS "opened!",
+            COUNT(*) FILTER (WHERE kind = 'click') AS "clicked!",
+            COUNT(*) FILTER (WHERE kind = 'unsubscribe') AS "unsubscribed!"
+        FROM firsts
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    let links = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT
+            url AS "url!",
+            COUNT(DISTINCT subscriber_id) AS "subscribers!",
+            COUNT(*) AS "clicks!"
+        FROM newsletter_issue_events
+        WHERE newsletter_issue_id = $1 AND kind = 'click'
+        GROUP BY url
+        ORDER BY 2 DESC, 1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(IssueStats { days, links })
+}
@@ -0,0 +1,246 @@
+use crate::email_html::EmailHtml;
+use crate::email_layouts::EmailLayout;
+use crate::merge_tags::{
+    EmailTemplates, MergeField, MergeTagError, MergeTemplate, MergeValues, PersonalisedEmail,
+    ISSUE_TAGS,
+};
+use crate::newsletter_issues::NewsletterIssue;
+use crate::tracking_links::TrackingLinks;
+use crate::unsubscribe_links::UnsubscribeLinks;
+use ammonia::url::ParseError;
+use secrecy::Secret;
+use uuid::Uuid;
+
+// Added on top of public issues that do not place `{{ view_in_browser_url }}` themselves
+const VIEW_IN_BROWSER_HTML: &str =
+    "<p><a href=\"{{ view_in_browser_url }}\">View this email in your browser</a></p>\n";
+const VIEW_IN_BROWSER_TEXT: &str = "View this email in your browser: {{ view_in_browser_url }}\n\n";
+
+/// Turns newsletter issues into the emails of their recipients.
+/// Shared by the request handlers and the scheduler.
+pub struct IssueRenderer {
+    base_url: String,
+    email_html: EmailHtml,
+    unsubscribe_links: UnsubscribeLinks,
+    tracking_links: TrackingLinks,
+    tracking_enabled: bool,
+}
+
+impl IssueRenderer {
+    /// `tracking_enabled` is the `tracking.enabled` setting, see `is_tracked`.
+    pub fn new(
+        base_url: &str,
+        hmac_secret: Secret<String>,
+        tracking_enabled: bool,
+    ) -> Result<Self, ParseError> {
+        Ok(Self {
+            base_url: base_url.to_owned(),
+            email_html: EmailHtml::new(base_url)?,
+            unsubscribe_links: UnsubscribeLinks::new(base_url.to_owned(), hmac_secret.clone()),
+            tracking_links: TrackingLinks::new(base_url.to_owned(), hmac_secret),
+            tracking_enabled,
+        })
+    }
+
+    pub fn email_html(&self) -> &EmailHtml {
+        &self.email_html
+    }
+
+    pub fn unsubscribe_links(&self) -> &UnsubscribeLinks {
+        &self.unsubscribe_links
+    }
+
+    pub fn tracking_links(&self) -> &TrackingLinks {
+        &self.tracking_links
+    }
+
+    /// Whether opens and clicks are recorded at all: when they are not,
+    /// the links of issues that were sent tracked still work.
+    pub fn is_tracking_enabled(&self) -> bool {
+        self.tracking_enabled
+    }
+
+    /// Whether the emails of `issue` get an open pixel and click redirects:
+    /// both the application and the issue have to opt in.
+    pub fn is_tracked(&self, issue: &NewsletterIssue) -> bool {
+        self.tracking_enabled && issue.tracked
+    }
+
+    /// The permalink of an issue in the public archive.
+    pub fn archive_url(&self, newsletter_issue_id: Uuid) -> String {
+        format!("{}/archive/{}", self.base_url, newsletter_issue_id)
+    }
+
+    /// The value of `{{ view_in_browser_url }}`: issues hidden from the archive have none.
+    pub fn view_in_browser_url(&self, issue: &NewsletterIssue) -> Option<String> {
+        (!issue.hidden_from_archive).then(|| self.archive_url(issue.newsletter_issue_id))
+    }
+
+    /// The HTML body of `issue` as sent, before its merge tags are filled in.
+    pub fn html_body(&self, issue: &NewsletterIssue, layout: Option<&EmailLayout>) -> String {
+        let mut html_content = wrap_html(issue, layout, false);
+        if !issue.hidden_from_archive && !uses_view_in_browser_url(&html_content) {
+            html_content = wrap_html(issue, layout, true);
+        }
+        self.email_html.prepare(&html_content)
+    }
+
+    /// The plain-text body of `issue` as sent, before its merge tags are filled in.
+    pub fn text_body(&self, issue: &NewsletterIssue, layout: Option<&EmailLayout>) -> String {
+        let text_content = wrap_text(issue, layout, false);
+        if !issue.hidden_from_archive && !uses_view_in_browser_url(&text_content) {
+            return wrap_text(issue, layout, true);
+        }
+        text_content
+    }
+
+    /// The title and both bodies of `issue`, parsed once for all its recipients.
+    pub fn templates(
+        &self,
+        issue: &NewsletterIssue,
+        layout: Option<&EmailLayout>,
+    ) -> Result<EmailTemplates, MergeTagError> {
+        EmailTemplates::parse(
+            &issue.title,
+            &self.html_body(issue, layout),
+            &self.text_body(issue, layout),
+            ISSUE_TAGS,
+        )
+    }
+
+    /// `issue` as shown in the public archive and its feeds: for nobody in particular,
+    /// and without a link to itself.
+    pub fn web_version(
+        &self,
+        issue: &NewsletterIssue,
+        layout: Option<&EmailLayout>,
+    ) -> Result<PersonalisedEmail, MergeTagError> {
+        let templates = EmailTemplates::parse(
+            &issue.title,
+            &self.email_html.prepare(&wrap_html(issue, layout, false)),
+            &wrap_text(issue, layout, false),
+            ISSUE_TAGS,
+        )?;
+        let archive_url = self.archive_url(issue.newsletter_issue_id);
+        Ok(templates.render(&web_values(&archive_url)))
+    }
+
+    /// The title of `issue` in the public archive and its feeds, see `web_version`.
+    pub fn web_title(&self, issue: &NewsletterIssue) -> Result<String, MergeTagError> {
+        let archive_url = self.archive_url(issue.newsletter_issue_id);
+        Ok(MergeTemplate::parse(&issue.title)?.render_text(&web_values(&archive_url)))
+    }
+}
+
+// The archive has no recipient: the defaults of the tags apply
+fn web_values(archive_url: &str) -> MergeValues<'_> {
+    MergeValues {
+        name: None,
+        email: "",
+        unsubscribe_url: None,
+        view_in_browser_url: Some(archive_url),
+        confirmation_url: None,
+    }
+}
+
+// The layout is wrapped after the link is added, so that it is part of the content.
+// It is wrapped before the HTML is prepared, so that its `<style>` rules apply to the issue too.
+fn wrap_html(
+    issue: &NewsletterIssue,
+    layout: Option<&EmailLayout>,
+    view_in_browser_link: bool,
+) -> String {
+    let html_content = if view_in_browser_link {
+        format!("{}{}", VIEW_IN_BROWSER_HTML, issue.html_content)
+    } else {
+        issue.html_content.clone()
+    };
+    match layout {
+        Some(layout) => layout.wrap_html(&html_content),
+        None => html_content,
+    }
+}
+
+fn wrap_text(
+    issue: &NewsletterIssue,
+    layout: Option<&EmailLayout>,
+    view_in_browser_link: bool,
+) -> String {
+    let text_content = if view_in_browser_link {
+        format!("{}{}", VIEW_IN_BROWSER_TEXT, issue.text_content)
+    } else {
+        issue.text_content.clone()
+    };
+    match layout {
+        Some(layout) => layout.wrap_text(&text_content),
+        None => text_content,
+    }
+}
+
+// Templates with typos are rejected later on, when the issue is sent
+fn uses_view_in_browser_url(source: &str) -> bool {
+    MergeTemplate::parse(source).is_ok_and(|template| template.uses(MergeField::ViewInBrowserUrl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueRenderer;
+    use crate::merge_tags::MergeValues;
+    use crate::newsletter_issues::{IssueContent, IssueStatus, NewsletterIssue};
+    use chrono::Utc;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    #[test]
+    fn merge_tags_in_markdown_links_are_filled_in() {
+        let content = IssueContent::from_form(
+            String::new(),
+            String::new(),
+            "Had enough? [Unsubscribe]({{ unsubscribe_url }})".into(),
+        );
+        let issue = NewsletterIssue {
+            newsletter_issue_id: Uuid::new_v4(),
+            title: "Hi {{ name | default: there }}".into(),
+            text_content: content.text_content,
+            html_content: content.html_content,
+            markdown_content: content.markdown_content,
+            status: IssueStatus::Draft,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            published_at: None,
+            scheduled_for: None,
+            schedule_timezone: None,
+            layout_id: None,
+            layout_version: None,
+            hidden_from_archive: true,
+            tracked: false,
+        };
+        let renderer = IssueRenderer::new(
+            "https://newsletter.example.com",
+            Secret::new("secret".into()),
+            false,
+        )
+        .unwrap();
+
+        let email = renderer
+            .templates(&issue, None)
+            .unwrap()
+            .render(&MergeValues {
+                name: Some("Ursula"),
+                email: "ursula@example.com",
+                unsubscribe_url: Some(
+                    "https://newsletter.example.com/subscriptions/unsubscribe?token=abc",
+                ),
+                view_in_browser_url: None,
+                confirmation_url: None,
+            });
+
+        assert!(email.html_content.contains(
+            r#"<a href="https://newsletter.example.com/subscriptions/unsubscribe?token=abc""#
+        ));
+        assert_eq!(
+            email.text_content,
+            "Had enough? Unsubscribe (https://newsletter.example.com/subscriptions/unsubscribe?token=abc)"
+        );
+    }
+}
@@ -1,10 +1,27 @@
 pub mod authentication;
+pub mod cli;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_deliveries;
+pub mod email_html;
+pub mod email_layouts;
 pub mod idempotency;
+pub mod issue_events;
+pub mod issue_renderer;
+pub mod markdown;
+pub mod merge_tags;
+pub mod metrics;
+pub mod newsletter_issues;
+pub mod request_id;
 pub mod routes;
+pub mod scheduler;
+pub mod security_headers;
 pub mod session_state;
+pub mod signup_protection;
 pub mod startup;
 pub mod telemetry;
+pub mod tracking_links;
+pub mod transactional_emails;
+pub mod unsubscribe_links;
 pub mod utils;
@@ -1,15 +1,53 @@
-use zero2prod::configuration::get_configuration;
+use clap::Parser;
+use zero2prod::cli::{check_config, run_admin_command, Cli, Command};
+use zero2prod::configuration::{get_configuration, LogFormat};
 use zero2prod::startup::Application;
-use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::telemetry::{get_subscriber, get_tracer_provider, init_subscriber, init_tracer};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            // Panic if we cant read configuration
+            let configuration = get_configuration().expect("Failed to read configuration.");
 
-    // Panic if we cant read configuration
-    let configuration = get_configuration().expect("Failed to read configuration.");
-    let application = Application::build(configuration).await?;
-    application.run_until_stopped().await?;
+            let tracer = if configuration.opentelemetry.enabled {
+                let provider =
+                    get_tracer_provider("zero2prod".into(), &configuration.opentelemetry)?;
+                Some(init_tracer("zero2prod".into(), provider))
+            } else {
+                None
+            };
+            let (subscriber, log_filter) = get_subscriber(
+                "zero2prod".into(),
+                configuration.telemetry.env_filter(),
+                configuration.telemetry.format,
+                std::io::stdout,
+                tracer,
+            );
+            init_subscriber(subscriber);
+
+            let application = Application::build(configuration, log_filter).await?;
+            application.run_until_stopped().await?;
+            // Flush the spans still waiting to be exported
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+        Command::CheckConfig => check_config()?,
+        Command::Admin(command) => {
+            // Keep stdout for the command output - logs go to stderr
+            let (subscriber, _) = get_subscriber(
+                "zero2prod".into(),
+                "warn".into(),
+                LogFormat::Compact,
+                std::io::stderr,
+                None,
+            );
+            init_subscriber(subscriber);
+
+            let configuration = get_configuration().expect("Failed to read configuration.");
+            run_admin_command(command, configuration).await?;
+        }
+    }
     Ok(())
 }
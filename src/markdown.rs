@@ -0,0 +1,389 @@
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::borrow::Cow;
+
+/// Both versions of a newsletter issue, generated from a single Markdown body.
+#[derive(Debug)]
+pub struct RenderedMarkdown {
+    /// Sanitized: raw HTML, scripts and `javascript:` links written in the Markdown are dropped.
+    pub html: String,
+    pub text: String,
+}
+
+/// Merge tags, e.g. `{{ unsubscribe_url }}`, come out as they were written,
+/// link and image destinations included.
+pub fn render_markdown(markdown: &str) -> RenderedMarkdown {
+    let (merge_tags, markdown) = MergeTagPlaceholders::swap_out(markdown);
+    let options = Options::ENABLE_STRIKETHROUGH;
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(&markdown, options));
+    RenderedMarkdown {
+        html: merge_tags.swap_in(&ammonia::clean(&html), escape_html),
+        text: merge_tags.swap_in(
+            &PlainText::render(Parser::new_ext(&markdown, options)),
+            |tag| Cow::Borrowed(tag),
+        ),
+    }
+}
+
+// Merge tags are not Markdown: left in, `[Unsubscribe]({{ unsubscribe_url }})` is not a link,
+// and `[Unsubscribe]({{unsubscribe_url}})` links to a percent-encoded `%7B%7Bunsubscribe_url%7D%7D`.
+// They are replaced by placeholders while the Markdown is rendered.
+struct MergeTagPlaceholders {
+    // Placeholders are made of letters and digits, which Markdown leaves alone:
+    // `{marker}{index}{marker}`, with a marker that is not found in the Markdown.
+    marker: String,
+    tags: Vec<String>,
+}
+
+impl MergeTagPlaceholders {
+    fn swap_out(markdown: &str) -> (Self, String) {
+        let mut marker = "mergetag".to_owned();
+        while markdown.contains(&marker) {
+            marker.push('x');
+        }
+        let mut tags = Vec::new();
+        let mut output = String::new();
+        let mut rest = markdown;
+        while let Some(start) = rest.find("{{") {
+            // Unclosed tags are reported when the merge tags are checked
+            let Some(length) = rest[start..].find("}}") else {
+                break;
+            };
+            let end = start + length + 2;
+            output.push_str(&rest[..start]);
+            output.push_str(&format!("{0}{1}{0}", marker, tags.len()));
+            tags.push(rest[start..end].to_owned());
+            rest = &rest[end..];
+        }
+        output.push_str(rest);
+        (Self { marker, tags }, output)
+    }
+
+    fn swap_in(&self, rendered: &str, escape: fn(&str) -> Cow<'_, str>) -> String {
+        let mut output = rendered.to_owned();
+        for (index, tag) in self.tags.iter().enumerate() {
+            let placeholder = format!("{0}{1}{0}", self.marker, index);
+            output = output.replace(&placeholder, &escape(tag));
+        }
+        output
+    }
+}
+
+// What pulldown-cmark escapes in text and attributes, e.g. in `{{ name | default: Tom & Jerry }}`
+fn escape_html(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    )
+}
+
+// Markdown is already readable as plain text: we mostly drop the markup, raw HTML tags included.
+// Links are followed by their URL, images replaced by their alt text
+// and code blocks indented.
+#[derive(Default)]
+struct PlainText {
+    output: String,
+    at_line_start: bool,
+    // Set between a list item marker and the first text of the item
+    item_start: bool,
+    quote_depth: usize,
+    // One entry per open list: the number of the next item, `None` for bullets
+    lists: Vec<Option<u64>>,
+    in_code_block: bool,
+    // The URL of the open links, along with where their text starts in `output`
+    links: Vec<(String, usize)>,
+    // The alt text of the open image: it is not written as it is read
+    image_alt: Option<String>,
+    // Set within raw `<script>` and `<style>` elements, whose text is not content
+    in_raw_code: bool,
+}
+
+impl PlainText {
+    fn render<'a>(events: impl Iterator<Item = Event<'a>>) -> String {
+        let mut plain_text = Self {
+            at_line_start: true,
+            ..Self::default()
+        };
+        for event in events {
+            plain_text.push(event);
+        }
+        plain_text.output.trim_end().to_owned()
+    }
+
+    fn push(&mut self, event: Event<'_>) {
+        if let Event::Html(html) = &event {
+            let html = html.to_ascii_lowercase();
+            if html.starts_with("<script") || html.starts_with("<style") {
+                self.in_raw_code = true;
+            }
+            if html.contains("</script") || html.contains("</style") {
+                self.in_raw_code = false;
+            }
+            return;
+        }
+        if self.in_raw_code {
+            return;
+        }
+        if let Some(alt) = &mut self.image_alt {
+            match event {
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                Event::End(Tag::Image(..)) => {
+                    let alt = self.image_alt.take().unwrap_or_default();
+                    if alt.is_empty() {
+                        self.write("[Image]");
+                    } else {
+                        self.write(&format!("[Image: {}]", alt));
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(Tag::Paragraph | Tag::Heading(..)) => self.block_break(),
+            Event::Start(Tag::BlockQuote) => {
+                self.block_break();
+                self.quote_depth += 1;
+            }
+            Event::End(Tag::BlockQuote) => self.quote_depth -= 1,
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.block_break();
+                self.in_code_block = true;
+            }
+            Event::End(Tag::CodeBlock(_)) => self.in_code_block = false,
+            Event::Start(Tag::List(first_number)) => {
+                // A nested list goes right under the text of its item
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.new_line();
+                }
+                self.lists.push(first_number);
+            }
+            Event::End(Tag::List(_)) => {
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.new_line();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_owned(),
+                };
+                let prefix = self.prefix(self.lists.len().saturating_sub(1));
+                self.output.push_str(&prefix);
+                self.output.push_str(&marker);
+                self.at_line_start = false;
+                self.item_start = true;
+            }
+            Event::Start(Tag::Link(_, url, _)) => {
+                self.links.push((url.into_string(), self.output.len()));
+            }
+            Event::End(Tag::Link(..)) => {
+                if let Some((url, start)) = self.links.pop() {
+                    // Autolinks, e.g. `<https://example.com>`, are their own text
+                    let text = &self.output[start..];
+                    if text != url && Some(text) != url.strip_prefix("mailto:") {
+                        self.write(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::Start(Tag::Image(..)) => self.image_alt = Some(String::new()),
+            Event::Text(text) | Event::Code(text) => self.write(&text),
+            Event::SoftBreak | Event::HardBreak => {
+                self.at_line_start = false;
+                self.new_line();
+            }
+            Event::Rule => {
+                self.block_break();
+                self.write("---");
+            }
+            _ => {}
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.output.push('\n');
+                self.at_line_start = true;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.at_line_start {
+                let prefix = self.prefix(self.lists.len());
+                self.output.push_str(&prefix);
+                self.at_line_start = false;
+            }
+            self.output.push_str(line);
+            self.item_start = false;
+        }
+    }
+
+    // What starts every line: quote markers, then the indentation of `list_depth` lists
+    fn prefix(&self, list_depth: usize) -> String {
+        let mut prefix = "> ".repeat(self.quote_depth);
+        for list in &self.lists[..list_depth] {
+            prefix.push_str(if list.is_some() { "   " } else { "  " });
+        }
+        if self.in_code_block {
+            prefix.push_str("    ");
+        }
+        prefix
+    }
+
+    fn new_line(&mut self) {
+        if !self.at_line_start {
+            self.output.push('\n');
+            self.at_line_start = true;
+        }
+    }
+
+    // Blocks are separated by a blank line, except for the first block of a list item
+    fn block_break(&mut self) {
+        if self.item_start || self.output.is_empty() {
+            return;
+        }
+        self.new_line();
+        if !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn paragraphs_and_headings_are_separated_by_a_blank_line() {
+        let rendered = render_markdown("# Title\n\nSome *emphasis*\nand **more**.\n\nThe end.");
+
+        assert_eq!(
+            rendered.html,
+            "<h1>Title</h1>\n<p>Some <em>emphasis</em>\nand <strong>more</strong>.</p>\n<p>The end.</p>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            "Title\n\nSome emphasis\nand more.\n\nThe end."
+        );
+    }
+
+    #[test]
+    fn links_are_followed_by_their_url_in_plain_text() {
+        let rendered =
+            render_markdown("Read [the docs](https://example.com/docs) or <https://example.com>.");
+
+        assert_eq!(
+            rendered.html,
+            "<p>Read <a href=\"https://example.com/docs\" rel=\"noopener noreferrer\">the docs</a> \
+            or <a href=\"https://example.com\" rel=\"noopener noreferrer\">https://example.com</a>.</p>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            "Read the docs (https://example.com/docs) or https://example.com."
+        );
+    }
+
+    #[test]
+    fn javascript_links_are_dropped() {
+        let rendered = render_markdown("[Click me](javascript:alert(1))");
+
+        assert!(!rendered.html.contains("javascript"));
+        assert!(rendered.html.contains("Click me"));
+    }
+
+    #[test]
+    fn lists_keep_their_markers_and_nesting() {
+        let rendered = render_markdown("- one\n- two\n  1. first\n  2. second\n- three");
+
+        assert_eq!(
+            rendered.html,
+            "<ul>\n<li>one</li>\n<li>two\n<ol>\n<li>first</li>\n<li>second</li>\n</ol>\n</li>\n<li>three</li>\n</ul>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            "- one\n- two\n  1. first\n  2. second\n- three"
+        );
+    }
+
+    #[test]
+    fn ordered_lists_can_start_at_any_number() {
+        let rendered = render_markdown("Steps:\n\n3. three\n4. four");
+
+        assert_eq!(rendered.text, "Steps:\n\n3. three\n4. four");
+    }
+
+    #[test]
+    fn code_blocks_are_escaped_and_indented() {
+        let rendered = render_markdown("Run:\n\n```html\n<script>alert(1)</script>\n```\n\nDone.");
+
+        assert_eq!(
+            rendered.html,
+            "<p>Run:</p>\n<pre><code>&lt;script&gt;alert(1)&lt;/script&gt;\n</code></pre>\n<p>Done.</p>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            "Run:\n\n    <script>alert(1)</script>\n\nDone."
+        );
+    }
+
+    #[test]
+    fn images_are_replaced_by_their_alt_text_in_plain_text() {
+        let rendered = render_markdown(
+            "![A *cat*](https://example.com/cat.png) ![](https://example.com/dog.png)",
+        );
+
+        assert_eq!(
+            rendered.html,
+            "<p><img src=\"https://example.com/cat.png\" alt=\"A cat\"> \
+            <img src=\"https://example.com/dog.png\" alt=\"\"></p>\n"
+        );
+        assert_eq!(rendered.text, "[Image: A cat] [Image]");
+    }
+
+    #[test]
+    fn raw_html_is_sanitized() {
+        let rendered = render_markdown("Hi <script>alert(1)</script><b onclick=\"x()\">there</b>");
+
+        assert_eq!(rendered.html, "<p>Hi <b>there</b></p>\n");
+        assert_eq!(rendered.text, "Hi there");
+    }
+
+    #[test]
+    fn merge_tags_are_kept_as_written_in_links_and_images() {
+        let rendered = render_markdown(
+            "[Unsubscribe]({{ unsubscribe_url }}) [Read online](<{{view_in_browser_url}}>)\n\n\
+            ![Logo]({{ view_in_browser_url }}/logo.png) Hi {{ name | default: *Tom* & Jerry }}",
+        );
+
+        assert_eq!(
+            rendered.html,
+            "<p><a href=\"{{ unsubscribe_url }}\" rel=\"noopener noreferrer\">Unsubscribe</a> \
+            <a href=\"{{view_in_browser_url}}\" rel=\"noopener noreferrer\">Read online</a></p>\n\
+            <p><img src=\"{{ view_in_browser_url }}/logo.png\" alt=\"Logo\"> \
+            Hi {{ name | default: *Tom* &amp; Jerry }}</p>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            "Unsubscribe ({{ unsubscribe_url }}) Read online ({{view_in_browser_url}})\n\n\
+            [Image: Logo] Hi {{ name | default: *Tom* & Jerry }}"
+        );
+    }
+
+    #[test]
+    fn quotes_are_prefixed() {
+        let rendered = render_markdown("> Quoted\n> text\n\nReply");
+
+        assert_eq!(rendered.text, "> Quoted\n> text\n\nReply");
+    }
+}
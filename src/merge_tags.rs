@@ -0,0 +1,372 @@
+use std::borrow::Cow;
+
+/// What a merge tag stands for, e.g. `{{ name }}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeField {
+    Name,
+    Email,
+    UnsubscribeUrl,
+    ViewInBrowserUrl,
+    ConfirmationUrl,
+}
+
+/// The tags of newsletter issues and of their layouts.
+pub const ISSUE_TAGS: &[MergeField] = &[
+    MergeField::Name,
+    MergeField::Email,
+    MergeField::UnsubscribeUrl,
+    MergeField::ViewInBrowserUrl,
+];
+
+/// The tags of the email sent to confirm a subscription.
+pub const CONFIRMATION_TAGS: &[MergeField] = &[MergeField::Name, MergeField::ConfirmationUrl];
+
+impl MergeField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeField::Name => "name",
+            MergeField::Email => "email",
+            MergeField::UnsubscribeUrl => "unsubscribe_url",
+            MergeField::ViewInBrowserUrl => "view_in_browser_url",
+            MergeField::ConfirmationUrl => "confirmation_url",
+        }
+    }
+}
+
+impl std::fmt::Display for MergeField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{{{{ {} }}}}`", self.as_str())
+    }
+}
+
+/// The values of the merge tags for a single recipient.
+/// Each template only uses the values of the tags it knows about.
+pub struct MergeValues<'a> {
+    /// Unknown for the test copies sent to admins
+    pub name: Option<&'a str>,
+    pub email: &'a str,
+    pub unsubscribe_url: Option<&'a str>,
+    /// The page of the issue in the public archive, unless it is hidden from it
+    pub view_in_browser_url: Option<&'a str>,
+    pub confirmation_url: Option<&'a str>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum MergeTagError {
+    #[error("`{{{{ {0} }}}}` is not a known merge tag: use {}", list_tags(.1))]
+    UnknownTag(String, &'static [MergeField]),
+    #[error(
+        "`{0}` is not a valid merge tag: defaults are written `{{{{ name | default: there }}}}`"
+    )]
+    InvalidTag(String),
+    #[error("A merge tag is not closed with `}}}}`: `{0}`")]
+    Unclosed(String),
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Tag {
+        field: MergeField,
+        default: Option<String>,
+    },
+}
+
+/// A subject or a body with merge tags, e.g. `Hello {{ name | default: there }}!`.
+/// The default is used when the value is missing or empty.
+#[derive(Debug)]
+pub struct MergeTemplate {
+    parts: Vec<Part>,
+}
+
+impl MergeTemplate {
+    /// Fails on the first unknown or malformed tag, e.g. `{{ nmae }}`.
+    /// Only `ISSUE_TAGS` are known, see `parse_with` for the others.
+    pub fn parse(source: &str) -> Result<Self, MergeTagError> {
+        Self::parse_with(source, ISSUE_TAGS)
+    }
+
+    pub fn parse_with(source: &str, known: &'static [MergeField]) -> Result<Self, MergeTagError> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            let after_start = &rest[start + 2..];
+            let Some(end) = after_start.find("}}") else {
+                let snippet: String = rest[start..].chars().take(30).collect();
+                return Err(MergeTagError::Unclosed(snippet));
+            };
+            parts.push(parse_tag(&after_start[..end], known)?);
+            rest = &after_start[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Whether `field` is used at least once, e.g. to require a link.
+    pub fn uses(&self, field: MergeField) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Tag { field: f, .. } if *f == field))
+    }
+
+    /// For HTML sources: the values are escaped, the defaults are kept as they were written.
+    pub fn render_html(&self, values: &MergeValues) -> String {
+        self.render(values, escape_html)
+    }
+
+    /// For plain-text sources, e.g. subjects.
+    pub fn render_text(&self, values: &MergeValues) -> String {
+        self.render(values, Cow::Borrowed)
+    }
+
+    fn render<'a>(
+        &'a self,
+        values: &MergeValues<'a>,
+        escape: fn(&'a str) -> Cow<'a, str>,
+    ) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Tag { field, default } => {
+                    let value = match field {
+                        MergeField::Name => values.name,
+                        MergeField::Email => Some(values.email),
+                        MergeField::UnsubscribeUrl => values.unsubscribe_url,
+                        MergeField::ViewInBrowserUrl => values.view_in_browser_url,
+                        MergeField::ConfirmationUrl => values.confirmation_url,
+                    };
+                    match (value.filter(|v| !v.trim().is_empty()), default) {
+                        (Some(value), _) => output.push_str(&escape(value)),
+                        (None, Some(default)) => output.push_str(default),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+// `name`, or `name | default: there`. Spaces are optional.
+fn parse_tag(tag: &str, known: &'static [MergeField]) -> Result<Part, MergeTagError> {
+    let (field, default) = match tag.split_once('|') {
+        Some((field, filter)) => {
+            let default = filter
+                .trim()
+                .strip_prefix("default:")
+                .ok_or_else(|| MergeTagError::InvalidTag(format!("{{{{{}}}}}", tag)))?;
+            (field, Some(default.trim().to_owned()))
+        }
+        None => (tag, None),
+    };
+    let field = field.trim();
+    match known.iter().find(|known| known.as_str() == field) {
+        Some(field) => Ok(Part::Tag {
+            field: *field,
+            default,
+        }),
+        None => Err(MergeTagError::UnknownTag(field.to_owned(), known)),
+    }
+}
+
+// "`{{ name }}`, `{{ email }}` or `{{ unsubscribe_url }}`"
+fn list_tags(tags: &[MergeField]) -> String {
+    let tags: Vec<String> = tags.iter().map(MergeField::to_string).collect();
+    match tags.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, others)) => format!("{} or {}", others.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// The subject and both bodies of an email, parsed once for all its recipients.
+pub struct EmailTemplates {
+    pub subject: MergeTemplate,
+    pub html_body: MergeTemplate,
+    pub text_body: MergeTemplate,
+}
+
+pub struct PersonalisedEmail {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+impl EmailTemplates {
+    pub fn parse(
+        subject: &str,
+        html_body: &str,
+        text_body: &str,
+        known: &'static [MergeField],
+    ) -> Result<Self, MergeTagError> {
+        Ok(Self {
+            subject: MergeTemplate::parse_with(subject, known)?,
+            html_body: MergeTemplate::parse_with(html_body, known)?,
+            text_body: MergeTemplate::parse_with(text_body, known)?,
+        })
+    }
+
+    pub fn render(&self, values: &MergeValues) -> PersonalisedEmail {
+        PersonalisedEmail {
+            subject: self.subject.render_text(values),
+            html_content: self.html_body.render_html(values),
+            text_content: self.text_body.render_text(values),
+        }
+    }
+}
+
+fn escape_html(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MergeField, MergeTagError, MergeTemplate, MergeValues, CONFIRMATION_TAGS, ISSUE_TAGS,
+    };
+    use claims::{assert_err, assert_ok};
+
+    fn values() -> MergeValues<'static> {
+        MergeValues {
+            name: Some("Ursula"),
+            email: "ursula@example.com",
+            unsubscribe_url: Some("https://example.com/unsubscribe?id=1&signature=2"),
+            view_in_browser_url: Some("https://example.com/archive/1"),
+            confirmation_url: None,
+        }
+    }
+
+    #[test]
+    fn every_tag_is_replaced() {
+        let template =
+            MergeTemplate::parse("Hi {{ name }} ({{email}}), leave: {{  unsubscribe_url  }}.")
+                .unwrap();
+
+        assert_eq!(
+            template.render_text(&values()),
+            "Hi Ursula (ursula@example.com), leave: https://example.com/unsubscribe?id=1&signature=2."
+        );
+    }
+
+    #[test]
+    fn text_without_tags_is_kept_as_is() {
+        let template = MergeTemplate::parse("<p>No tags { here } }}</p>").unwrap();
+
+        assert_eq!(
+            template.render_html(&values()),
+            "<p>No tags { here } }}</p>"
+        );
+    }
+
+    #[test]
+    fn values_are_escaped_in_html_only() {
+        let template =
+            MergeTemplate::parse(r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}">"#)
+                .unwrap();
+        let values = MergeValues {
+            name: Some("<script>alert('Bobby')</script>"),
+            ..values()
+        };
+
+        assert_eq!(
+            template.render_html(&values),
+            "<p>Hi &lt;script&gt;alert(&#x27;Bobby&#x27;)&lt;/script&gt;</p>\
+            <a href=\"https://example.com/unsubscribe?id=1&amp;signature=2\">"
+        );
+        assert_eq!(
+            template.render_text(&values),
+            r#"<p>Hi <script>alert('Bobby')</script></p><a href="https://example.com/unsubscribe?id=1&signature=2">"#
+        );
+    }
+
+    #[test]
+    fn defaults_replace_missing_or_empty_values() {
+        let template = MergeTemplate::parse("Hi {{ name | default: there }}!").unwrap();
+
+        for name in [None, Some(""), Some("  ")] {
+            let values = MergeValues { name, ..values() };
+            assert_eq!(template.render_text(&values), "Hi there!");
+        }
+        assert_eq!(template.render_text(&values()), "Hi Ursula!");
+    }
+
+    #[test]
+    fn missing_values_without_default_are_left_empty() {
+        let template = MergeTemplate::parse("Hi {{ name }}!").unwrap();
+        let values = MergeValues {
+            name: None,
+            ..values()
+        };
+
+        assert_eq!(template.render_text(&values), "Hi !");
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        let error = MergeTemplate::parse("Hi {{ nmae }}!").err().unwrap();
+
+        assert_eq!(error, MergeTagError::UnknownTag("nmae".into(), ISSUE_TAGS));
+        assert_eq!(
+            error.to_string(),
+            "`{{ nmae }}` is not a known merge tag: use `{{ name }}`, `{{ email }}`, `{{ unsubscribe_url }}` or `{{ view_in_browser_url }}`"
+        );
+    }
+
+    #[test]
+    fn malformed_tags_are_rejected() {
+        for source in [
+            "Hi {{ name",
+            "Hi {{ name | uppercase }}",
+            "Hi {{ }}",
+            "Hi {{ Name }}",
+        ] {
+            assert_err!(MergeTemplate::parse(source));
+        }
+    }
+
+    #[test]
+    fn tags_can_be_used_back_to_back() {
+        assert_ok!(MergeTemplate::parse("{{name}}{{email}}"));
+    }
+
+    #[test]
+    fn each_kind_of_email_has_its_own_tags() {
+        let source = "Hi {{ name }}, confirm at {{ confirmation_url }}";
+
+        let error = MergeTemplate::parse(source).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "`{{ confirmation_url }}` is not a known merge tag: use `{{ name }}`, `{{ email }}`, `{{ unsubscribe_url }}` or `{{ view_in_browser_url }}`"
+        );
+        let template = MergeTemplate::parse_with(source, CONFIRMATION_TAGS).unwrap();
+        assert!(template.uses(MergeField::ConfirmationUrl));
+        let values = MergeValues {
+            confirmation_url: Some("https://example.com/confirm"),
+            ..values()
+        };
+        assert_eq!(
+            template.render_text(&values),
+            "Hi Ursula, confirm at https://example.com/confirm"
+        );
+    }
+}
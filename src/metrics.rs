@@ -0,0 +1,190 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web_lab::middleware::Next;
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+
+// All our metrics are registered once, in a dedicated registry,
+// no matter how many `Application`s are running in the process (e.g. in tests).
+static REGISTRY: Lazy<Registry> = Lazy::new(|| {
+    let registry = Registry::new_custom(Some("zero2prod".into()), None)
+        .expect("Failed to create the metrics registry");
+    registry
+        .register(Box::new(HTTP_REQUEST_DURATION.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(EMAILS_SENT_TOTAL.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(EMAIL_SEND_DURATION.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(DB_POOL_CONNECTIONS.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(DB_POOL_IDLE_CONNECTIONS.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(SUBSCRIPTIONS_CREATED_TOTAL.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(SUBSCRIPTIONS_CONFIRMED_TOTAL.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(SIGNUPS_BLOCKED_TOTAL.clone()))
+        .unwrap();
+    registry
+        .register(Box::new(PASSWORD_VERIFICATION_DURATION.clone()))
+        .unwrap();
+    registry
+});
+
+static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "Time spent serving HTTP requests, by matched route pattern.",
+        ),
+        &["method", "route", "status"],
+    )
+    .unwrap()
+});
+
+static EMAILS_SENT_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "emails_sent_total",
+            "Emails handed over to the email provider, by outcome.",
+        ),
+        &["outcome"],
+    )
+    .unwrap()
+});
+
+static EMAIL_SEND_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "email_send_duration_seconds",
+            "Time spent calling the email provider, by outcome.",
+        ),
+        &["outcome"],
+    )
+    .unwrap()
+});
+
+static DB_POOL_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::new(
+        "db_pool_connections",
+        "Connections currently held by the Postgres pool, idle or in use.",
+    )
+    .unwrap()
+});
+
+static DB_POOL_IDLE_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::new(
+        "db_pool_idle_connections",
+        "Idle connections in the Postgres pool.",
+    )
+    .unwrap()
+});
+
+static SUBSCRIPTIONS_CREATED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "subscriptions_created_total",
+        "New subscribers waiting for confirmation.",
+    )
+    .unwrap()
+});
+
+static SUBSCRIPTIONS_CONFIRMED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "subscriptions_confirmed_total",
+        "Subscribers who clicked on their confirmation link.",
+    )
+    .unwrap()
+});
+
+static SIGNUPS_BLOCKED_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "signups_blocked_total",
+            "Subscription attempts turned down by the signup protections, by reason.",
+        ),
+        &["reason"],
+    )
+    .unwrap()
+});
+
+static PASSWORD_VERIFICATION_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "password_verification_duration_seconds",
+            "Time spent checking a password against its Argon2 hash, by outcome.",
+        ),
+        &["outcome"],
+    )
+    .unwrap()
+});
+
+/// Record the duration of every request, labelled with the route pattern
+/// (e.g. `/admin/newsletters`) rather than the raw path to keep cardinality bounded.
+pub async fn record_http_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".into());
+    let start = std::time::Instant::now();
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+        .observe(start.elapsed().as_secs_f64());
+    result
+}
+
+pub fn record_email_sent(succeeded: bool, duration: std::time::Duration) {
+    let outcome = if succeeded { "success" } else { "failure" };
+    EMAILS_SENT_TOTAL.with_label_values(&[outcome]).inc();
+    EMAIL_SEND_DURATION
+        .with_label_values(&[outcome])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn record_subscription_created() {
+    SUBSCRIPTIONS_CREATED_TOTAL.inc();
+}
+
+pub fn record_subscription_confirmed() {
+    SUBSCRIPTIONS_CONFIRMED_TOTAL.inc();
+}
+
+/// `reason` is one of `ip_rate_limit`, `email_domain_rate_limit`, `honeypot` or `challenge`.
+pub fn record_signup_blocked(reason: &'static str) {
+    SIGNUPS_BLOCKED_TOTAL.with_label_values(&[reason]).inc();
+}
+
+pub fn record_password_verification(succeeded: bool, duration: std::time::Duration) {
+    let outcome = if succeeded { "success" } else { "failure" };
+    PASSWORD_VERIFICATION_DURATION
+        .with_label_values(&[outcome])
+        .observe(duration.as_secs_f64());
+}
+
+/// Render every metric in the Prometheus text exposition format.
+pub fn encode_metrics(pool: &PgPool) -> Result<String, anyhow::Error> {
+    // Pool gauges are sampled when scraped rather than tracked on every checkout
+    DB_POOL_CONNECTIONS.set(pool.size().into());
+    DB_POOL_IDLE_CONNECTIONS.set(pool.num_idle() as i64);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
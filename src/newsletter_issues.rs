@@ -0,0 +1,752 @@
+use crate::domain::{IssueSchedule, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::email_deliveries::record_email_delivery;
+use crate::email_layouts::{get_issue_layout, EmailLayout};
+use crate::issue_renderer::IssueRenderer;
+use crate::markdown::render_markdown;
+use crate::merge_tags::{MergeTagError, MergeTemplate, MergeValues};
+use crate::request_id::RequestId;
+use crate::routes::error_chain_fmt;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Where an issue is in its lifecycle: only drafts can be edited,
+/// and an issue leaves the drafts exactly once, when it starts being sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueStatus {
+    Draft,
+    Scheduled,
+    Sending,
+    Sent,
+}
+
+impl IssueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueStatus::Draft => "draft",
+            IssueStatus::Scheduled => "scheduled",
+            IssueStatus::Sending => "sending",
+            IssueStatus::Sent => "sent",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "draft" => Ok(IssueStatus::Draft),
+            "scheduled" => Ok(IssueStatus::Scheduled),
+            "sending" => Ok(IssueStatus::Sending),
+            "sent" => Ok(IssueStatus::Sent),
+            other => Err(anyhow::anyhow!(
+                "`{}` is not a newsletter issue status",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for IssueStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    /// Set for issues written in Markdown: both contents are generated from it.
+    pub markdown_content: Option<String>,
+    pub status: IssueStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+    /// Only set while the issue is `scheduled`, or once it has been sent on schedule.
+    pub scheduled_for: Option<DateTime<Utc>>,
+    /// The timezone `scheduled_for` was picked in, e.g. `Europe/Paris`.
+    pub schedule_timezone: Option<String>,
+    /// The layout the issue is wrapped in, if any.
+    pub layout_id: Option<Uuid>,
+    /// Pinned when the issue starts being sent: until then, the latest version is used.
+    pub layout_version: Option<i32>,
+    /// Left out of the public archive and its feeds once sent.
+    pub hidden_from_archive: bool,
+    /// Opens and clicks are recorded, see `IssueRenderer::is_tracked`.
+    pub tracked: bool,
+}
+
+impl NewsletterIssue {
+    /// Reject unknown or malformed merge tags in the title and both contents,
+    /// before anything is sent.
+    pub fn check_merge_tags(&self) -> Result<(), MergeTagError> {
+        check_merge_tags(&self.title, &self.html_content, &self.text_content)
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.status == IssueStatus::Draft
+    }
+
+    pub fn is_scheduled(&self) -> bool {
+        self.status == IssueStatus::Scheduled
+    }
+
+    /// Whether it can be read on `/archive`: sending issues are, so that the
+    /// "view in browser" link of the first recipients works right away.
+    pub fn is_public(&self) -> bool {
+        matches!(self.status, IssueStatus::Sending | IssueStatus::Sent) && !self.hidden_from_archive
+    }
+
+    pub fn schedule(&self) -> Option<IssueSchedule> {
+        self.scheduled_for.map(|send_at| {
+            IssueSchedule::from_stored(send_at, self.schedule_timezone.as_deref().unwrap_or("UTC"))
+        })
+    }
+}
+
+// The status is stored as text, see `IssueStatus::parse`
+struct NewsletterIssueRow {
+    newsletter_issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    markdown_content: Option<String>,
+    status: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    published_at: Option<DateTime<Utc>>,
+    scheduled_for: Option<DateTime<Utc>>,
+    schedule_timezone: Option<String>,
+    layout_id: Option<Uuid>,
+    layout_version: Option<i32>,
+    hidden_from_archive: bool,
+    tracked: bool,
+}
+
+impl TryFrom<NewsletterIssueRow> for NewsletterIssue {
+    type Error = anyhow::Error;
+
+    fn try_from(row: NewsletterIssueRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            newsletter_issue_id: row.newsletter_issue_id,
+            title: row.title,
+            text_content: row.text_content,
+            html_content: row.html_content,
+            markdown_content: row.markdown_content,
+            status: IssueStatus::parse(&row.status)?,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            published_at: row.published_at,
+            scheduled_for: row.scheduled_for,
+            schedule_timezone: row.schedule_timezone,
+            layout_id: row.layout_id,
+            layout_version: row.layout_version,
+            hidden_from_archive: row.hidden_from_archive,
+            tracked: row.tracked,
+        })
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum PublishError {
+    #[error("This newsletter issue has already been published")]
+    NotADraft,
+    #[error(transparent)]
+    InvalidMergeTags(#[from] MergeTagError),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// The body of an issue, as written by its editor.
+pub struct IssueContent {
+    pub text_content: String,
+    pub html_content: String,
+    pub markdown_content: Option<String>,
+    /// See `email_layouts`.
+    pub layout_id: Option<Uuid>,
+    /// See `NewsletterIssue::hidden_from_archive`.
+    pub hidden_from_archive: bool,
+    /// See `NewsletterIssue::tracked`.
+    pub tracked: bool,
+}
+
+impl IssueContent {
+    /// Raw HTML mode: both versions are written by hand.
+    pub fn raw(text_content: String, html_content: String) -> Self {
+        Self {
+            text_content,
+            html_content,
+            markdown_content: None,
+            layout_id: None,
+            hidden_from_archive: false,
+            tracked: false,
+        }
+    }
+
+    /// Both versions are generated from the Markdown, unless it is empty.
+    pub fn from_form(text_content: String, html_content: String, markdown_content: String) -> Self {
+        if markdown_content.trim().is_empty() {
+            return Self::raw(text_content, html_content);
+        }
+        let rendered = render_markdown(&markdown_content);
+        Self {
+            text_content: rendered.text,
+            html_content: rendered.html,
+            markdown_content: Some(markdown_content),
+            layout_id: None,
+            hidden_from_archive: false,
+            tracked: false,
+        }
+    }
+
+    pub fn with_layout(self, layout_id: Option<Uuid>) -> Self {
+        Self { layout_id, ..self }
+    }
+
+    pub fn hide_from_archive(self, hidden_from_archive: bool) -> Self {
+        Self {
+            hidden_from_archive,
+            ..self
+        }
+    }
+
+    pub fn track(self, tracked: bool) -> Self {
+        Self { tracked, ..self }
+    }
+
+    /// See `NewsletterIssue::check_merge_tags`.
+    pub fn check_merge_tags(&self, title: &str) -> Result<(), MergeTagError> {
+        check_merge_tags(title, &self.html_content, &self.text_content)
+    }
+}
+
+fn check_merge_tags(title: &str, html: &str, text: &str) -> Result<(), MergeTagError> {
+    for source in [title, html, text] {
+        MergeTemplate::parse(source)?;
+    }
+    Ok(())
+}
+
+/// Stored as a draft.
+#[tracing::instrument(name = "Store a newsletter issue", skip(pool, content))]
+pub async fn insert_newsletter_issue(
+    pool: &PgPool,
+    title: &str,
+    content: &IssueContent,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, markdown_content,
+            layout_id, hidden_from_archive, tracked, status, created_at, updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'draft', now(), now())
+        "#,
+        newsletter_issue_id,
+        title,
+        content.text_content,
+        content.html_content,
+        content.markdown_content,
+        content.layout_id,
+        content.hidden_from_archive,
+        content.tracked,
+    )
+    .execute(pool)
+    .await?;
+    Ok(newsletter_issue_id)
+}
+
+/// Returns `false` if the issue is not a draft (anymore).
+#[tracing::instrument(name = "Update a newsletter draft", skip(pool, content))]
+pub async fn update_draft(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    title: &str,
+    content: &IssueContent,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $2, text_content = $3, html_content = $4, markdown_content = $5,
+            layout_id = $6, hidden_from_archive = $7, tracked = $8, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        title,
+        content.text_content,
+        content.html_content,
+        content.markdown_content,
+        content.layout_id,
+        content.hidden_from_archive,
+        content.tracked,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(name = "Get a newsletter issue", skip(pool))]
+pub async fn get_newsletter_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        NewsletterIssueRow,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, markdown_content, status,
+            created_at, updated_at, published_at, scheduled_for, schedule_timezone,
+            layout_id, layout_version, hidden_from_archive, tracked
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a newsletter issue.")?;
+    row.map(NewsletterIssue::try_from).transpose()
+}
+
+/// Most recently edited first.
+#[tracing::instrument(name = "List newsletter issues", skip(pool))]
+pub async fn list_newsletter_issues(pool: &PgPool) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        NewsletterIssueRow,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, markdown_content, status,
+            created_at, updated_at, published_at, scheduled_for, schedule_timezone,
+            layout_id, layout_version, hidden_from_archive, tracked
+        FROM newsletter_issues
+        ORDER BY updated_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the newsletter issues.")?;
+    rows.into_iter().map(NewsletterIssue::try_from).collect()
+}
+
+/// The sent issues of the public archive, most recently published first.
+/// `None` lists all of them.
+#[tracing::instrument(name = "List archived newsletter issues", skip(pool))]
+pub async fn list_archived_issues(
+    pool: &PgPool,
+    limit: Option<i64>,
+) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        NewsletterIssueRow,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, markdown_content, status,
+            created_at, updated_at, published_at, scheduled_for, schedule_timezone,
+            layout_id, layout_version, hidden_from_archive, tracked
+        FROM newsletter_issues
+        WHERE status = 'sent' AND NOT hidden_from_archive
+        ORDER BY published_at DESC
+        LIMIT $1
+        "#,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the archived newsletter issues.")?;
+    rows.into_iter().map(NewsletterIssue::try_from).collect()
+}
+
+/// Unlike the rest of an issue, this can be changed after it has been sent.
+/// Returns `false` if there is no such issue.
+#[tracing::instrument(name = "Hide or show a newsletter issue in the archive", skip(pool))]
+pub async fn set_hidden_from_archive(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    hidden_from_archive: bool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET hidden_from_archive = $2, updated_at = now()
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        hidden_from_archive,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to change whether a newsletter issue is archived.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Queue a draft for `schedule`, or move the send time of an issue that is already scheduled.
+/// Returns `false` if the issue is being or has been sent.
+#[tracing::instrument(name = "Schedule a newsletter issue", skip(pool))]
+pub async fn schedule_newsletter_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    schedule: &IssueSchedule,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'scheduled', scheduled_for = $2, schedule_timezone = $3, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        "#,
+        newsletter_issue_id,
+        schedule.send_at(),
+        schedule.timezone(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Turn a scheduled issue back into a draft.
+/// Returns `false` if it was not scheduled, e.g. because the scheduler already picked it up.
+#[tracing::instrument(name = "Cancel a scheduled newsletter issue", skip(pool))]
+pub async fn cancel_scheduled_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'draft', scheduled_for = NULL, schedule_timezone = NULL, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// How long an issue can stay in `sending` without news from the replica sending it,
+/// before `claim_due_issue` hands it over to another one.
+pub const SENDING_CLAIM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+// How often the replica sending an issue lets the others know, see `renew_sending_claim`
+const SENDING_CLAIM_RENEWAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Move the scheduled issue that has been due the longest to `sending`, and return it.
+/// Like `publish_newsletter_issue`, this pins the version of its layout.
+/// An issue left in `sending` by a replica that stopped - see `SENDING_CLAIM_TIMEOUT` -
+/// is claimed again, keeping its layout version, to be sent to the remaining subscribers.
+/// Every replica runs a scheduler: `SKIP LOCKED` lets them claim different issues
+/// at the same time, and never the same one twice.
+#[tracing::instrument(name = "Claim a due newsletter issue", skip(pool))]
+pub async fn claim_due_issue(pool: &PgPool) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        NewsletterIssueRow,
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = now(), layout_version = CASE
+            WHEN status = 'scheduled' THEN (
+                SELECT MAX(version) FROM email_layout_versions v
+                WHERE v.layout_id = newsletter_issues.layout_id
+            )
+            ELSE layout_version
+        END
+        WHERE newsletter_issue_id = (
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            WHERE (status = 'scheduled' AND scheduled_for <= now())
+                OR (status = 'sending' AND updated_at < now() - make_interval(secs => $1))
+            ORDER BY COALESCE(scheduled_for, updated_at)
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING newsletter_issue_id, title, text_content, html_content, markdown_content, status,
+            created_at, updated_at, published_at, scheduled_for, schedule_timezone,
+            layout_id, layout_version, hidden_from_archive, tracked
+        "#,
+        SENDING_CLAIM_TIMEOUT.as_secs_f64(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to claim a due newsletter issue.")?;
+    row.map(NewsletterIssue::try_from).transpose()
+}
+
+/// Send a copy of the issue to a single address, without recording it
+/// against the issue: it does not count as a delivery.
+#[tracing::instrument(
+    name = "Send a test copy of a newsletter issue",
+    skip(email_client, renderer, recipient, issue, layout),
+    fields(newsletter_issue_id=%issue.newsletter_issue_id)
+)]
+pub async fn send_test_copy(
+    email_client: &EmailClient,
+    renderer: &IssueRenderer,
+    recipient: &SubscriberEmail,
+    issue: &NewsletterIssue,
+    layout: Option<&EmailLayout>,
+) -> Result<(), anyhow::Error> {
+    let templates = renderer.templates(issue, layout)?;
+    let unsubscribe_url = renderer.unsubscribe_links().sample_url();
+    // Drafts are not in the archive yet: the link only works once the issue is sent
+    let view_in_browser_url = renderer.view_in_browser_url(issue);
+    let email = templates.render(&MergeValues {
+        name: None,
+        email: recipient.as_ref(),
+        unsubscribe_url: Some(&unsubscribe_url),
+        view_in_browser_url: view_in_browser_url.as_deref(),
+        confirmation_url: None,
+    });
+    email_client
+        .send_email(
+            recipient,
+            &format!("[Test] {}", email.subject),
+            &email.html_content,
+            &email.text_content,
+        )
+        .await
+        .context("Failed to send a test copy of a newsletter issue.")?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Publish a newsletter issue",
+    skip(pool, email_client, renderer, issue, request_id, shutdown),
+    fields(newsletter_issue_id=%issue.newsletter_issue_id)
+)]
+pub async fn publish_newsletter_issue(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    renderer: &IssueRenderer,
+    issue: &NewsletterIssue,
+    request_id: Option<&RequestId>,
+    shutdown: &CancellationToken,
+) -> Result<(), PublishError> {
+    issue.check_merge_tags()?;
+    // Claimed before sending anything, so that two concurrent requests cannot both send it.
+    // A scheduled issue can be sent right away: the scheduler will not find it anymore.
+    // The latest version of its layout is pinned, see `get_issue_layout`.
+    let claimed = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = now(), layout_version = (
+            SELECT MAX(version) FROM email_layout_versions v
+            WHERE v.layout_id = newsletter_issues.layout_id
+        )
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        "#,
+        issue.newsletter_issue_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark a newsletter issue as sending.")?;
+    if claimed.rows_affected() == 0 {
+        return Err(PublishError::NotADraft);
+    }
+    deliver_newsletter_issue(pool, email_client, renderer, issue, request_id, shutdown).await?;
+    Ok(())
+}
+
+/// Send a claimed issue - see `publish_newsletter_issue` and `claim_due_issue` -
+/// to every confirmed subscriber it has not been delivered to yet, then mark it as sent.
+/// Once `shutdown` is triggered, no more subscribers are sent it: the issue stays in `sending`
+/// until `claim_due_issue` hands it over again.
+// Fetch the confirmed subscribers who have no delivery of the issue yet.
+// Iterate through the whole list:
+//  - Get the subscriber email.
+//  - Send an email out via Postmark.
+//  - Keep a record of the delivery, failed or not.
+// A failed delivery does not stop the others: it can be found in the deliveries of the issue.
+#[tracing::instrument(
+    name = "Deliver a newsletter issue",
+    skip(pool, email_client, renderer, issue, request_id, shutdown),
+    fields(newsletter_issue_id=%issue.newsletter_issue_id)
+)]
+pub async fn deliver_newsletter_issue(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    renderer: &IssueRenderer,
+    issue: &NewsletterIssue,
+    request_id: Option<&RequestId>,
+    shutdown: &CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let layout = get_issue_layout(pool, issue.newsletter_issue_id).await?;
+    let templates = renderer.templates(issue, layout.as_ref())?;
+    let view_in_browser_url = renderer.view_in_browser_url(issue);
+    let tracked = renderer.is_tracked(issue);
+    let subscribers = get_confirmed_subscribers(pool, issue.newsletter_issue_id).await?;
+    let mut claim_renewed_at = std::time::Instant::now();
+    for subscriber in subscribers {
+        if shutdown.is_cancelled() {
+            tracing::info!("Shutting down: the delivery of the newsletter issue is interrupted.");
+            return Ok(());
+        }
+        if claim_renewed_at.elapsed() >= SENDING_CLAIM_RENEWAL {
+            renew_sending_claim(pool, issue.newsletter_issue_id).await?;
+            claim_renewed_at = std::time::Instant::now();
+        }
+        // The subscriber forces us to handle both the happy and the unhappy case
+        match subscriber {
+            Ok(subscriber) => {
+                let unsubscribe_url = if tracked {
+                    renderer
+                        .unsubscribe_links()
+                        .url_for_issue(subscriber.id, issue.newsletter_issue_id)
+                } else {
+                    renderer.unsubscribe_links().url_for(subscriber.id)
+                };
+                let mut email = templates.render(&MergeValues {
+                    name: Some(&subscriber.name),
+                    email: subscriber.email.as_ref(),
+                    unsubscribe_url: Some(&unsubscribe_url),
+                    view_in_browser_url: view_in_browser_url.as_deref(),
+                    confirmation_url: None,
+                });
+                if tracked {
+                    email.html_content = renderer.tracking_links().track(
+                        &email.html_content,
+                        issue.newsletter_issue_id,
+                        subscriber.id,
+                    );
+                }
+                let outcome = email_client
+                    .send_email(
+                        &subscriber.email,
+                        &email.subject,
+                        &email.html_content,
+                        &email.text_content,
+                    )
+                    .await;
+                // The email is gone, or not: a missing record must not stop the issue
+                if let Err(e) = record_email_delivery(
+                    pool,
+                    &subscriber.email,
+                    &email.subject,
+                    Some(issue.newsletter_issue_id),
+                    request_id,
+                    outcome.is_ok(),
+                    outcome.as_ref().ok().and_then(Option::as_deref),
+                )
+                .await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to record the delivery of a newsletter issue."
+                    );
+                }
+                if let Err(e) = outcome {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send the newsletter issue to a subscriber. Moving on to the next one."
+                    );
+                }
+            }
+            Err(error) => {
+                tracing::warn!(
+                    // We record the error chain as a structured field
+                    // on the log record.
+                    error.cause.chain = ?error,
+                    error.message = %error,
+                    // Using `\` to split a long string literal over
+                    // two lines, without creating a `\n` character.
+                    "Skipping a confirmed subscriber. \
+                    Their stored contact details are invalid",
+                );
+            }
+        }
+    }
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sent', published_at = now(), updated_at = now()
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue.newsletter_issue_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark a newsletter issue as published.")?;
+    Ok(())
+}
+
+// Keeps `claim_due_issue` from handing the issue over to another replica while we send it
+#[tracing::instrument(name = "Renew the claim on a newsletter issue", skip(pool))]
+async fn renew_sending_claim(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'sending'
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to renew the claim on a newsletter issue.")?;
+    Ok(())
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    email: SubscriberEmail,
+    name: String,
+}
+
+#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
+async fn get_confirmed_subscribers(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    // We are returning a `Vec` of `Results`'s in the happy case.
+    // This allows the caller to bubble up the errors due to network issues or other
+    // transient failures using the `?` operator, while the compiler
+    // forces them to handle the subtler mapping error.
+    // See http:://sled.rs/errors.html for a deep-dive about this technique.
+) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
+    // We are minimizing the amount of data we are fetching from the DB:
+    // only what the merge tags need.
+    // Less work for the DB and less data over the network!
+    // Leaves out the subscribers who unsubscribed, and those suppressed
+    // after a hard bounce or a spam complaint, see `routes::postmark_webhook`.
+    // Also leaves out those the issue has been delivered to - or failed to be -
+    // before the delivery was interrupted.
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, email, name
+        FROM subscriptions s
+        WHERE status = 'confirmed' AND NOT EXISTS (
+            SELECT 1
+            FROM email_deliveries d
+            WHERE d.newsletter_issue_id = $1 AND d.recipient = s.email
+        )
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Map into the domain type
+    // You might argue that all the emails stored in database are necessarily valid.
+    // We choose to validate again since these emails were market as valid by
+    // a previous version of our app, which now could have changed.
+    let confirmed_subscribers = rows
+        .into_iter()
+        .map(|r| match SubscriberEmail::parse(r.email) {
+            Ok(email) => Ok(ConfirmedSubscriber {
+                id: r.id,
+                email,
+                name: r.name,
+            }),
+            Err(error) => Err(anyhow::anyhow!(error)),
+        })
+        .collect();
+
+    Ok(confirmed_subscribers)
+}
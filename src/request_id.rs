@@ -0,0 +1,129 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web::Data;
+use actix_web::HttpMessage;
+use actix_web_lab::middleware::Next;
+use tracing_actix_web::RootSpan;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Long enough for UUIDs and the ids generated by common proxies and load balancers.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    // Lets code far away from the handler (e.g. `EmailClient`, `e500`)
+    // tag its output without threading the id through every call.
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// Identifies a request across our logs, our responses, the records it creates
+/// and the emails it triggers.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(transparent)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// The id of the request being served, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let is_valid = !s.is_empty()
+            && s.len() <= MAX_REQUEST_ID_LENGTH
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        is_valid.then(|| Self(s.to_owned()))
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Whether an `X-Request-Id` sent by the client is kept.
+/// Only enable it behind a proxy that sets or sanitises the header.
+#[derive(Clone, Copy)]
+pub struct TrustRequestIdHeader(pub bool);
+
+/// Pick the id of the request and echo it in the `X-Request-Id` response header.
+///
+/// We reuse the id `TracingLogger` generated for the root span, unless a trusted
+/// upstream sent a valid one: it then replaces the generated id in the root span too.
+/// It must be registered *inside* `TracingLogger`.
+pub async fn propagate_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_trusted = req
+        .app_data::<Data<TrustRequestIdHeader>>()
+        .is_some_and(|trust| trust.0);
+    let inbound = is_trusted
+        .then(|| req.headers().get(REQUEST_ID_HEADER))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        .and_then(RequestId::parse);
+    let request_id = match inbound {
+        Some(request_id) => {
+            if let Some(root_span) = req.extensions().get::<RootSpan>() {
+                root_span.record("request_id", request_id.as_str());
+            }
+            request_id
+        }
+        None => RequestId(
+            req.extensions()
+                .get::<tracing_actix_web::RequestId>()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        ),
+    };
+    req.extensions_mut().insert(request_id.clone());
+
+    let mut response = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), next.call(req))
+        .await?;
+    response.headers_mut().insert(
+        REQUEST_ID_HEADER,
+        // Generated ids are UUIDs, inbound ones are restricted to visible ASCII
+        HeaderValue::from_str(request_id.as_str()).unwrap(),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request_id::RequestId;
+    use claims::{assert_none, assert_some};
+
+    #[test]
+    fn ids_from_common_proxies_are_accepted() {
+        assert_some!(RequestId::parse("f058ebd6-02f7-4d3f-942e-904344e8cde5"));
+        assert_some!(RequestId::parse("1-67891233-abcdef012345678912345678"));
+        assert_some!(RequestId::parse("req_01.abc_DEF"));
+    }
+
+    #[test]
+    fn empty_ids_are_rejected() {
+        assert_none!(RequestId::parse(""));
+    }
+
+    #[test]
+    fn ids_longer_than_128_characters_are_rejected() {
+        assert_some!(RequestId::parse(&"a".repeat(128)));
+        assert_none!(RequestId::parse(&"a".repeat(129)));
+    }
+
+    #[test]
+    fn ids_that_could_be_used_for_injection_are_rejected() {
+        for id in ["<script>", "id with spaces", "id\r\nSet-Cookie: a=b", "ïd"] {
+            assert_none!(RequestId::parse(id));
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::authentication::{list_api_keys, ApiKeyRecord, ApiScope};
+use crate::utils::{e500, flash_messages, html_page};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+#[derive(Template)]
+#[template(path = "admin/api_keys.html")]
+struct ApiKeysPage {
+    keys: Vec<ApiKeyRecord>,
+    scopes: [ApiScope; 4],
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/api_keys",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The API keys and the creation form", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
+pub async fn api_keys_form(
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(ApiKeysPage {
+        keys: list_api_keys(&pool).await.map_err(e500)?,
+        scopes: ApiScope::ALL,
+        flash_messages: flash_messages(&incoming),
+    })
+}
@@ -0,0 +1,7 @@
+mod get;
+mod post;
+
+pub use get::{__path_api_keys_form, api_keys_form};
+pub use post::{
+    __path_create_api_key, __path_revoke_api_key, create_api_key, revoke_api_key, ApiKeyForm,
+};
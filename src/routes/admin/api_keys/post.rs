@@ -0,0 +1,100 @@
+use crate::authentication::{self, ApiScope, UserId};
+use crate::utils::{e500, html_page, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use askama::Template;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// A list of pairs rather than a struct: each checked scope is sent as its own `scope` field.
+type FormData = Vec<(String, String)>;
+
+#[derive(Template)]
+#[template(path = "admin/api_key_created.html")]
+struct ApiKeyCreatedPage<'a> {
+    key: &'a str,
+    // Always empty: the admin layout has room for them
+    flash_messages: Vec<String>,
+}
+
+/// The fields of `FormData`, for the OpenAPI document.
+#[derive(utoipa::ToSchema)]
+#[allow(dead_code)]
+pub struct ApiKeyForm {
+    name: String,
+    /// Repeated once per scope, e.g. `subscribers:read`
+    scope: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/api_keys",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = ApiKeyForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The new key, shown once", content_type = "text/html"),
+        (status = 303, description = "Invalid form: redirect to `/admin/api_keys`, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Create an API key", skip(form, pool, user_id), fields(user_id=%*user_id))]
+pub async fn create_api_key(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut name = "";
+    let mut scopes = Vec::new();
+    for (field, value) in form.iter() {
+        match field.as_str() {
+            "name" => name = value.trim(),
+            "scope" => scopes.extend(ApiScope::parse(value)),
+            _ => {}
+        }
+    }
+    if name.is_empty() {
+        FlashMessage::error("The API key must have a name.").send();
+        return Ok(see_other("/admin/api_keys"));
+    }
+    if scopes.is_empty() {
+        FlashMessage::error("The API key must have at least one scope.").send();
+        return Ok(see_other("/admin/api_keys"));
+    }
+    let key = authentication::create_api_key(name, &scopes, **user_id, &pool)
+        .await
+        .map_err(e500)?;
+    // We only store a hash of the key: this page is the only place it is ever shown,
+    // hence no redirect (and no flash message, which would put it in a cookie).
+    html_page(ApiKeyCreatedPage {
+        key: key.expose_secret(),
+        flash_messages: Vec::new(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/api_keys/{key_id}/revoke",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("key_id" = Uuid, Path, description = "The id of the key to revoke")),
+    responses(
+        (status = 303, description = "Redirect to `/admin/api_keys` with the outcome, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Revoke an API key", skip(pool, user_id), fields(user_id=%*user_id))]
+pub async fn revoke_api_key(
+    key_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authentication::revoke_api_key(*key_id, &pool)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The API key has been revoked.").send();
+    } else {
+        FlashMessage::error("The API key does not exist or has already been revoked.").send();
+    }
+    Ok(see_other("/admin/api_keys"))
+}
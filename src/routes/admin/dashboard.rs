@@ -1,17 +1,36 @@
 use crate::session_state::TypedSession;
 
-use crate::utils::e500;
-use actix_web::http::header::ContentType;
+use crate::utils::{e500, flash_messages, html_page};
 use actix_web::web;
 use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
+use askama::Template;
 use reqwest::header::LOCATION;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+struct DashboardPage {
+    username: String,
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The dashboard", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
 pub async fn admin_dashboard(
     session: TypedSession,
     pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
     let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
         get_username(user_id, &pool).await.map_err(e500)?
@@ -20,29 +39,10 @@ pub async fn admin_dashboard(
             .insert_header((LOCATION, "/login"))
             .finish());
     };
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-            <html lang="en">
-                <head>
-                <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                    <title>Admin dashboard</title>
-                </head>
-                <body>
-                    <p>Welcome {username}!</p>
-                    <p>Available actions:</p>
-                    <ol>
-                        <li><a href="/admin/password">Change password</a></li>
-                        <li>
-                        <form name="logoutForm" action="/admin/logout" method="post">
-                            <input type="submit" value="Logout">
-                        </form>
-                        </li>
-                    </ol>
-                </body>
-            </html>"#
-        )))
+    html_page(DashboardPage {
+        username,
+        flash_messages: flash_messages(&incoming),
+    })
 }
 
 #[tracing::instrument(name = "Get username", skip(pool))]
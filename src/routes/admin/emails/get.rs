@@ -0,0 +1,139 @@
+use super::super::layouts::TemplateVersionPage;
+use crate::transactional_emails::{
+    get_current_template, get_template_version, list_template_versions, EmailTemplate,
+    TemplateVersionSummary, TransactionalEmail,
+};
+use crate::utils::{e500, flash_messages, html_page, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use askama::Template;
+use sqlx::PgPool;
+
+struct EmailSummary {
+    kind: TransactionalEmail,
+    // `None` while the built-in default is used
+    version: Option<i32>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/emails.html")]
+struct TransactionalEmailsPage {
+    emails: Vec<EmailSummary>,
+    flash_messages: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/email.html")]
+struct EditTransactionalEmailPage {
+    kind: TransactionalEmail,
+    template: EmailTemplate,
+    // e.g. "`{{ name }}` or `{{ confirmation_url }}`"
+    tags: String,
+    versions: Vec<TemplateVersionSummary>,
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/emails",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The emails sent by the application itself", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
+pub async fn transactional_emails_page(
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut emails = Vec::new();
+    for kind in TransactionalEmail::ALL {
+        let versions = list_template_versions(&pool, *kind).await.map_err(e500)?;
+        emails.push(EmailSummary {
+            kind: *kind,
+            version: versions.first().map(|v| v.version),
+        });
+    }
+    html_page(TransactionalEmailsPage {
+        emails,
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/emails/{kind}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("kind" = String, Path, description = "The email, e.g. `confirmation`")),
+    responses(
+        (status = 200, description = "The current version of the email, to edit, and its history", content_type = "text/html"),
+        (status = 303, description = "Unknown email: redirect to `/admin/emails`, or to `/login`"),
+    )
+)]
+pub async fn edit_transactional_email_form(
+    kind: web::Path<String>,
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Ok(kind) = TransactionalEmail::parse(&kind) else {
+        return Ok(email_not_found());
+    };
+    let tags: Vec<String> = kind
+        .known_tags()
+        .iter()
+        .map(|tag| tag.to_string())
+        .collect();
+    html_page(EditTransactionalEmailPage {
+        kind,
+        template: get_current_template(&pool, kind).await.map_err(e500)?,
+        tags: tags.join(", "),
+        versions: list_template_versions(&pool, kind).await.map_err(e500)?,
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/emails/{kind}/versions/{version}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(
+        ("kind" = String, Path, description = "The email, e.g. `confirmation`"),
+        ("version" = i32, Path, description = "The version, starting from 1"),
+    ),
+    responses(
+        (status = 200, description = "The email, as it was in this version", content_type = "text/html"),
+        (status = 303, description = "Unknown email or version: redirect to `/admin/emails`, or to `/login`"),
+    )
+)]
+pub async fn transactional_email_version_page(
+    path: web::Path<(String, i32)>,
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (kind, version) = path.into_inner();
+    let Ok(kind) = TransactionalEmail::parse(&kind) else {
+        return Ok(email_not_found());
+    };
+    let Some(template) = get_template_version(&pool, kind, version)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(email_not_found());
+    };
+    html_page(TemplateVersionPage {
+        title: format!("{}, version {}", kind.description(), version),
+        subject: Some(template.subject),
+        html_template: template.html_template,
+        text_template: template.text_template,
+        back_url: format!("/admin/emails/{}", kind),
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+pub(super) fn email_not_found() -> HttpResponse {
+    FlashMessage::error("There is no such email.").send();
+    see_other("/admin/emails")
+}
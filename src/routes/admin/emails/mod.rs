@@ -0,0 +1,11 @@
+mod get;
+mod post;
+
+pub use get::{
+    __path_edit_transactional_email_form, __path_transactional_email_version_page,
+    __path_transactional_emails_page, edit_transactional_email_form,
+    transactional_email_version_page, transactional_emails_page,
+};
+pub use post::{
+    __path_update_transactional_email, update_transactional_email, TransactionalEmailForm,
+};
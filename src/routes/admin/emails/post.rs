@@ -0,0 +1,63 @@
+use super::get::email_not_found;
+use crate::issue_renderer::IssueRenderer;
+use crate::transactional_emails::{save_template, EmailTemplate, TransactionalEmail};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct TransactionalEmailForm {
+    subject: String,
+    html_template: String,
+    text_template: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/emails/{kind}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("kind" = String, Path, description = "The email, e.g. `confirmation`")),
+    request_body(content = TransactionalEmailForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the email with the outcome, to `/admin/emails` or to `/login`"),
+    )
+)]
+#[tracing::instrument(
+    name = "Save a new version of a transactional email",
+    skip(form, pool, issue_renderer)
+)]
+pub async fn update_transactional_email(
+    kind: web::Path<String>,
+    form: web::Form<TransactionalEmailForm>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Ok(kind) = TransactionalEmail::parse(&kind) else {
+        return Ok(email_not_found());
+    };
+    let TransactionalEmailForm {
+        subject,
+        html_template,
+        text_template,
+    } = form.0;
+    let back = format!("/admin/emails/{}", kind);
+    if subject.trim().is_empty() {
+        FlashMessage::error("The email must have a subject.").send();
+        return Ok(see_other(&back));
+    }
+    // Sanitized once and for all, like the bodies of issues before they are sent
+    let template = EmailTemplate {
+        subject,
+        html_template: issue_renderer.email_html().prepare(&html_template),
+        text_template,
+    };
+    if let Err(message) = template.parse(kind) {
+        FlashMessage::error(message).send();
+        return Ok(see_other(&back));
+    }
+    let version = save_template(&pool, kind, &template).await.map_err(e500)?;
+    FlashMessage::info(format!("Version {} of the email has been saved.", version)).send();
+    Ok(see_other(&back))
+}
@@ -0,0 +1,131 @@
+use crate::email_layouts::{
+    get_layout, list_layout_versions, list_layouts, EmailLayout, LayoutSummary,
+    LayoutVersionSummary, DEFAULT_HTML_TEMPLATE, DEFAULT_TEXT_TEMPLATE,
+};
+use crate::utils::{e500, flash_messages, html_page, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Template)]
+#[template(path = "admin/layouts.html")]
+struct LayoutsPage {
+    layouts: Vec<LayoutSummary>,
+    // Pre-fill the creation form
+    default_html: &'static str,
+    default_text: &'static str,
+    flash_messages: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/layout.html")]
+struct EditLayoutPage {
+    layout: EmailLayout,
+    versions: Vec<LayoutVersionSummary>,
+    flash_messages: Vec<String>,
+}
+
+/// A past version of a layout or of a transactional email.
+#[derive(Template)]
+#[template(path = "admin/template_version.html")]
+pub(in crate::routes::admin) struct TemplateVersionPage {
+    pub title: String,
+    pub subject: Option<String>,
+    pub html_template: String,
+    pub text_template: String,
+    pub back_url: String,
+    pub flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/layouts",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The email layouts and the creation form", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
+pub async fn layouts_page(
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(LayoutsPage {
+        layouts: list_layouts(&pool).await.map_err(e500)?,
+        default_html: DEFAULT_HTML_TEMPLATE,
+        default_text: DEFAULT_TEXT_TEMPLATE,
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/layouts/{id}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the layout")),
+    responses(
+        (status = 200, description = "The latest version of the layout, to edit, and its history", content_type = "text/html"),
+        (status = 303, description = "Unknown layout: redirect to `/admin/layouts`, or to `/login`"),
+    )
+)]
+pub async fn edit_layout_form(
+    layout_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(layout) = get_layout(&pool, *layout_id, None).await.map_err(e500)? else {
+        return Ok(layout_not_found());
+    };
+    html_page(EditLayoutPage {
+        versions: list_layout_versions(&pool, layout.layout_id)
+            .await
+            .map_err(e500)?,
+        layout,
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/layouts/{id}/versions/{version}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(
+        ("id" = Uuid, Path, description = "The id of the layout"),
+        ("version" = i32, Path, description = "The version, starting from 1"),
+    ),
+    responses(
+        (status = 200, description = "The layout, as it was in this version", content_type = "text/html"),
+        (status = 303, description = "Unknown layout or version: redirect to `/admin/layouts`, or to `/login`"),
+    )
+)]
+pub async fn layout_version_page(
+    path: web::Path<(Uuid, i32)>,
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (layout_id, version) = path.into_inner();
+    let Some(layout) = get_layout(&pool, layout_id, Some(version))
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(layout_not_found());
+    };
+    html_page(TemplateVersionPage {
+        title: format!("{}, version {}", layout.name, layout.version),
+        subject: None,
+        html_template: layout.html_template,
+        text_template: layout.text_template,
+        back_url: format!("/admin/layouts/{}", layout.layout_id),
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+pub(super) fn layout_not_found() -> HttpResponse {
+    FlashMessage::error("There is no such layout.").send();
+    see_other("/admin/layouts")
+}
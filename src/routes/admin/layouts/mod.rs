@@ -0,0 +1,12 @@
+mod get;
+mod post;
+
+pub(super) use get::TemplateVersionPage;
+pub use get::{
+    __path_edit_layout_form, __path_layout_version_page, __path_layouts_page, edit_layout_form,
+    layout_version_page, layouts_page,
+};
+pub use post::{
+    __path_create_layout, __path_update_layout, create_layout, update_layout, EditLayoutForm,
+    NewLayoutForm,
+};
@@ -0,0 +1,87 @@
+use super::get::layout_not_found;
+use crate::email_layouts::{self, add_layout_version, check_layout, layout_exists};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct NewLayoutForm {
+    name: String,
+    /// Must contain `{{ content }}` and `{{ unsubscribe_url }}`
+    html_template: String,
+    /// Must contain `{{ content }}` and `{{ unsubscribe_url }}`
+    text_template: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct EditLayoutForm {
+    html_template: String,
+    text_template: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/layouts",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = NewLayoutForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Created: redirect to the layout. Otherwise back to `/admin/layouts`, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Create an email layout", skip(form, pool))]
+pub async fn create_layout(
+    form: web::Form<NewLayoutForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The layout must have a name.").send();
+        return Ok(see_other("/admin/layouts"));
+    }
+    if let Err(message) = check_layout(&form.html_template, &form.text_template) {
+        FlashMessage::error(message).send();
+        return Ok(see_other("/admin/layouts"));
+    }
+    let layout_id =
+        email_layouts::create_layout(&pool, name, &form.html_template, &form.text_template)
+            .await
+            .map_err(e500)?;
+    FlashMessage::info("The layout has been saved.").send();
+    Ok(see_other(&format!("/admin/layouts/{}", layout_id)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/layouts/{id}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the layout")),
+    request_body(content = EditLayoutForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the layout with the outcome, to `/admin/layouts` or to `/login`"),
+    )
+)]
+// Issues already sent keep the version they were sent with
+#[tracing::instrument(name = "Save a new version of an email layout", skip(form, pool))]
+pub async fn update_layout(
+    layout_id: web::Path<Uuid>,
+    form: web::Form<EditLayoutForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !layout_exists(&pool, *layout_id).await.map_err(e500)? {
+        return Ok(layout_not_found());
+    }
+    let back = format!("/admin/layouts/{}", layout_id);
+    if let Err(message) = check_layout(&form.html_template, &form.text_template) {
+        FlashMessage::error(message).send();
+        return Ok(see_other(&back));
+    }
+    let version = add_layout_version(&pool, *layout_id, &form.html_template, &form.text_template)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info(format!("Version {} of the layout has been saved.", version)).send();
+    Ok(see_other(&back))
+}
@@ -0,0 +1,32 @@
+use crate::telemetry::LogFilterHandle;
+use crate::utils::{e500, flash_messages, html_page};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "admin/log_filter.html")]
+struct LogFilterPage {
+    current_filter: String,
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/log_filter",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The log filter form", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
+pub async fn log_filter_form(
+    log_filter: web::Data<LogFilterHandle>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(LogFilterPage {
+        current_filter: log_filter.current().map_err(e500)?,
+        flash_messages: flash_messages(&incoming),
+    })
+}
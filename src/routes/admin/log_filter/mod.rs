@@ -0,0 +1,5 @@
+mod get;
+pub use get::{__path_log_filter_form, log_filter_form};
+
+mod post;
+pub use post::{__path_change_log_filter, change_log_filter, FormData as LogFilterForm};
@@ -0,0 +1,49 @@
+use crate::authentication::UserId;
+use crate::telemetry::{LogFilterError, LogFilterHandle};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = LogFilterForm)]
+pub struct FormData {
+    /// An `EnvFilter` directive, e.g. `info,sqlx=debug`
+    filter: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/log_filter",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = LogFilterForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to `/admin/log_filter` with the outcome, or to `/login`"),
+    )
+)]
+#[tracing::instrument(
+    name = "Change the log filter",
+    skip(form, log_filter, user_id),
+    fields(user_id=%*user_id, filter=%form.filter)
+)]
+pub async fn change_log_filter(
+    form: web::Form<FormData>,
+    log_filter: web::Data<LogFilterHandle>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let filter = form.0.filter.trim();
+    if filter.is_empty() {
+        FlashMessage::error("The log filter cannot be empty.").send();
+        return Ok(see_other("/admin/log_filter"));
+    }
+    match log_filter.reload(filter) {
+        Ok(()) => {
+            // Loud enough to be kept by all but the quietest filters
+            tracing::warn!("The log filter has been changed.");
+            FlashMessage::info("The log filter has been changed.").send();
+        }
+        Err(e @ LogFilterError::InvalidFilter(_)) => FlashMessage::error(e.to_string()).send(),
+        Err(e) => return Err(e500(e)),
+    }
+    Ok(see_other("/admin/log_filter"))
+}
@@ -3,6 +3,15 @@ use crate::utils::{e500, see_other};
 use actix_web::HttpResponse;
 use actix_web_flash_messages::FlashMessage;
 
+#[utoipa::path(
+    post,
+    path = "/admin/logout",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 303, description = "Logged out: redirect to `/login`"),
+    )
+)]
 pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         Ok(see_other("/login"))
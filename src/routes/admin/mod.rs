@@ -1,9 +1,25 @@
+mod api_keys;
 mod dashboard;
+mod emails;
+mod layouts;
+mod log_filter;
 mod logout;
 mod newsletter;
 mod password;
 
-pub use dashboard::admin_dashboard;
-pub use logout::log_out;
+pub use api_keys::*;
+pub use dashboard::{__path_admin_dashboard, admin_dashboard};
+pub use emails::*;
+pub use layouts::*;
+pub use log_filter::*;
+pub use logout::{__path_log_out, log_out};
 pub use newsletter::*;
 pub use password::*;
+
+use actix_web::web;
+
+/// Newsletter issues and layouts are posted as forms: the default limit of 16 KB
+/// would turn away issues long before Gmail clips them, at 102 KB.
+pub fn admin_form_config() -> web::FormConfig {
+    web::FormConfig::default().limit(1024 * 1024)
+}
@@ -0,0 +1,188 @@
+use crate::authentication::{get_user_email, UserId};
+use crate::email_html::clipping_warning;
+use crate::email_layouts::{get_issue_layout, list_layouts, EmailLayout, LayoutSummary};
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::{get_newsletter_issue, NewsletterIssue};
+use crate::utils::{e500, flash_messages, html_page, see_other};
+use actix_web::http::header::{ContentType, CONTENT_SECURITY_POLICY, X_FRAME_OPTIONS};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The HTML body is written by an admin for mail clients: it is shown in a sandboxed frame
+// of the preview page, with the remote images and inline styles emails rely on.
+const PREVIEW_POLICY: &str = "sandbox; default-src 'none'; img-src * data:; \
+    style-src * 'unsafe-inline'; font-src *; frame-ancestors 'self'";
+
+#[derive(Template)]
+#[template(path = "admin/draft.html")]
+struct EditDraftPage {
+    issue: NewsletterIssue,
+    // Pre-fills the test copy form
+    test_email: String,
+    layouts: Vec<LayoutSummary>,
+    clipping_warning: Option<String>,
+    flash_messages: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/draft_preview.html")]
+struct DraftPreviewPage {
+    issue: NewsletterIssue,
+    // The version it was sent with, see `get_issue_layout`
+    layout: Option<EmailLayout>,
+    text_body: String,
+    clipping_warning: Option<String>,
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/drafts/{id}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The draft edition form", content_type = "text/html"),
+        (status = 303, description = "Not a draft: redirect to its preview, to `/admin/newsletters` or to `/login`"),
+    )
+)]
+pub async fn edit_draft_form(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+    user_id: web::ReqData<UserId>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    if !issue.is_draft() {
+        return Ok(see_other(&format!(
+            "/admin/newsletters/drafts/{}/preview",
+            issue.newsletter_issue_id
+        )));
+    }
+    let test_email = get_user_email(**user_id, &pool)
+        .await
+        .map_err(e500)?
+        .unwrap_or_default();
+    let layout = get_issue_layout(&pool, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    html_page(EditDraftPage {
+        clipping_warning: clipping_warning(&issue_renderer.html_body(&issue, layout.as_ref())),
+        issue,
+        test_email,
+        layouts: list_layouts(&pool).await.map_err(e500)?,
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/drafts/{id}/preview",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The HTML and plain-text versions of the issue", content_type = "text/html"),
+        (status = 303, description = "Unknown issue: redirect to `/admin/newsletters`, or to `/login`"),
+    )
+)]
+pub async fn preview_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+    incoming: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    let layout = get_issue_layout(&pool, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    html_page(DraftPreviewPage {
+        clipping_warning: clipping_warning(&issue_renderer.html_body(&issue, layout.as_ref())),
+        text_body: issue_renderer.text_body(&issue, layout.as_ref()),
+        issue,
+        layout,
+        flash_messages: flash_messages(&incoming),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/drafts/{id}/preview/html",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The HTML body of the issue, as sent", content_type = "text/html"),
+        (status = 303, description = "Unknown issue: redirect to `/admin/newsletters`, or to `/login`"),
+    )
+)]
+pub async fn preview_draft_html(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    let layout = get_issue_layout(&pool, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    // `set_security_headers` keeps the headers set here
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header((CONTENT_SECURITY_POLICY, PREVIEW_POLICY))
+        .insert_header((X_FRAME_OPTIONS, "SAMEORIGIN"))
+        .body(issue_renderer.html_body(&issue, layout.as_ref())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/drafts/{id}/preview/text",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The plain-text body of the issue, as sent", content_type = "text/plain"),
+        (status = 303, description = "Unknown issue: redirect to `/admin/newsletters`, or to `/login`"),
+    )
+)]
+pub async fn preview_draft_text(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    let layout = get_issue_layout(&pool, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(issue_renderer.text_body(&issue, layout.as_ref())))
+}
+
+pub(super) fn issue_not_found() -> HttpResponse {
+    FlashMessage::error("There is no such newsletter issue.").send();
+    see_other("/admin/newsletters")
+}
@@ -0,0 +1,14 @@
+mod get;
+mod post;
+
+pub use get::{
+    __path_edit_draft_form, __path_preview_draft, __path_preview_draft_html,
+    __path_preview_draft_text, edit_draft_form, preview_draft, preview_draft_html,
+    preview_draft_text,
+};
+pub use post::{
+    __path_cancel_schedule, __path_create_draft, __path_publish_draft, __path_schedule_draft,
+    __path_send_draft_test_copy, __path_update_archive_visibility, __path_update_draft,
+    cancel_schedule, create_draft, publish_draft, schedule_draft, send_draft_test_copy,
+    update_archive_visibility, update_draft, ArchiveForm, DraftForm, ScheduleForm, TestCopyForm,
+};
@@ -0,0 +1,409 @@
+use super::get::issue_not_found;
+use crate::authentication::{set_user_email, UserId};
+use crate::domain::{IssueSchedule, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::email_layouts::{get_issue_layout, parse_layout_choice, LayoutChoiceError};
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::{
+    cancel_scheduled_issue, get_newsletter_issue, insert_newsletter_issue,
+    publish_newsletter_issue, schedule_newsletter_issue, send_test_copy, set_hidden_from_archive,
+    update_draft as update_newsletter_draft, IssueContent, NewsletterIssue, PublishError,
+};
+use crate::request_id::RequestId;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct DraftForm {
+    title: String,
+    /// Raw HTML mode: ignored when `markdown_content` is set
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    html_content: String,
+    /// Generates both `text_content` and `html_content`
+    #[serde(default)]
+    markdown_content: String,
+    /// The id of the layout to wrap the issue in, empty for none
+    #[serde(default)]
+    layout_id: String,
+    /// Keep the issue out of the public archive and its feeds once sent
+    #[serde(default)]
+    hidden_from_archive: bool,
+    /// Record opens and clicks, unless `tracking.enabled` is unset
+    #[serde(default)]
+    tracked: bool,
+}
+
+impl DraftForm {
+    async fn into_parts(self, pool: &PgPool) -> Result<(String, IssueContent), LayoutChoiceError> {
+        let layout_id = parse_layout_choice(pool, &self.layout_id).await?;
+        let content =
+            IssueContent::from_form(self.text_content, self.html_content, self.markdown_content)
+                .with_layout(layout_id)
+                .hide_from_archive(self.hidden_from_archive)
+                .track(self.tracked);
+        Ok((self.title, content))
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct TestCopyForm {
+    /// Remembered as the address of the admin for the next test copies
+    email: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ScheduleForm {
+    /// As sent by `<input type="datetime-local">`, e.g. `2023-07-11T09:00`
+    scheduled_for: String,
+    /// The timezone `scheduled_for` is in, e.g. `Europe/Paris`
+    timezone: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ArchiveForm {
+    /// Unchecked checkboxes are not submitted: missing means shown
+    #[serde(default)]
+    hidden_from_archive: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = DraftForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Saved: redirect to the draft, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Create a newsletter draft", skip(form, pool))]
+pub async fn create_draft(
+    form: web::Form<DraftForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (title, content) = match form.0.into_parts(&pool).await {
+        Ok(parts) => parts,
+        Err(e @ LayoutChoiceError::UnknownLayout) => {
+            FlashMessage::error(e.to_string()).send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    let newsletter_issue_id = insert_newsletter_issue(&pool, &title, &content)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The draft has been saved.").send();
+    warn_about_merge_tags(&title, &content);
+    Ok(see_other(&draft_path(newsletter_issue_id)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts/{id}",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    request_body(content = DraftForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the draft, to its preview if it is not a draft anymore, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Update a newsletter draft", skip(form, pool))]
+pub async fn update_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<DraftForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (title, content) = match form.0.into_parts(&pool).await {
+        Ok(parts) => parts,
+        Err(e @ LayoutChoiceError::UnknownLayout) => {
+            FlashMessage::error(e.to_string()).send();
+            return Ok(see_other(&draft_path(*newsletter_issue_id)));
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    if update_newsletter_draft(&pool, *newsletter_issue_id, &title, &content)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The draft has been saved.").send();
+        warn_about_merge_tags(&title, &content);
+        Ok(see_other(&draft_path(*newsletter_issue_id)))
+    } else {
+        // Sent issues are only shown by their preview
+        FlashMessage::error("Only drafts can be edited.").send();
+        Ok(see_other(&preview_path(*newsletter_issue_id)))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts/{id}/test",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    request_body(content = TestCopyForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the draft with the outcome, or to `/login`"),
+    )
+)]
+#[tracing::instrument(
+    name = "Send a test copy of a newsletter draft",
+    skip(form, pool, email_client, issue_renderer, user_id),
+    fields(user_id=%*user_id)
+)]
+pub async fn send_draft_test_copy(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<TestCopyForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    issue_renderer: web::Data<IssueRenderer>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(message) => {
+            FlashMessage::error(message).send();
+            return Ok(see_other(&draft_path(issue.newsletter_issue_id)));
+        }
+    };
+    set_user_email(**user_id, &email, &pool)
+        .await
+        .map_err(e500)?;
+    if let Err(e) = issue.check_merge_tags() {
+        FlashMessage::error(e.to_string()).send();
+        return Ok(see_other(&draft_path(issue.newsletter_issue_id)));
+    }
+    let layout = get_issue_layout(&pool, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    send_test_copy(
+        &email_client,
+        &issue_renderer,
+        &email,
+        &issue,
+        layout.as_ref(),
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info(format!("A test copy has been sent to {}.", email)).send();
+    Ok(see_other(&draft_path(issue.newsletter_issue_id)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts/{id}/publish",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 303, description = "Published: redirect to `/admin/newsletters`. Otherwise back to the draft, or to `/login`"),
+    )
+)]
+#[tracing::instrument(
+    name = "Publish a newsletter draft",
+    skip(pool, email_client, issue_renderer, request_id, shutdown)
+)]
+pub async fn publish_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    issue_renderer: web::Data<IssueRenderer>,
+    request_id: web::ReqData<RequestId>,
+    shutdown: web::Data<CancellationToken>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    if is_incomplete(&issue) {
+        FlashMessage::error("A title and both contents are required to publish.").send();
+        return Ok(see_other(&draft_path(issue.newsletter_issue_id)));
+    }
+    // Publishing claims the draft: submitting the form twice cannot send it twice
+    match publish_newsletter_issue(
+        &pool,
+        &email_client,
+        &issue_renderer,
+        &issue,
+        Some(&request_id),
+        &shutdown,
+    )
+    .await
+    {
+        Ok(()) => {
+            FlashMessage::info("The newsletter issue has been published!").send();
+            Ok(see_other("/admin/newsletters"))
+        }
+        Err(e @ PublishError::InvalidMergeTags(_)) => {
+            FlashMessage::error(e.to_string()).send();
+            Ok(see_other(&draft_path(issue.newsletter_issue_id)))
+        }
+        Err(e @ PublishError::NotADraft) => {
+            FlashMessage::error(e.to_string()).send();
+            Ok(see_other("/admin/newsletters"))
+        }
+        Err(e) => Err(e500(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts/{id}/schedule",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    request_body(content = ScheduleForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Scheduled or rescheduled: redirect to the preview. Otherwise back to the issue, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Schedule a newsletter draft", skip(form, pool))]
+pub async fn schedule_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<ScheduleForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(issue_not_found());
+    };
+    // Scheduled issues cannot be edited: they are rescheduled from their preview
+    let back = if issue.is_draft() {
+        draft_path(issue.newsletter_issue_id)
+    } else {
+        preview_path(issue.newsletter_issue_id)
+    };
+    if is_incomplete(&issue) {
+        FlashMessage::error("A title and both contents are required to schedule.").send();
+        return Ok(see_other(&back));
+    }
+    // The scheduler cannot tell anyone about a typo in a merge tag
+    if let Err(e) = issue.check_merge_tags() {
+        FlashMessage::error(e.to_string()).send();
+        return Ok(see_other(&back));
+    }
+    let schedule = match IssueSchedule::parse(&form.scheduled_for, &form.timezone, Utc::now()) {
+        Ok(schedule) => schedule,
+        Err(message) => {
+            FlashMessage::error(message).send();
+            return Ok(see_other(&back));
+        }
+    };
+    if schedule_newsletter_issue(&pool, issue.newsletter_issue_id, &schedule)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info(format!(
+            "The newsletter issue has been scheduled for {}.",
+            schedule
+        ))
+        .send();
+        Ok(see_other(&preview_path(issue.newsletter_issue_id)))
+    } else {
+        FlashMessage::error(PublishError::NotADraft.to_string()).send();
+        Ok(see_other("/admin/newsletters"))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts/{id}/cancel",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 303, description = "Cancelled: redirect to the draft. Otherwise to the preview, or to `/login`"),
+    )
+)]
+#[tracing::instrument(name = "Cancel a scheduled newsletter issue", skip(pool))]
+pub async fn cancel_schedule(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if cancel_scheduled_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The schedule has been cancelled: the issue is a draft again.").send();
+        Ok(see_other(&draft_path(*newsletter_issue_id)))
+    } else {
+        // Too late: the scheduler picked it up already
+        FlashMessage::error("This newsletter issue is not scheduled.").send();
+        Ok(see_other(&preview_path(*newsletter_issue_id)))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts/{id}/archive",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    request_body(content = ArchiveForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the preview, to `/admin/newsletters` for unknown issues, or to `/login`"),
+    )
+)]
+#[tracing::instrument(
+    name = "Hide or show a newsletter issue in the archive",
+    skip(form, pool)
+)]
+pub async fn update_archive_visibility(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Form<ArchiveForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Sent issues cannot be edited anymore, but they can still be taken out of the archive
+    if !set_hidden_from_archive(&pool, *newsletter_issue_id, form.hidden_from_archive)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(issue_not_found());
+    }
+    if form.hidden_from_archive {
+        FlashMessage::info("The newsletter issue is hidden from the public archive.").send();
+    } else {
+        FlashMessage::info("The newsletter issue is no longer hidden from the public archive.")
+            .send();
+    }
+    Ok(see_other(&preview_path(*newsletter_issue_id)))
+}
+
+// Drafts are saved as they are, typos included: they are rejected when publishing
+fn warn_about_merge_tags(title: &str, content: &IssueContent) {
+    if let Err(e) = content.check_merge_tags(title) {
+        FlashMessage::warning(e.to_string()).send();
+    }
+}
+
+fn is_incomplete(issue: &NewsletterIssue) -> bool {
+    issue.title.trim().is_empty()
+        || issue.text_content.trim().is_empty()
+        || issue.html_content.trim().is_empty()
+}
+
+fn draft_path(newsletter_issue_id: Uuid) -> String {
+    format!("/admin/newsletters/drafts/{}", newsletter_issue_id)
+}
+
+fn preview_path(newsletter_issue_id: Uuid) -> String {
+    format!("{}/preview", draft_path(newsletter_issue_id))
+}
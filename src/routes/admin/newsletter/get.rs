@@ -1,59 +1,39 @@
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use crate::email_layouts::{list_layouts, LayoutSummary};
+use crate::newsletter_issues::{list_newsletter_issues, NewsletterIssue};
+use crate::utils::{e500, flash_messages, html_page};
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
 
+#[derive(Template)]
+#[template(path = "admin/newsletter.html")]
+struct PublishNewsletterPage {
+    idempotency_key: Uuid,
+    issues: Vec<NewsletterIssue>,
+    layouts: Vec<LayoutSummary>,
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The newsletter issues and the publishing form", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
 pub async fn publish_newsletter_form(
-    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    incoming: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let mut msg_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-    let idempotency_key = uuid::Uuid::new_v4();
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-            <html lang="en">
-            <head>
-                <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                <title>Publish Newsletter Issue</title>
-            </head>
-            <body>
-                {msg_html}
-                <form action="/admin/newsletters" method="post">
-                    <label>Title:<br>
-                        <input
-                            type="text"
-                            placeholder="Enter the issue title"
-                            name="title"
-                        >
-                    </label>
-                    <br>
-                    <label>Plain text content:<br>
-                        <textarea
-                            placeholder="Enter the content in plain text"
-                            name="text_content"
-                            rows="20"
-                            cols="50"
-                        ></textarea>
-                    </label>
-                    <br>
-                    <label>HTML content:<br>
-                        <textarea
-                            placeholder="Enter the content in HTML format"
-                            name="html_content"
-                            rows="20"
-                            cols="50"
-                        ></textarea>
-                    </label>
-                    <br>
-                    <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
-                    <button type="submit">Publish</button>
-                </form>
-                <p><a href="/admin/dashboard">&lt;- Back</a></p>
-            </body>
-            </html>"#,
-        )))
+    html_page(PublishNewsletterPage {
+        idempotency_key: Uuid::new_v4(),
+        issues: list_newsletter_issues(&pool).await.map_err(e500)?,
+        layouts: list_layouts(&pool).await.map_err(e500)?,
+        flash_messages: flash_messages(&incoming),
+    })
 }
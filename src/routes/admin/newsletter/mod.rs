@@ -1,5 +1,9 @@
+mod drafts;
 mod get;
 mod post;
+mod stats;
 
-pub use get::publish_newsletter_form;
-pub use post::publish_newsletter;
+pub use drafts::*;
+pub use get::{__path_publish_newsletter_form, publish_newsletter_form};
+pub use post::{__path_publish_newsletter, publish_newsletter, FormData as NewsletterForm};
+pub use stats::{__path_issue_stats, issue_stats};
@@ -1,44 +1,85 @@
 use crate::authentication::UserId;
-use crate::domain::SubscriberEmail;
+use crate::domain::IssueSchedule;
 use crate::email_client::EmailClient;
+use crate::email_layouts::{parse_layout_choice, LayoutChoiceError};
 use crate::idempotency::IdempotencyKey;
 use crate::idempotency::{get_saved_response, save_response};
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::{
+    get_newsletter_issue, insert_newsletter_issue, publish_newsletter_issue,
+    schedule_newsletter_issue, IssueContent,
+};
+use crate::request_id::RequestId;
 use crate::utils::e400;
 use crate::utils::{e500, see_other};
 use actix_web::web::ReqData;
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::Utc;
 use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = NewsletterForm)]
 pub struct FormData {
     title: String,
+    /// Raw HTML mode: ignored when `markdown_content` is set
+    #[serde(default)]
     text_content: String,
+    #[serde(default)]
     html_content: String,
+    /// Generates both `text_content` and `html_content`
+    #[serde(default)]
+    markdown_content: String,
+    /// The id of the layout to wrap the issue in, empty for none
+    #[serde(default)]
+    layout_id: String,
+    /// Keep the issue out of the public archive and its feeds
+    #[serde(default)]
+    hidden_from_archive: bool,
+    /// Record opens and clicks, unless `tracking.enabled` is unset
+    #[serde(default)]
+    tracked: bool,
     idempotency_key: String,
+    /// Send it later instead: as sent by `<input type="datetime-local">`, e.g. `2023-07-11T09:00`
+    #[serde(default)]
+    scheduled_for: String,
+    /// The timezone `scheduled_for` is in, e.g. `Europe/Paris`. Defaults to UTC.
+    #[serde(default)]
+    timezone: String,
 }
 
-// Naive approach:
-// Retrieve the newsletter issue from details form the body of the incoming API call.
-// Fetch the list of confirmed subscribers from the database.
-// Iterate through the whole list:
-//  - Get the subscriber email.
-//  - Send an email out via Postmark.
+// Store the newsletter issue from the details in the body of the incoming call,
+// then send it out to every confirmed subscriber - or queue it if a send time is given.
 
-// The actix extractor to parse bodyData out od call is JSON.
-// when dealing with an HTML form it would be `application/x-ww-form-urlencoded`
+// The actix extractor to parse the body of the call is `Form`:
+// the HTML form on `/admin/newsletters` submits `application/x-www-form-urlencoded`
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = NewsletterForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Published or scheduled: redirect to `/admin/newsletters`, or to `/login`"),
+        (status = 400, description = "Missing content, unknown merge tag or layout, invalid idempotency key or send time"),
+    )
+)]
 #[tracing::instrument(
     name="Publish a newsletter issue",
-    skip(form, pool, email_client, user_id),
+    skip(form, pool, email_client, issue_renderer, user_id, request_id, shutdown),
     fields(user_id=%*user_id)
 )]
 pub async fn publish_newsletter(
-    form: web::Json<FormData>,
+    form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
+    issue_renderer: web::Data<IssueRenderer>,
     // Inject the user id extracted from the user session
     user_id: ReqData<UserId>,
+    request_id: ReqData<RequestId>,
+    shutdown: web::Data<CancellationToken>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     // We must destructure the form to avoid upsetting the borrow-checker
@@ -46,7 +87,13 @@ pub async fn publish_newsletter(
         title,
         text_content,
         html_content,
+        markdown_content,
+        layout_id,
+        hidden_from_archive,
+        tracked,
         idempotency_key,
+        scheduled_for,
+        timezone,
     } = form.0;
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
     // Return early if we have a saved response in the database
@@ -54,107 +101,78 @@ pub async fn publish_newsletter(
         .await
         .map_err(e500)?
     {
-        FlashMessage::info("The newsletter issue have been published!").send();
+        if scheduled_for.is_empty() {
+            FlashMessage::info("The newsletter issue has been published!").send();
+        } else {
+            FlashMessage::info("The newsletter issue has been scheduled.").send();
+        }
         return Ok(saved_response);
     }
-    let subscribers = get_confirmed_subscribers(&pool).await.map_err(e500)?;
-    for subscriber in subscribers {
-        // The subscriber forces us to handle both the happy and the unhappy case
-        match subscriber {
-            Ok(subscriber) => {
-                email_client
-                    .send_email(&subscriber.email, &title, &html_content, &text_content)
-                    .await
-                    // close relative of `context` covert `error` variant of `Result` to `anyhow::Error`
-                    // if the context you are adding has a runtime cost - use `with_context` (it is lazy)
-                    // Using `context` would allocate that string everytime we send an email
-                    // with `with_context` instead, we only would be invoke format! is the delivery fails!
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", subscriber.email)
-                    })
-                    .map_err(e500)?;
-            }
-            Err(error) => {
-                tracing::warn!(
-                    // We record the error chain as a structured field
-                    // on the log record.
-                    error.cause.chain = ?error,
-                    error.message = %error,
-                    // Using `\` to split a long string literal over
-                    // two lines, without creating a `\n` character.
-                    "Skipping a confirmed subscriber. \
-                    Their stored contact details are invalid",
-                );
-            }
+    // Checked before storing anything: the browser still has the content to fix the form
+    let schedule = if scheduled_for.is_empty() {
+        None
+    } else {
+        let timezone = if timezone.trim().is_empty() {
+            "UTC"
+        } else {
+            &timezone
+        };
+        Some(IssueSchedule::parse(&scheduled_for, timezone, Utc::now()).map_err(e400)?)
+    };
+    let layout_id = match parse_layout_choice(&pool, &layout_id).await {
+        Ok(layout_id) => layout_id,
+        Err(e @ LayoutChoiceError::UnknownLayout) => return Err(e400(e)),
+        Err(e) => return Err(e500(e)),
+    };
+    let content = IssueContent::from_form(text_content, html_content, markdown_content)
+        .with_layout(layout_id)
+        .hide_from_archive(hidden_from_archive)
+        .track(tracked);
+    if content.text_content.trim().is_empty() || content.html_content.trim().is_empty() {
+        return Err(e400(
+            "Either the Markdown content or both the text and the HTML content are required",
+        ));
+    }
+    content.check_merge_tags(&title).map_err(e400)?;
+    let newsletter_issue_id = insert_newsletter_issue(&pool, &title, &content)
+        .await
+        .context("Failed to store newsletter issue details.")
+        .map_err(e500)?;
+    let issue = get_newsletter_issue(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the newsletter issue.")
+        .map_err(e500)?
+        .ok_or_else(|| anyhow::anyhow!("The newsletter issue we just stored is missing."))
+        .map_err(e500)?;
+    match schedule {
+        Some(schedule) => {
+            schedule_newsletter_issue(&pool, issue.newsletter_issue_id, &schedule)
+                .await
+                .context("Failed to schedule the newsletter issue.")
+                .map_err(e500)?;
+            FlashMessage::info(format!(
+                "The newsletter issue has been scheduled for {}.",
+                schedule
+            ))
+            .send();
+        }
+        None => {
+            publish_newsletter_issue(
+                &pool,
+                &email_client,
+                &issue_renderer,
+                &issue,
+                Some(&request_id),
+                &shutdown,
+            )
+            .await
+            .map_err(e500)?;
+            FlashMessage::info("The newsletter issue has been published!").send();
         }
     }
-    FlashMessage::info("The newsletter issue have been published!").send();
     let response = see_other("/admin/newsletters");
     let response = save_response(&pool, &idempotency_key, *user_id, response)
         .await
         .map_err(e500)?;
     Ok(response)
 }
-
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
-
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-    // We are returning a `Vec` of `Results`'s in the happy case.
-    // This allows the caller to bubble up the errors due to network issues or other
-    // transient failures using the `?` operator, while the compiler
-    // forces them to handle the subtler mapping error.
-    // See http:://sled.rs/errors.html for a deep-dive about this technique.
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    // We only need `Row` to map the coming out of this query.
-    // Nesting its definition inside the function itself is a simple way
-    // to clearly communicate this coupling (and to ensure it doesn't
-    // get used elsewhere by mistake).
-    // struct Row { // removed later version
-    //     email: String,
-    // }
-
-    // `query_as` maps the retrieved rows to the type specified as its first argument
-    // We are minimizing the amount of data  we are fetching form DB (email only).
-    // Less work for the DB and less data over the network!
-    // Changed to query!
-    let rows = sqlx::query!(
-        // Row,
-        r#"
-        SELECT email
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#,
-    )
-    .fetch_all(pool)
-    .await?;
-
-    // Map into the domain type
-    let confirmed_subscribers = rows
-        .into_iter()
-        .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
-            Err(error) => Err(anyhow::anyhow!(error)),
-        })
-        // Used above instead!
-        // You might argue that all the emails stored in database are necessarily valid.
-        // We choose to validate again since these emails were market as valid by
-        // a previous version of our app, which now could have changed.
-        // We can either skip validation or process all - we need half-way - just emit a warning.
-        // .filter_map(|r| match SubscriberEmail::parse(r.email) {
-        //     Ok(email) => Some(ConfirmedSubscriber { email }),
-        //     Err(error) => {
-        //         tracing::warn!(
-        //             "A confirmed subscriber is using an invalid email address.\n{}.",
-        //             error
-        //         );
-        //         None
-        //     }
-        // })
-        .collect();
-
-    Ok(confirmed_subscribers)
-}
@@ -0,0 +1,59 @@
+use crate::issue_events::{get_issue_stats, IssueStats};
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::{get_newsletter_issue, NewsletterIssue};
+use crate::utils::{e500, html_page, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Template)]
+#[template(path = "admin/issue_stats.html")]
+struct IssueStatsPage {
+    issue: NewsletterIssue,
+    stats: IssueStats,
+    // Why opens and clicks are missing, if they are
+    tracking_note: Option<&'static str>,
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/{id}/stats",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "Delivered, opened, clicked and unsubscribed counts, day by day", content_type = "text/html"),
+        (status = 303, description = "Unknown issue: redirect to `/admin/newsletters`, or to `/login`"),
+    )
+)]
+pub async fn issue_stats(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        FlashMessage::error("There is no such newsletter issue.").send();
+        return Ok(see_other("/admin/newsletters"));
+    };
+    let tracking_note = if !issue.tracked {
+        Some("Opens and clicks are not tracked for this issue: only deliveries are counted.")
+    } else if !issue_renderer.is_tracking_enabled() {
+        Some("Tracking is disabled: new opens and clicks are not recorded.")
+    } else {
+        None
+    };
+    html_page(IssueStatsPage {
+        stats: get_issue_stats(&pool, issue.newsletter_issue_id)
+            .await
+            .map_err(e500)?,
+        issue,
+        tracking_note,
+        flash_messages: Vec::new(),
+    })
+}
@@ -1,62 +1,34 @@
 use crate::session_state::TypedSession;
-use crate::utils::{e500, see_other};
-use actix_web::{http::header::ContentType, HttpResponse};
+use crate::utils::{e500, flash_messages, html_page, see_other};
+use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use askama::Template;
 
+#[derive(Template)]
+#[template(path = "admin/password.html")]
+struct ChangePasswordPage {
+    flash_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The password change form", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirect to `/login`"),
+    )
+)]
 pub async fn change_password_form(
     session: TypedSession,
-    flash_messages: IncomingFlashMessages,
+    incoming: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         return Ok(see_other("/login"));
     };
 
-    let mut msg_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-            <html lang="en">
-                <head>
-                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                    <title>Change Password</title>
-                </head>
-                <body>
-                    {msg_html}
-                    <form action="/admin/password" method="post">
-                        <label>Current password
-                            <input
-                                type="password"
-                                placeholder="Enter current password"
-                                name="current_password"
-                            >
-                        </label>
-                        <br>
-                        <label>New password
-                            <input
-                                type="password"
-                                placeholder="Enter new password"
-                                name="new_password"
-                            >
-                        </label>
-                        <br>
-                        <label>Confirm new password
-                            <input
-                                type="password"
-                                placeholder="Type the new password again"
-                                name="new_password_check"
-                            >
-                        </label>
-                        <br>
-                        <button type="submit">Change password</button>
-                    </form>
-                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
-                </body>
-            </html>"#,
-        )))
+    html_page(ChangePasswordPage {
+        flash_messages: flash_messages(&incoming),
+    })
 }
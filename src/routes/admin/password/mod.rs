@@ -1,5 +1,5 @@
 mod get;
-pub use post::change_password;
+pub use post::{__path_change_password, change_password, FormData as ChangePasswordForm};
 
 mod post;
-pub use get::change_password_form;
+pub use get::{__path_change_password_form, change_password_form};
@@ -6,13 +6,27 @@ use actix_web_flash_messages::FlashMessage;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = ChangePasswordForm)]
 pub struct FormData {
+    #[schema(value_type = String, format = Password)]
     current_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password_check: Secret<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = ChangePasswordForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to `/admin/password` with the outcome, or to `/login`"),
+    )
+)]
 pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
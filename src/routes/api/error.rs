@@ -0,0 +1,144 @@
+use crate::authentication::ApiScope;
+use crate::domain::FieldErrors;
+use crate::request_id::RequestId;
+use crate::routes::{error_chain_fmt, LoginError, SubscribeError};
+use crate::signup_protection::Throttled;
+use actix_web::http::header::{RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+
+/// The errors of the JSON API.
+/// The body is always `{"error": {"code": ..., "message": ..., "request_id": ...}}`,
+/// plus `fields` when specific inputs were rejected.
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    Validation(String),
+    #[error("Some fields are invalid")]
+    InvalidFields(FieldErrors),
+    #[error("Authentication failed")]
+    Unauthorized(#[source] anyhow::Error),
+    #[error("This API key is missing the `{0}` scope")]
+    Forbidden(ApiScope),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Too many requests, please try again later")]
+    TooManyRequests(Throttled),
+    #[error("Something went wrong")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    error: ErrorDetails,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorDetails {
+    /// e.g. `validation_error`, `unauthorized`, `not_found`
+    code: &'static str,
+    message: String,
+    #[schema(value_type = Option<String>)]
+    request_id: Option<RequestId>,
+    /// The validation error of each rejected field, e.g. `{"email": "..."}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<HashMap<String, String>>)]
+    fields: Option<FieldErrors>,
+}
+
+impl ErrorBody {
+    pub fn new(code: &'static str, message: String, fields: Option<FieldErrors>) -> Self {
+        Self {
+            error: ErrorDetails {
+                code,
+                message,
+                request_id: RequestId::current(),
+                fields,
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => "validation_error",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyRequests(_) => "rate_limited",
+            ApiError::Unexpected(_) => "internal_error",
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            ApiError::Unauthorized(_) => {
+                response.insert_header((WWW_AUTHENTICATE, r#"Bearer realm="api""#));
+            }
+            ApiError::TooManyRequests(throttled) => {
+                response.insert_header((RETRY_AFTER, throttled.retry_after_seconds()));
+            }
+            _ => {}
+        }
+        // `Display` only ever gives the user-facing message, never the cause
+        let fields = match self {
+            ApiError::InvalidFields(fields) => Some(fields.clone()),
+            _ => None,
+        };
+        response.json(ErrorBody::new(self.code(), self.to_string(), fields))
+    }
+}
+
+impl From<SubscribeError> for ApiError {
+    fn from(e: SubscribeError) -> Self {
+        match e {
+            SubscribeError::ValidationError(fields) => ApiError::InvalidFields(fields),
+            SubscribeError::InvalidBody(message) => ApiError::Validation(message),
+            SubscribeError::UnsupportedMediaType | SubscribeError::ChallengeFailed => {
+                ApiError::Validation(e.to_string())
+            }
+            SubscribeError::TooManyRequests(throttled) => ApiError::TooManyRequests(throttled),
+            SubscribeError::UnexpectedError(e) => ApiError::Unexpected(e),
+        }
+    }
+}
+
+impl From<LoginError> for ApiError {
+    fn from(e: LoginError) -> Self {
+        match e {
+            LoginError::AuthError(e) => ApiError::Unauthorized(e),
+            LoginError::UnexpectedError(e) => ApiError::Unexpected(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::Unexpected(e.into())
+    }
+}
@@ -0,0 +1,236 @@
+use super::{require_scope, ApiError};
+use crate::authentication::{ApiKey, ApiScope};
+use crate::email_client::EmailClient;
+use crate::email_deliveries::{
+    get_issue_deliveries, get_issue_delivery_summary, DeliverySummary, EmailDelivery,
+};
+use crate::email_layouts::layout_exists;
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::{
+    get_newsletter_issue, insert_newsletter_issue, publish_newsletter_issue, IssueContent,
+    NewsletterIssue, PublishError,
+};
+use crate::request_id::RequestId;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateIssueBody {
+    title: String,
+    text_content: String,
+    html_content: String,
+    /// The layout to wrap the issue in, see `/admin/layouts`
+    #[serde(default)]
+    layout_id: Option<Uuid>,
+    /// Keep the issue out of the public archive and its feeds once sent
+    #[serde(default)]
+    hidden_from_archive: bool,
+    /// Record opens and clicks, unless `tracking.enabled` is unset
+    #[serde(default)]
+    tracked: bool,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct IssueWithDeliveries {
+    #[serde(flatten)]
+    issue: NewsletterIssue,
+    deliveries: DeliverySummary,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct DeliveryList {
+    deliveries: Vec<EmailDelivery>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues",
+    tag = "api",
+    security(("api_key" = ["issues:write"])),
+    request_body = CreateIssueBody,
+    responses(
+        (status = 201, description = "The newsletter issue, as a draft", body = IssueWithDeliveries),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "API: create a newsletter issue", skip(body, pool, api_key))]
+pub async fn create_issue(
+    body: web::Json<CreateIssueBody>,
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::IssuesWrite)?;
+    let CreateIssueBody {
+        title,
+        text_content,
+        html_content,
+        layout_id,
+        hidden_from_archive,
+        tracked,
+    } = body.into_inner();
+    if title.trim().is_empty() {
+        return Err(ApiError::Validation("The title cannot be empty".into()));
+    }
+    if text_content.trim().is_empty() || html_content.trim().is_empty() {
+        return Err(ApiError::Validation(
+            "Both the text and the HTML content are required".into(),
+        ));
+    }
+    if let Some(layout_id) = layout_id {
+        if !layout_exists(&pool, layout_id).await? {
+            return Err(ApiError::Validation("There is no such layout".into()));
+        }
+    }
+    let content = IssueContent::raw(text_content, html_content)
+        .with_layout(layout_id)
+        .hide_from_archive(hidden_from_archive)
+        .track(tracked);
+    content
+        .check_merge_tags(&title)
+        .map_err(|e| ApiError::Validation(e.to_string()))?;
+    let newsletter_issue_id = insert_newsletter_issue(&pool, &title, &content)
+        .await
+        .context("Failed to store newsletter issue details.")?;
+    let issue = get_issue_with_deliveries(&pool, newsletter_issue_id).await?;
+    Ok(HttpResponse::Created().json(issue))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{id}",
+    tag = "api",
+    security(("api_key" = ["issues:read"])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The newsletter issue", body = IssueWithDeliveries),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 404, description = "Not found", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "API: get a newsletter issue", skip(pool, api_key))]
+pub async fn get_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::IssuesRead)?;
+    let issue = get_issue_with_deliveries(&pool, *newsletter_issue_id).await?;
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues/{id}/publish",
+    tag = "api",
+    security(("api_key" = ["issues:write"])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The published newsletter issue", body = IssueWithDeliveries),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 404, description = "Not found", body = ErrorBody),
+        (status = 409, description = "Conflict", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+// Sends the issue to every confirmed subscriber before responding.
+// Only drafts can be published, so an issue is published at most once.
+#[tracing::instrument(
+    name = "API: publish a newsletter issue",
+    skip(pool, email_client, issue_renderer, request_id, api_key, shutdown)
+)]
+pub async fn publish_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    issue_renderer: web::Data<IssueRenderer>,
+    request_id: web::ReqData<RequestId>,
+    api_key: web::ReqData<ApiKey>,
+    shutdown: web::Data<CancellationToken>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::IssuesWrite)?;
+    let issue = get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await?
+        .ok_or_else(|| issue_not_found(*newsletter_issue_id))?;
+    publish_newsletter_issue(
+        &pool,
+        &email_client,
+        &issue_renderer,
+        &issue,
+        Some(&request_id),
+        &shutdown,
+    )
+    .await
+    .map_err(|e| match e {
+        PublishError::NotADraft => ApiError::Conflict(e.to_string()),
+        PublishError::InvalidMergeTags(_) => ApiError::Validation(e.to_string()),
+        PublishError::UnexpectedError(e) => ApiError::Unexpected(e),
+    })?;
+    let issue = get_issue_with_deliveries(&pool, *newsletter_issue_id).await?;
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{id}/deliveries",
+    tag = "api",
+    security(("api_key" = ["issues:read"])),
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The emails sent for the newsletter issue", body = DeliveryList),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 404, description = "Not found", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(
+    name = "API: list the deliveries of a newsletter issue",
+    skip(pool, api_key)
+)]
+pub async fn list_issue_deliveries(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::IssuesRead)?;
+    // Tell an unknown issue apart from one that has not been sent yet
+    get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await?
+        .ok_or_else(|| issue_not_found(*newsletter_issue_id))?;
+    let deliveries = get_issue_deliveries(&pool, *newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the deliveries of a newsletter issue.")?;
+    Ok(HttpResponse::Ok().json(DeliveryList { deliveries }))
+}
+
+async fn get_issue_with_deliveries(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<IssueWithDeliveries, ApiError> {
+    let issue = get_newsletter_issue(pool, newsletter_issue_id)
+        .await?
+        .ok_or_else(|| issue_not_found(newsletter_issue_id))?;
+    let deliveries = get_issue_delivery_summary(pool, newsletter_issue_id)
+        .await
+        .context("Failed to summarise the deliveries of a newsletter issue.")?;
+    Ok(IssueWithDeliveries { issue, deliveries })
+}
+
+fn issue_not_found(newsletter_issue_id: Uuid) -> ApiError {
+    ApiError::NotFound(format!(
+        "There is no newsletter issue with id {}",
+        newsletter_issue_id
+    ))
+}
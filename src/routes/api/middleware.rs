@@ -0,0 +1,56 @@
+use super::ApiError;
+use crate::authentication::{authenticate_api_key, ApiKey, ApiScope};
+use crate::routes::LoginError;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, AUTHORIZATION};
+use actix_web::web::Data;
+use actix_web::HttpMessage;
+use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+
+/// Authenticate the request with the API key in its `Authorization: Bearer` header.
+/// The key is made available to handlers as `ReqData<ApiKey>`.
+pub async fn reject_invalid_api_keys(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let key = bearer_token(req.headers())
+        .map_err(LoginError::AuthError)
+        .map_err(ApiError::from)?;
+    let pool = req
+        .app_data::<Data<PgPool>>()
+        .context("The database pool is not registered.")
+        .map_err(ApiError::Unexpected)?;
+    let api_key = authenticate_api_key(&key, pool)
+        .await
+        .map_err(LoginError::UnexpectedError)
+        .map_err(ApiError::from)?
+        .context("Unknown or revoked API key.")
+        .map_err(LoginError::AuthError)
+        .map_err(ApiError::from)?;
+    req.extensions_mut().insert(api_key);
+    next.call(req).await
+}
+
+fn bearer_token(headers: &HeaderMap) -> Result<Secret<String>, anyhow::Error> {
+    let header_value = headers
+        .get(AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let token = header_value
+        .strip_prefix("Bearer ")
+        .context("The authorization scheme was not 'Bearer'.")?;
+    Ok(Secret::new(token.trim().to_owned()))
+}
+
+pub fn require_scope(api_key: &ApiKey, scope: ApiScope) -> Result<(), ApiError> {
+    if api_key.has_scope(scope) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(scope))
+    }
+}
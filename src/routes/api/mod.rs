@@ -0,0 +1,22 @@
+mod error;
+mod issues;
+mod middleware;
+mod subscribers;
+
+pub use error::{ApiError, ErrorBody, ErrorDetails};
+pub use issues::*;
+pub use middleware::{reject_invalid_api_keys, require_scope};
+pub use subscribers::*;
+
+use actix_web::web;
+
+/// Turn the extractor errors of the API routes into JSON errors.
+pub fn api_json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .error_handler(|err, _req| ApiError::Validation(err.to_string()).into())
+}
+
+pub fn api_path_config() -> web::PathConfig {
+    web::PathConfig::default()
+        .error_handler(|err, _req| ApiError::Validation(err.to_string()).into())
+}
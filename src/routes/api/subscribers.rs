@@ -0,0 +1,294 @@
+use super::{require_scope, ApiError};
+use crate::authentication::{ApiKey, ApiScope};
+use crate::domain::{FieldErrors, NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::request_id::RequestId;
+use crate::routes::{register_subscriber, SubscribeError};
+use crate::startup::ApplicationBaseUrl;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberList {
+    subscribers: Vec<Subscriber>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateSubscriberBody {
+    email: String,
+    name: String,
+}
+
+impl TryFrom<CreateSubscriberBody> for NewSubscriber {
+    type Error = FieldErrors;
+
+    fn try_from(value: CreateSubscriberBody) -> Result<Self, Self::Error> {
+        NewSubscriber::parse(value.email, value.name)
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct UpdateSubscriberBody {
+    name: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "api",
+    security(("api_key" = ["subscribers:read"])),
+    responses(
+        (status = 200, description = "Every subscriber", body = SubscriberList),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "API: list subscribers", skip(pool, api_key))]
+pub async fn list_subscribers(
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::SubscribersRead)?;
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        ORDER BY subscribed_at
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to perform a query to list subscribers.")?;
+    Ok(HttpResponse::Ok().json(SubscriberList { subscribers }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers",
+    tag = "api",
+    security(("api_key" = ["subscribers:write"])),
+    request_body = CreateSubscriberBody,
+    responses(
+        (status = 201, description = "The subscriber, pending confirmation", body = Subscriber),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 409, description = "Conflict", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+// Subscribers created through the API go through the same double opt-in
+// as the ones signing up from the home page.
+#[tracing::instrument(
+    name = "API: create a subscriber",
+    skip(body, pool, email_client, base_url, request_id, api_key),
+    fields(subscriber_email = %body.email, subscriber_name = %body.name)
+)]
+pub async fn create_subscriber(
+    body: web::Json<CreateSubscriberBody>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    request_id: web::ReqData<RequestId>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::SubscribersWrite)?;
+    let new_subscriber: NewSubscriber = body
+        .into_inner()
+        .try_into()
+        .map_err(SubscribeError::ValidationError)?;
+    if subscriber_email_exists(&pool, &new_subscriber.email).await? {
+        return Err(ApiError::Conflict(format!(
+            "{} is already subscribed",
+            new_subscriber.email
+        )));
+    }
+    let subscriber_id = register_subscriber(
+        &pool,
+        &email_client,
+        &base_url.0,
+        &new_subscriber,
+        &request_id,
+    )
+    .await?;
+    let subscriber = get_subscriber(&pool, subscriber_id)
+        .await?
+        .context("The subscriber we just stored is missing.")?;
+    Ok(HttpResponse::Created().json(subscriber))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers/{id}",
+    tag = "api",
+    security(("api_key" = ["subscribers:read"])),
+    params(("id" = Uuid, Path, description = "The id of the subscriber")),
+    responses(
+        (status = 200, description = "The subscriber", body = Subscriber),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 404, description = "Not found", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "API: get a subscriber", skip(pool, api_key))]
+pub async fn get_subscriber_by_id(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::SubscribersRead)?;
+    let subscriber = get_subscriber(&pool, *subscriber_id)
+        .await?
+        .ok_or_else(|| subscriber_not_found(*subscriber_id))?;
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/subscribers/{id}",
+    tag = "api",
+    security(("api_key" = ["subscribers:write"])),
+    params(("id" = Uuid, Path, description = "The id of the subscriber")),
+    request_body = UpdateSubscriberBody,
+    responses(
+        (status = 200, description = "The updated subscriber", body = Subscriber),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 404, description = "Not found", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "API: update a subscriber", skip(body, pool, api_key))]
+pub async fn update_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<UpdateSubscriberBody>,
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::SubscribersWrite)?;
+    let name = SubscriberName::parse(body.into_inner().name).map_err(ApiError::Validation)?;
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscriptions
+        SET name = $2
+        WHERE id = $1
+        RETURNING id, email, name, status, subscribed_at
+        "#,
+        *subscriber_id,
+        name.as_ref(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update a subscriber.")?
+    .ok_or_else(|| subscriber_not_found(*subscriber_id))?;
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscribers/{id}",
+    tag = "api",
+    security(("api_key" = ["subscribers:write"])),
+    params(("id" = Uuid, Path, description = "The id of the subscriber")),
+    responses(
+        (status = 204, description = "The subscriber has been deleted"),
+        (status = 400, description = "Invalid body or path", body = ErrorBody),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorBody),
+        (status = 403, description = "The API key lacks the required scope", body = ErrorBody),
+        (status = 404, description = "Not found", body = ErrorBody),
+        (status = 500, description = "Something went wrong", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "API: delete a subscriber", skip(pool, api_key))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_key: web::ReqData<ApiKey>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_key, ApiScope::SubscribersWrite)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // Pending subscribers still have a confirmation token pointing at them
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        *subscriber_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the subscription tokens of a subscriber.")?;
+    // Along with what they did with tracked issues: the counts of these issues go down
+    sqlx::query!(
+        r#"DELETE FROM newsletter_issue_events WHERE subscriber_id = $1"#,
+        *subscriber_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the newsletter issue events of a subscriber.")?;
+    let deleted = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, *subscriber_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete a subscriber.")?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(subscriber_not_found(*subscriber_id));
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, ApiError> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a subscriber.")?;
+    Ok(subscriber)
+}
+
+async fn subscriber_email_exists(pool: &PgPool, email: &SubscriberEmail) -> Result<bool, ApiError> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE email = $1"#,
+        email.as_ref(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to look up a subscriber.")?;
+    Ok(row.is_some())
+}
+
+fn subscriber_not_found(subscriber_id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("There is no subscriber with id {}", subscriber_id))
+}
@@ -0,0 +1,143 @@
+use super::get::published_at;
+use crate::email_layouts::get_issue_layout;
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::list_archived_issues;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::PgPool;
+
+// Feed readers poll often: only the latest issues are in the feeds, the rest is on `/archive`
+const FEED_SIZE: i64 = 20;
+const FEED_TITLE: &str = "Newsletter archive";
+
+struct FeedEntry {
+    url: String,
+    title: String,
+    // RFC 3339, as both formats expect
+    published_at: String,
+    html_content: String,
+    text_content: String,
+}
+
+#[derive(Template)]
+#[template(path = "archive/feed.xml")]
+struct AtomFeed<'a> {
+    title: &'a str,
+    archive_url: String,
+    feed_url: String,
+    updated_at: String,
+    entries: Vec<FeedEntry>,
+}
+
+/// See <https://www.jsonfeed.org/version/1.1/>
+#[derive(serde::Serialize)]
+struct JsonFeed<'a> {
+    version: &'a str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    content_html: String,
+    content_text: String,
+    date_published: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/feed.atom",
+    tag = "archive",
+    responses((status = 200, description = "The latest sent issues, as an Atom feed", content_type = "application/atom+xml"))
+)]
+pub async fn atom_feed(
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let entries = feed_entries(&pool, &issue_renderer).await.map_err(e500)?;
+    let feed = AtomFeed {
+        title: FEED_TITLE,
+        archive_url: format!("{}/archive", base_url.0),
+        feed_url: format!("{}/feed.atom", base_url.0),
+        // An empty feed was last updated... now, as far as readers are concerned
+        updated_at: entries
+            .first()
+            .map(|entry| entry.published_at.clone())
+            .unwrap_or_else(|| rfc3339(Utc::now())),
+        entries,
+    };
+    let body = feed.render().map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/feed.json",
+    tag = "archive",
+    responses((status = 200, description = "The latest sent issues, as a JSON Feed", content_type = "application/feed+json"))
+)]
+pub async fn json_feed(
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let items = feed_entries(&pool, &issue_renderer)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|entry| JsonFeedItem {
+            id: entry.url.clone(),
+            url: entry.url,
+            title: entry.title,
+            content_html: entry.html_content,
+            content_text: entry.text_content,
+            date_published: entry.published_at,
+        })
+        .collect();
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: FEED_TITLE,
+        home_page_url: format!("{}/archive", base_url.0),
+        feed_url: format!("{}/feed.json", base_url.0),
+        items,
+    };
+    let body = serde_json::to_string(&feed).map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/feed+json; charset=utf-8")
+        .body(body))
+}
+
+// The issues as shown on their archive page, most recent first
+async fn feed_entries(
+    pool: &PgPool,
+    issue_renderer: &IssueRenderer,
+) -> Result<Vec<FeedEntry>, anyhow::Error> {
+    let mut entries = Vec::new();
+    for issue in list_archived_issues(pool, Some(FEED_SIZE)).await? {
+        let layout = get_issue_layout(pool, issue.newsletter_issue_id).await?;
+        let web_version = issue_renderer.web_version(&issue, layout.as_ref())?;
+        entries.push(FeedEntry {
+            url: issue_renderer.archive_url(issue.newsletter_issue_id),
+            title: web_version.subject,
+            published_at: rfc3339(published_at(&issue)),
+            html_content: web_version.html_content,
+            text_content: web_version.text_content,
+        });
+    }
+    Ok(entries)
+}
+
+fn rfc3339(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,110 @@
+use crate::email_layouts::get_issue_layout;
+use crate::issue_renderer::IssueRenderer;
+use crate::merge_tags::MergeTagError;
+use crate::newsletter_issues::{get_newsletter_issue, list_archived_issues, NewsletterIssue};
+use crate::utils::{e404, e500, html_page};
+use actix_web::http::header::{ContentType, CONTENT_SECURITY_POLICY};
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Issues are written by admins for mail clients: like the previews of drafts, they are shown
+// with the remote images and inline styles emails rely on, and nothing else.
+const ARCHIVED_ISSUE_POLICY: &str = "sandbox allow-popups allow-popups-to-escape-sandbox; \
+    default-src 'none'; img-src * data:; style-src * 'unsafe-inline'; font-src *; \
+    frame-ancestors 'none'";
+
+#[derive(Template)]
+#[template(path = "archive/index.html")]
+struct ArchivePage {
+    issues: Vec<ArchiveEntry>,
+}
+
+struct ArchiveEntry {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(Template)]
+#[template(path = "archive/issue.html")]
+struct ArchivedIssuePage {
+    title: String,
+    published_at: DateTime<Utc>,
+    html_content: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/archive",
+    tag = "archive",
+    responses((status = 200, description = "The sent issues, most recent first", content_type = "text/html"))
+)]
+pub async fn archive_page(
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues: Vec<ArchiveEntry> = list_archived_issues(&pool, None)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|issue| {
+            Ok(ArchiveEntry {
+                title: issue_renderer.web_title(&issue)?,
+                published_at: published_at(&issue),
+                newsletter_issue_id: issue.newsletter_issue_id,
+            })
+        })
+        .collect::<Result<_, MergeTagError>>()
+        .map_err(e500)?;
+    html_page(ArchivePage { issues })
+}
+
+#[utoipa::path(
+    get,
+    path = "/archive/{id}",
+    tag = "archive",
+    params(("id" = Uuid, Path, description = "The id of the newsletter issue")),
+    responses(
+        (status = 200, description = "The issue, as sent: the \"view in browser\" link of the emails", content_type = "text/html"),
+        (status = 404, description = "Unknown, unsent or hidden from the archive"),
+    )
+)]
+pub async fn archived_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = match get_newsletter_issue(&pool, *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(issue) if issue.is_public() => issue,
+        _ => return Err(e404("There is no such newsletter issue.")),
+    };
+    let layout = get_issue_layout(&pool, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    let web_version = issue_renderer
+        .web_version(&issue, layout.as_ref())
+        .map_err(e500)?;
+    let body = ArchivedIssuePage {
+        title: web_version.subject,
+        published_at: published_at(&issue),
+        html_content: web_version.html_content,
+    }
+    .render()
+    .map_err(e500)?;
+    // `set_security_headers` keeps the policy set here
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header((CONTENT_SECURITY_POLICY, ARCHIVED_ISSUE_POLICY))
+        .body(body))
+}
+
+// Issues that are still being sent have not been published yet
+pub(super) fn published_at(issue: &NewsletterIssue) -> DateTime<Utc> {
+    issue.published_at.unwrap_or(issue.updated_at)
+}
@@ -0,0 +1,5 @@
+mod feeds;
+mod get;
+
+pub use feeds::{__path_atom_feed, __path_json_feed, atom_feed, json_feed};
+pub use get::{__path_archive_page, __path_archived_issue, archive_page, archived_issue};
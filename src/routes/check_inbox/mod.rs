@@ -0,0 +1,17 @@
+use crate::utils::html_page;
+use actix_web::HttpResponse;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "subscriptions/check_inbox.html")]
+struct CheckInboxPage;
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/check_inbox",
+    tag = "subscriptions",
+    responses((status = 200, description = "Where browsers land after subscribing", content_type = "text/html"))
+)]
+pub async fn check_inbox() -> Result<HttpResponse, actix_web::Error> {
+    html_page(CheckInboxPage)
+}
@@ -1,5 +1,134 @@
-use actix_web::HttpResponse;
+use crate::configuration::HealthCheckSettings;
+use crate::email_client::EmailClient;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
+// Redis and the email provider do not have a pool with an acquire timeout
+// (Postgres has a 2s one): we bound their probes with the same value.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The process is up"))
+)]
+/// The process is up and able to serve requests.
+/// It does not look at any dependency: an orchestrator restarts the pod when it fails.
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+#[derive(serde::Serialize, utoipa::ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ComponentHealth {
+    name: &'static str,
+    status: HealthStatus,
+    // A component that is down but not required is reported without failing the check
+    required: bool,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ReadinessReport {
+    status: HealthStatus,
+    components: Vec<ComponentHealth>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every required dependency is up", body = ReadinessReport),
+        (status = 503, description = "A required dependency is down", body = ReadinessReport),
+    )
+)]
+/// The process can serve traffic: every required dependency is reachable.
+/// It returns a `503` otherwise, so that traffic is routed to healthier pods.
+#[tracing::instrument(name = "Readiness check", skip_all)]
+pub async fn readiness_check(
+    pool: web::Data<PgPool>,
+    redis_client: web::Data<redis::Client>,
+    email_client: web::Data<EmailClient>,
+    settings: web::Data<HealthCheckSettings>,
+) -> HttpResponse {
+    let (postgres, redis) = tokio::join!(
+        probe("postgres", true, check_postgres(&pool)),
+        probe("redis", true, check_redis(&redis_client)),
+    );
+    let mut components = vec![postgres, redis];
+    if settings.probe_email_provider {
+        components.push(probe("email_provider", false, email_client.check_reachable()).await);
+    }
+
+    let is_ready = components
+        .iter()
+        .all(|c| !c.required || c.status == HealthStatus::Up);
+    let report = ReadinessReport {
+        status: if is_ready {
+            HealthStatus::Up
+        } else {
+            HealthStatus::Down
+        },
+        components,
+    };
+    if is_ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+async fn probe<E>(
+    name: &'static str,
+    required: bool,
+    check: impl Future<Output = Result<(), E>>,
+) -> ComponentHealth
+where
+    E: std::fmt::Display,
+{
+    let start = Instant::now();
+    let outcome = match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(outcome) => outcome.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("timed out after {:?}", PROBE_TIMEOUT)),
+    };
+    let latency_ms = start.elapsed().as_millis();
+    if let Err(e) = &outcome {
+        tracing::warn!(component = name, error.message = %e, "Health check failed");
+    }
+    ComponentHealth {
+        name,
+        status: if outcome.is_ok() {
+            HealthStatus::Up
+        } else {
+            HealthStatus::Down
+        },
+        required,
+        latency_ms,
+        error: outcome.err(),
+    }
+}
+
+async fn check_postgres(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+async fn check_redis(client: &redis::Client) -> Result<(), redis::RedisError> {
+    let mut connection = client.get_async_connection().await?;
+    redis::cmd("PING")
+        .query_async::<_, String>(&mut connection)
+        .await?;
+    Ok(())
+}
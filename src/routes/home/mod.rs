@@ -1,10 +1,35 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use crate::security_headers::CspNonce;
+use crate::signup_protection::{ChallengeWidget, SignupProtection};
+use crate::utils::{flash_messages, html_page};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
 
-pub async fn home() -> HttpResponse {
-    // We want to read the html file and return it as the body of the GET "/"" endpoint
+#[derive(Template)]
+#[template(path = "home.html")]
+struct HomePage<'a> {
+    nonce: &'a str,
+    // The validation errors of a rejected subscription come back as flash messages.
+    // They can echo the submitted email: the template escapes them.
+    flash_messages: Vec<String>,
+    challenge_widget: Option<&'a ChallengeWidget>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "subscriptions",
+    responses((status = 200, description = "The home page, with the subscription form", content_type = "text/html"))
+)]
+pub async fn home(
+    incoming: IncomingFlashMessages,
+    protection: web::Data<SignupProtection>,
+    nonce: web::ReqData<CspNonce>,
+) -> Result<HttpResponse, actix_web::Error> {
     // Launch app with cargo and visit: http://localhost:8000 in the browser you should see our newsletter! message
-    HttpResponse::Ok()
-        // This contentType HTTP header is understood by all HTTP clients (this one is also set on the HTML itself)
-        .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+    html_page(HomePage {
+        nonce: nonce.as_str(),
+        flash_messages: flash_messages(&incoming),
+        challenge_widget: protection.challenge_widget(),
+    })
 }
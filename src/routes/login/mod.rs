@@ -1,5 +1,5 @@
 mod get;
 mod post;
 
-pub use get::login_form;
-pub use post::login;
+pub use get::{__path_login_form, login_form};
+pub use post::{__path_login, login, FormData as LoginForm, LoginError};
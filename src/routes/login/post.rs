@@ -10,12 +10,23 @@ use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = LoginForm)]
 pub struct FormData {
     username: String,
+    #[schema(value_type = String, format = Password)]
     password: Secret<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "admin",
+    request_body(content = LoginForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to `/admin/dashboard`, or back to `/login` with an error message"),
+    )
+)]
 #[tracing::instrument(
     skip(form, pool, session),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
@@ -34,7 +45,7 @@ pub async fn login(
 
     match validate_credentials(credentials, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
             // Rotate the session token when the user logs in - Prevents session fixation attacks
             session.renew();
             // We store the user identifier into the session
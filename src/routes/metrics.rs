@@ -0,0 +1,17 @@
+use crate::metrics::encode_metrics;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses((status = 200, description = "Prometheus metrics, unless served from a dedicated port", content_type = "text/plain"))
+)]
+pub async fn metrics(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let body = encode_metrics(&pool).map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(body))
+}
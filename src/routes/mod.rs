@@ -1,13 +1,29 @@
 mod admin;
+mod api;
+mod archive;
+mod check_inbox;
 mod health_check;
 mod home;
 mod login;
+mod metrics;
+mod openapi;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
+mod unsubscribe;
+mod webhooks;
 
 pub use admin::*;
+pub use api::*;
+pub use archive::*;
+pub use check_inbox::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use metrics::*;
+pub use openapi::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use tracking::*;
+pub use unsubscribe::*;
+pub use webhooks::*;
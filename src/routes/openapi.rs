@@ -0,0 +1,208 @@
+use crate::email_deliveries::{DeliverySummary, EmailDelivery};
+use crate::newsletter_issues::{IssueStatus, NewsletterIssue};
+use actix_web::HttpResponse;
+use utoipa::openapi::path::PathItemType;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// The OpenAPI document of every route registered in `startup::run`.
+/// `tests/api/openapi.rs` fails when a route is missing from it.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "zero2prod",
+        description = "A newsletter: public subscription endpoints, admin pages and a JSON API."
+    ),
+    paths(
+        super::home,
+        super::health_check,
+        super::readiness_check,
+        super::metrics,
+        super::subscribe,
+        super::check_inbox,
+        super::confirm,
+        super::unsubscribe_form,
+        super::unsubscribe,
+        super::archive_page,
+        super::archived_issue,
+        super::atom_feed,
+        super::json_feed,
+        super::track_open,
+        super::track_click,
+        super::postmark_webhook,
+        super::login_form,
+        super::login,
+        super::admin_dashboard,
+        super::change_password_form,
+        super::change_password,
+        super::log_out,
+        super::log_filter_form,
+        super::change_log_filter,
+        super::publish_newsletter_form,
+        super::publish_newsletter,
+        super::create_draft,
+        super::edit_draft_form,
+        super::update_draft,
+        super::preview_draft,
+        super::preview_draft_html,
+        super::preview_draft_text,
+        super::send_draft_test_copy,
+        super::publish_draft,
+        super::schedule_draft,
+        super::cancel_schedule,
+        super::update_archive_visibility,
+        super::issue_stats,
+        super::api_keys_form,
+        super::create_api_key,
+        super::revoke_api_key,
+        super::layouts_page,
+        super::create_layout,
+        super::edit_layout_form,
+        super::update_layout,
+        super::layout_version_page,
+        super::transactional_emails_page,
+        super::edit_transactional_email_form,
+        super::update_transactional_email,
+        super::transactional_email_version_page,
+        super::list_subscribers,
+        super::create_subscriber,
+        super::get_subscriber_by_id,
+        super::update_subscriber,
+        super::delete_subscriber,
+        super::create_issue,
+        super::get_issue,
+        super::publish_issue,
+        super::list_issue_deliveries,
+        openapi_document,
+    ),
+    components(schemas(
+        super::subscriptions::FormData,
+        super::SubscriptionPending,
+        super::UnsubscribeParameters,
+        super::PostmarkEvent,
+        super::LoginForm,
+        super::ChangePasswordForm,
+        super::LogFilterForm,
+        super::NewsletterForm,
+        super::DraftForm,
+        super::TestCopyForm,
+        super::ScheduleForm,
+        super::ArchiveForm,
+        super::ApiKeyForm,
+        super::NewLayoutForm,
+        super::EditLayoutForm,
+        super::TransactionalEmailForm,
+        super::ReadinessReport,
+        super::ComponentHealth,
+        super::HealthStatus,
+        super::Subscriber,
+        super::SubscriberList,
+        super::CreateSubscriberBody,
+        super::UpdateSubscriberBody,
+        super::CreateIssueBody,
+        super::IssueWithDeliveries,
+        super::DeliveryList,
+        super::ErrorBody,
+        super::ErrorDetails,
+        NewsletterIssue,
+        IssueStatus,
+        EmailDelivery,
+        DeliverySummary,
+    )),
+    modifiers(&SecuritySchemes, &LegacyHealthCheck, &JsonSubscriptions),
+    tags(
+        (name = "subscriptions", description = "Signing up to the newsletter"),
+        (name = "archive", description = "The public archive of sent issues, and its feeds"),
+        (name = "tracking", description = "Opens and clicks of the tracked newsletter issues"),
+        (name = "webhooks", description = "Events reported by the email provider"),
+        (name = "health", description = "Probes and metrics"),
+        (name = "admin", description = "The admin dashboard, behind a login"),
+        (name = "api", description = "The JSON API, authenticated with an API key"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("An API key created from `/admin/api_keys`"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "webhook_credentials",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Basic)
+                    .description(Some(
+                        "`email_client.webhook_username` and `email_client.webhook_password`",
+                    ))
+                    .build(),
+            ),
+        );
+        // The default cookie name of `actix-session`
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("id"))),
+        );
+    }
+}
+
+// `/health_check` is served by the same handler as `/health/live`.
+struct LegacyHealthCheck;
+
+impl Modify for LegacyHealthCheck {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let Some(live) = openapi.paths.paths.get("/health/live") else {
+            return;
+        };
+        let mut legacy = live.clone();
+        for operation in legacy.operations.values_mut() {
+            operation.deprecated = Some(utoipa::openapi::Deprecated::True);
+            operation.description = Some("Use `/health/live` instead.".into());
+        }
+        openapi.paths.paths.insert("/health_check".into(), legacy);
+    }
+}
+
+// `utoipa` takes a single content type per request body,
+// but `/subscriptions` accepts the same fields as JSON too.
+struct JsonSubscriptions;
+
+impl Modify for JsonSubscriptions {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let Some(request_body) = openapi
+            .paths
+            .paths
+            .get_mut("/subscriptions")
+            .and_then(|item| item.operations.get_mut(&PathItemType::Post))
+            .and_then(|operation| operation.request_body.as_mut())
+        else {
+            return;
+        };
+        if let Some(form) = request_body
+            .content
+            .get("application/x-www-form-urlencoded")
+            .cloned()
+        {
+            request_body.content.insert("application/json".into(), form);
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "health",
+    responses((status = 200, description = "This document", content_type = "application/json"))
+)]
+pub async fn openapi_document() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
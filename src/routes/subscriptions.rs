@@ -1,20 +1,46 @@
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::domain::{FieldErrors, NewSubscriber};
 use crate::email_client::EmailClient;
+use crate::email_deliveries::record_email_delivery;
+use crate::merge_tags::{MergeValues, PersonalisedEmail};
+use crate::metrics::{record_signup_blocked, record_subscription_created};
+use crate::request_id::RequestId;
+use crate::routes::api::ErrorBody;
+use crate::signup_protection::{SignupProtection, Throttled};
 use crate::startup::ApplicationBaseUrl;
-use actix_web::{web, HttpResponse, ResponseError};
+use crate::transactional_emails::{get_current_template, TransactionalEmail};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::{Accept, ContentType, Header, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use askama::Template;
 use chrono::Utc;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use reqwest::StatusCode;
 use sqlx::{query, PgPool, Postgres, Transaction};
 use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+#[derive(Default, serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = SubscriptionForm)]
+// Missing fields are reported by the validation of `NewSubscriber`,
+// next to the other field-level errors.
+#[serde(default)]
 pub struct FormData {
+    #[schema(required = true)]
     email: String,
+    #[schema(required = true)]
     name: String,
+    /// Hidden on the home page form: must be left empty
+    website: String,
+    /// The token added by the challenge widget, when a challenge is configured
+    #[serde(
+        alias = "cf-turnstile-response",
+        alias = "h-captcha-response",
+        alias = "g-recaptcha-response"
+    )]
+    challenge_response: String,
 }
 
 // NOTE: `TryFrom/TryInto` implementation instead of this!!!
@@ -28,23 +54,55 @@ pub struct FormData {
 // By implementing `TryFrom/TryInto` we are just making our intent clear.
 // We are spelling "This is a type conversion"
 impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+    type Error = FieldErrors;
 
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { email, name })
+        NewSubscriber::parse(value.email, value.name)
     }
 }
 
+impl FormData {
+    /// Deserialize the body according to its `Content-Type`:
+    /// JSON for embedded widgets, url-encoded for the home page form.
+    fn from_request_body(request: &HttpRequest, body: &[u8]) -> Result<Self, SubscribeError> {
+        match request.content_type() {
+            "application/json" => serde_json::from_slice(body)
+                .map_err(|e| SubscribeError::InvalidBody(format!("Invalid JSON body: {}", e))),
+            "application/x-www-form-urlencoded" => serde_urlencoded::from_bytes(body)
+                .map_err(|e| SubscribeError::InvalidBody(format!("Invalid form body: {}", e))),
+            _ => Err(SubscribeError::UnsupportedMediaType),
+        }
+    }
+}
+
+/// The JSON response to a successful subscription.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriptionPending {
+    /// Always `pending_confirmation`
+    status: &'static str,
+    message: &'static str,
+}
+
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
+    #[error("{}", describe_field_errors(.0))]
+    ValidationError(FieldErrors),
     #[error("{0}")]
-    ValidationError(String),
+    InvalidBody(String),
+    #[error("The body must be either JSON or url-encoded form data")]
+    UnsupportedMediaType,
+    #[error("Please complete the challenge to show you are not a robot")]
+    ChallengeFailed,
+    #[error("Too many subscription attempts, please try again later")]
+    TooManyRequests(Throttled),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
 
+fn describe_field_errors(errors: &FieldErrors) -> String {
+    errors.values().cloned().collect::<Vec<_>>().join(". ")
+}
+
 // We still using a bespoke implementation of `Debug`
 // to get a nice report using the error source chain
 impl std::fmt::Debug for SubscribeError {
@@ -53,46 +111,207 @@ impl std::fmt::Debug for SubscribeError {
     }
 }
 
+// Same body as the JSON API errors, so that widgets can point at the invalid fields.
 impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
-            SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::ValidationError(_)
+            | SubscribeError::InvalidBody(_)
+            | SubscribeError::ChallengeFailed => StatusCode::BAD_REQUEST,
+            SubscribeError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            SubscribeError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            SubscribeError::ValidationError(fields) => {
+                ErrorBody::new("validation_error", self.to_string(), Some(fields.clone()))
+            }
+            SubscribeError::InvalidBody(message) => {
+                ErrorBody::new("invalid_body", message.clone(), None)
+            }
+            SubscribeError::UnsupportedMediaType => {
+                ErrorBody::new("unsupported_media_type", self.to_string(), None)
+            }
+            SubscribeError::ChallengeFailed => {
+                ErrorBody::new("challenge_failed", self.to_string(), None)
+            }
+            SubscribeError::TooManyRequests(_) => {
+                ErrorBody::new("rate_limited", self.to_string(), None)
+            }
+            // The cause is for the logs only
+            SubscribeError::UnexpectedError(_) => {
+                ErrorBody::new("internal_error", "Something went wrong".into(), None)
+            }
+        };
+        let mut response = HttpResponse::build(self.status_code());
+        if let SubscribeError::TooManyRequests(throttled) = self {
+            response.insert_header((RETRY_AFTER, throttled.retry_after_seconds()));
+        }
+        response.json(body)
+    }
 }
 
+#[derive(Template)]
+#[template(path = "subscriptions/too_many_requests.html")]
+struct TooManyRequestsPage;
+
+// Browsers get a page rather than being sent back to the form, which they could resubmit right away.
+fn too_many_requests_page(throttled: Throttled) -> Result<HttpResponse, actix_web::Error> {
+    let body = TooManyRequestsPage.render().map_err(e500)?;
+    Ok(HttpResponse::TooManyRequests()
+        .insert_header((RETRY_AFTER, throttled.retry_after_seconds()))
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+/// Browsers that submit the home page form get pages and redirects,
+/// any other client gets JSON.
+fn prefers_html(request: &HttpRequest) -> bool {
+    Accept::parse(request)
+        .ok()
+        .and_then(|accept| accept.ranked().into_iter().next())
+        .is_some_and(|mime| mime.essence_str() == "text/html")
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(
+        content = SubscriptionForm,
+        content_type = "application/x-www-form-urlencoded",
+        description = "Also accepted as `application/json`"
+    ),
+    responses(
+        (status = 200, description = "The subscription is pending: a confirmation email has been sent", body = SubscriptionPending),
+        (status = 303, description = "Browsers (`Accept: text/html`) are redirected to `/subscriptions/check_inbox` on success, back to `/` otherwise"),
+        (status = 400, description = "Invalid name or email, with an error per field, or failed challenge", body = ErrorBody),
+        (status = 415, description = "The body is neither JSON nor url-encoded", body = ErrorBody),
+        (status = 429, description = "Too many attempts from this address or for this email domain, see `Retry-After`", body = ErrorBody),
+        (status = 500, description = "The subscription could not be stored or the email could not be sent", body = ErrorBody),
+    )
+)]
 // Creates a span at the beginning of the function invocation and
 // automatically attaches all instruments passed to the function to
 // the context of the span
 #[tracing::instrument(
     name="Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(request, body, pool, email_client, base_url, request_id, protection),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>,
+    request: HttpRequest,
+    body: web::Bytes,
     // Retrieving a connection from the application state
     pool: web::Data<PgPool>,
     // Get the email_client form the app context
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
-) -> Result<HttpResponse, SubscribeError> {
+    request_id: web::ReqData<RequestId>,
+    protection: web::Data<SignupProtection>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let outcome = async {
+        // Every attempt counts, valid or not
+        let client_ip = protection.client_ip(&request);
+        if let Some(client_ip) = client_ip {
+            protection.check_ip(client_ip).await.map_err(|throttled| {
+                record_signup_blocked("ip_rate_limit");
+                SubscribeError::TooManyRequests(throttled)
+            })?;
+        }
+        let form = FormData::from_request_body(&request, &body)?;
+        let span = tracing::Span::current();
+        span.record("subscriber_email", tracing::field::display(&form.email));
+        span.record("subscriber_name", tracing::field::display(&form.name));
+        if !form.website.is_empty() {
+            // The bot is answered as if it succeeded, so that it does not try to work around it
+            tracing::info!("The honeypot field was filled in, dropping the subscription.");
+            record_signup_blocked("honeypot");
+            return Ok(());
+        }
+        let challenge_passed = protection
+            .verify_challenge(&form.challenge_response, client_ip)
+            .await
+            .context("Failed to verify the signup challenge.")?;
+        if !challenge_passed {
+            record_signup_blocked("challenge");
+            return Err(SubscribeError::ChallengeFailed);
+        }
+        // We implemented `TryFrom` but we are calling `.try_into()`
+        // `TryFrom implementation  gives you this for free
+        // ` form.try_into()` equals `NewSubscriber::try_from(form)`
+        // is just a mather of taste really!!
+        let new_subscriber: NewSubscriber =
+            form.try_into().map_err(SubscribeError::ValidationError)?;
+        protection
+            .check_email_domain(&new_subscriber.email)
+            .await
+            .map_err(|throttled| {
+                record_signup_blocked("email_domain_rate_limit");
+                SubscribeError::TooManyRequests(throttled)
+            })?;
+        register_subscriber(
+            &pool,
+            &email_client,
+            &base_url.0,
+            &new_subscriber,
+            &request_id,
+        )
+        .await
+        .map(|_| ())
+    }
+    .await;
+
+    if prefers_html(&request) {
+        return match outcome {
+            Ok(()) => Ok(see_other("/subscriptions/check_inbox")),
+            Err(SubscribeError::UnexpectedError(e)) => Err(e500(e)),
+            Err(SubscribeError::TooManyRequests(throttled)) => too_many_requests_page(throttled),
+            Err(SubscribeError::ValidationError(errors)) => {
+                for message in errors.into_values() {
+                    FlashMessage::error(message).send();
+                }
+                Ok(see_other("/"))
+            }
+            Err(e) => {
+                FlashMessage::error(e.to_string()).send();
+                Ok(see_other("/"))
+            }
+        };
+    }
+    outcome?;
+    Ok(HttpResponse::Ok().json(SubscriptionPending {
+        status: "pending_confirmation",
+        message: "Check your inbox: we sent you a link to confirm your subscription.",
+    }))
+}
+
+/// Store a pending subscription and send out its confirmation email.
+/// Shared by the form on the home page and the JSON API.
+#[tracing::instrument(
+    name = "Register a new subscriber",
+    skip(pool, email_client, base_url, new_subscriber, request_id)
+)]
+pub async fn register_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    new_subscriber: &NewSubscriber,
+    request_id: &RequestId,
+) -> Result<Uuid, SubscribeError> {
     // transactions got its own API
     // to begin on our pool we acquire a connection from the pool and kick off a transaction
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    // We implemented `TryFrom` but we are calling `.try_into()`
-    // `TryFrom implementation  gives you this for free
-    // ` form.0.try_into()` equals `NewSubscriber::try_from(from.0)`
-    // is just a mather of taste really!!
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber_id = insert_subscriber(&mut transaction, new_subscriber, request_id)
         .await
         .context("Failed to insert new subscriber in the database.")?;
     let subscription_token = generate_subscription_token();
@@ -108,68 +327,91 @@ pub async fn subscribe(
         .commit()
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
+    record_subscription_created();
 
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
-        &subscription_token,
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token,
+    );
+    // Templates are checked when they are saved
+    let email = get_current_template(pool, TransactionalEmail::Confirmation)
+        .await?
+        .parse(TransactionalEmail::Confirmation)
+        .map_err(anyhow::Error::msg)
+        .context("The confirmation email template is invalid.")?
+        .render(&MergeValues {
+            name: Some(new_subscriber.name.as_ref()),
+            email: new_subscriber.email.as_ref(),
+            unsubscribe_url: None,
+            view_in_browser_url: None,
+            confirmation_url: Some(&confirmation_link),
+        });
+    let outcome = send_confirmation_email(email_client, new_subscriber, &email).await;
+    // The subscriber is stored and the email is gone, or not: a missing record
+    // must not turn the signup into an error
+    if let Err(e) = record_email_delivery(
+        pool,
+        &new_subscriber.email,
+        &email.subject,
+        None,
+        Some(request_id),
+        outcome.is_ok(),
+        outcome.as_ref().ok().and_then(Option::as_deref),
     )
     .await
-    .context("Failed to send a confirmation email.")?;
-    Ok(HttpResponse::Ok().finish())
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record the delivery of a confirmation email."
+        );
+    }
+    outcome.context("Failed to send a confirmation email.")?;
+    Ok(subscriber_id)
 }
 
 #[tracing::instrument(
     name = "Send a confirmation email to new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(email_client, new_subscriber, email)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
-    base_url: &str,
-    subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, subscription_token,
-    );
-    // Subscriptions will send an email
-    let html_body = &format!(
-        "Welcome to our newsletter!<br />\
-    Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    let plain_body = &format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-
+    new_subscriber: &NewSubscriber,
+    email: &PersonalisedEmail,
+) -> Result<Option<String>, reqwest::Error> {
+    // The welcome text is an admin-editable template, see `TransactionalEmail::Confirmation`
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", html_body, plain_body)
+        .send_email(
+            &new_subscriber.email,
+            &email.subject,
+            &email.html_content,
+            &email.text_content,
+        )
         .await
 }
 
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
-    skip(new_subscriber, transaction)
+    skip(new_subscriber, transaction, request_id)
 )]
 
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     // Retrieving a connection from the application state
     new_subscriber: &NewSubscriber,
+    request_id: &RequestId,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     query!(
         r#"
-                    INSERT INTO subscriptions (id, email, name, subscribed_at, status) 
-                    VALUES ($1, $2, $3, $4, 'pending_confirmation')
+                    INSERT INTO subscriptions (id, email, name, subscribed_at, status, request_id)
+                    VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
                 "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        request_id.as_str()
     )
     // Use the passed transaction instead of pool
     .execute(transaction)
@@ -1,12 +1,26 @@
+use crate::metrics::record_subscription_confirmed;
 use actix_web::{web, HttpResponse};
 use sqlx::PgPool;
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
+    /// The token sent in the confirmation email
     subscription_token: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscription is confirmed"),
+        (status = 400, description = "The token is missing"),
+        (status = 401, description = "Unknown token"),
+    )
+)]
 // It is enough to add a parameter of type Query<Parameter> to instruct
 // actix-web to only call the handler if the extraction was successful
 #[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
@@ -18,19 +32,24 @@ pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>
     match id {
         // Non-existing token
         None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+        Some(subscriber_id) => match confirm_subscriber(&pool, subscriber_id).await {
+            Ok(confirmed) => {
+                if confirmed {
+                    record_subscription_confirmed();
+                }
+                HttpResponse::Ok().finish()
             }
-            HttpResponse::Ok().finish()
-        }
+            Err(_) => HttpResponse::InternalServerError().finish(),
+        },
     }
 }
 
+/// Returns `false` if the subscriber was not pending anymore: links are opened again,
+/// sometimes by mail scanners, and suppressed or unsubscribed subscribers must stay so.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1 AND status = 'pending_confirmation'"#,
         subscriber_id,
     )
     .execute(pool)
@@ -39,7 +58,7 @@ pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<()
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    Ok(())
+    Ok(result.rows_affected() == 1)
 }
 
 #[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
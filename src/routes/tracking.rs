@@ -0,0 +1,141 @@
+use crate::issue_events::{record_issue_event, IssueEventKind};
+use crate::issue_renderer::IssueRenderer;
+use crate::utils::e400;
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// A transparent 1x1 GIF
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x44, 0x00, 0x3b,
+];
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OpenParameters {
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    /// The signature of the pixel in the newsletter issue
+    signature: String,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClickParameters {
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    /// Where the link of the newsletter issue points to
+    url: String,
+    /// The signature of the link in the newsletter issue
+    signature: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/tracking/open",
+    tag = "tracking",
+    params(OpenParameters),
+    responses(
+        (status = 200, description = "An invisible image: the open is recorded, if tracking is enabled", content_type = "image/gif"),
+        (status = 400, description = "The link is invalid"),
+    )
+)]
+#[tracing::instrument(
+    name = "Track an open",
+    skip(parameters, pool, issue_renderer),
+    fields(newsletter_issue_id=%parameters.newsletter_issue_id)
+)]
+pub async fn track_open(
+    parameters: web::Query<OpenParameters>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !issue_renderer.tracking_links().verify_open(
+        parameters.newsletter_issue_id,
+        parameters.subscriber_id,
+        &parameters.signature,
+    ) {
+        return Err(e400("This tracking link is invalid."));
+    }
+    record(
+        &pool,
+        &issue_renderer,
+        parameters.newsletter_issue_id,
+        parameters.subscriber_id,
+        IssueEventKind::Open,
+        None,
+    )
+    .await;
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        // Every open is a request, not a cache hit
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(PIXEL))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tracking/click",
+    tag = "tracking",
+    params(ClickParameters),
+    responses(
+        (status = 302, description = "Redirect to `url`: the click is recorded, if tracking is enabled"),
+        (status = 400, description = "The link is invalid"),
+    )
+)]
+#[tracing::instrument(
+    name = "Track a click",
+    skip(parameters, pool, issue_renderer),
+    fields(newsletter_issue_id=%parameters.newsletter_issue_id)
+)]
+pub async fn track_click(
+    parameters: web::Query<ClickParameters>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Only the links of the issue are signed: this is not an open redirect
+    if !issue_renderer.tracking_links().verify_click(
+        parameters.newsletter_issue_id,
+        parameters.subscriber_id,
+        &parameters.url,
+        &parameters.signature,
+    ) {
+        return Err(e400("This tracking link is invalid."));
+    }
+    record(
+        &pool,
+        &issue_renderer,
+        parameters.newsletter_issue_id,
+        parameters.subscriber_id,
+        IssueEventKind::Click,
+        Some(&parameters.url),
+    )
+    .await;
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, parameters.url.as_str()))
+        .finish())
+}
+
+// Readers get their image or their page even if the event cannot be stored
+async fn record(
+    pool: &PgPool,
+    issue_renderer: &IssueRenderer,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    kind: IssueEventKind,
+    url: Option<&str>,
+) {
+    if !issue_renderer.is_tracking_enabled() {
+        return;
+    }
+    if let Err(e) = record_issue_event(pool, newsletter_issue_id, subscriber_id, kind, url).await {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record a newsletter issue event",
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::issue_events::{record_issue_event, IssueEventKind};
+use crate::issue_renderer::IssueRenderer;
+use crate::utils::{e400, e500, html_page};
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::IntoParams, utoipa::ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct UnsubscribeParameters {
+    subscriber_id: Uuid,
+    /// The signature of the link in the newsletter issue
+    signature: String,
+    /// Set by tracked issues, to count who left after reading them
+    newsletter_issue_id: Option<Uuid>,
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions/unsubscribe.html")]
+struct UnsubscribePage {
+    subscriber_id: Uuid,
+    signature: String,
+    newsletter_issue_id: Option<Uuid>,
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions/unsubscribed.html")]
+struct UnsubscribedPage;
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "Asks to confirm: mail scanners follow links, they do not submit forms", content_type = "text/html"),
+        (status = 400, description = "The link is invalid"),
+    )
+)]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let UnsubscribeParameters {
+        subscriber_id,
+        signature,
+        newsletter_issue_id,
+    } = parameters.0;
+    if !issue_renderer
+        .unsubscribe_links()
+        .verify(subscriber_id, &signature)
+    {
+        return Err(e400("This unsubscribe link is invalid."));
+    }
+    html_page(UnsubscribePage {
+        subscriber_id,
+        signature,
+        newsletter_issue_id,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    request_body(content = UnsubscribeParameters, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The subscriber does not receive newsletter issues anymore", content_type = "text/html"),
+        (status = 400, description = "The link is invalid"),
+    )
+)]
+#[tracing::instrument(
+    name = "Unsubscribe",
+    skip(form, pool, issue_renderer),
+    fields(subscriber_id=%form.subscriber_id)
+)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    issue_renderer: web::Data<IssueRenderer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !issue_renderer
+        .unsubscribe_links()
+        .verify(form.subscriber_id, &form.signature)
+    {
+        return Err(e400("This unsubscribe link is invalid."));
+    }
+    // Unknown ids, e.g. the one of test copies, are not an error: there is nobody to unsubscribe
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1"#,
+        form.subscriber_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    // They are unsubscribed already: failing to count it is not worth an error page
+    if let Some(newsletter_issue_id) = form.newsletter_issue_id {
+        if issue_renderer.is_tracking_enabled() {
+            if let Err(e) = record_issue_event(
+                &pool,
+                newsletter_issue_id,
+                form.subscriber_id,
+                IssueEventKind::Unsubscribe,
+                None,
+            )
+            .await
+            {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to record an unsubscribe against a newsletter issue",
+                );
+            }
+        }
+    }
+    html_page(UnsubscribedPage)
+}
@@ -0,0 +1,207 @@
+use crate::email_deliveries::{record_delivery_event, DeliveryEventKind};
+use crate::utils::{e400, e500};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// The Basic credentials Postmark is configured to send its webhooks with.
+pub struct WebhookCredentials {
+    username: String,
+    password: Secret<String>,
+}
+
+impl WebhookCredentials {
+    pub fn new(username: String, password: Secret<String>) -> Self {
+        Self { username, password }
+    }
+
+    fn verify(&self, username: &str, password: &Secret<String>) -> bool {
+        // Comparing digests does not tell how much of the password was right
+        username == self.username
+            && Sha256::digest(password.expose_secret().as_bytes())
+                == Sha256::digest(self.password.expose_secret().as_bytes())
+    }
+}
+
+/// The fields we use out of the `Delivery`, `Bounce`, `SpamComplaint` and `Open` webhooks of Postmark.
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkEvent {
+    /// Other record types, e.g. `Click`, are acknowledged and ignored
+    record_type: String,
+    /// Returned by Postmark when the email was sent
+    #[serde(rename = "MessageID")]
+    message_id: String,
+    /// The recipient of a bounced or complained about email
+    email: Option<String>,
+    /// The type of a bounce, e.g. `HardBounce` or `SoftBounce`
+    #[serde(rename = "Type")]
+    bounce_type: Option<String>,
+    delivered_at: Option<DateTime<Utc>>,
+    bounced_at: Option<DateTime<Utc>>,
+    /// When an `Open` happened
+    received_at: Option<DateTime<Utc>>,
+}
+
+impl PostmarkEvent {
+    fn kind(&self) -> Option<DeliveryEventKind> {
+        match self.record_type.as_str() {
+            "Delivery" => Some(DeliveryEventKind::Delivery),
+            "Bounce" => Some(DeliveryEventKind::Bounce),
+            "SpamComplaint" => Some(DeliveryEventKind::SpamComplaint),
+            "Open" => Some(DeliveryEventKind::Open),
+            _ => None,
+        }
+    }
+
+    fn occurred_at(&self) -> DateTime<Utc> {
+        self.delivered_at
+            .or(self.bounced_at)
+            .or(self.received_at)
+            .unwrap_or_else(Utc::now)
+    }
+
+    // Soft bounces (full mailbox, server down...) may go away, hard bounces do not
+    fn suppresses_recipient(&self) -> bool {
+        match self.kind() {
+            Some(DeliveryEventKind::SpamComplaint) => true,
+            Some(DeliveryEventKind::Bounce) => self.bounce_type.as_deref() == Some("HardBounce"),
+            _ => false,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/postmark",
+    tag = "webhooks",
+    security(("webhook_credentials" = [])),
+    request_body(content = PostmarkEvent, content_type = "application/json"),
+    responses(
+        (status = 200, description = "The event is stored with its delivery. Hard bounces and spam complaints suppress the recipient: they are not sent newsletter issues anymore"),
+        (status = 400, description = "The body is not a Postmark event"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 500, description = "The event could not be stored: Postmark retries later"),
+    )
+)]
+#[tracing::instrument(
+    name = "Receive a Postmark webhook",
+    skip(request, body, pool, credentials),
+    fields(record_type = tracing::field::Empty, message_id = tracing::field::Empty)
+)]
+pub async fn postmark_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    credentials: web::Data<WebhookCredentials>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (username, password) = basic_credentials(request.headers()).map_err(unauthorized)?;
+    if !credentials.verify(&username, &password) {
+        return Err(unauthorized(anyhow::anyhow!(
+            "Invalid webhook credentials."
+        )));
+    }
+    let event: PostmarkEvent = serde_json::from_slice(&body).map_err(e400)?;
+    let span = tracing::Span::current();
+    span.record("record_type", tracing::field::display(&event.record_type));
+    span.record("message_id", tracing::field::display(&event.message_id));
+    let Some(kind) = event.kind() else {
+        // Postmark only posts what it is asked for: another webhook was turned on
+        tracing::info!("Ignoring a Postmark event of an unexpected type.");
+        return Ok(HttpResponse::Ok().finish());
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let recorded = record_delivery_event(
+        &mut transaction,
+        &event.message_id,
+        kind,
+        event.bounce_type.as_deref(),
+        event.occurred_at(),
+    )
+    .await
+    .context("Failed to record an email delivery event.")
+    .map_err(e500)?;
+    if !recorded {
+        tracing::warn!("No delivery matches the message of a Postmark event.");
+    }
+    // Even without a matching delivery: the address is not worth mailing anymore
+    if let Some(email) = event
+        .email
+        .as_deref()
+        .filter(|_| event.suppresses_recipient())
+    {
+        suppress_subscriber(&mut transaction, email)
+            .await
+            .context("Failed to suppress a subscriber.")
+            .map_err(e500)?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store an email delivery event.")
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Suppressed subscribers are not sent newsletter issues anymore,
+/// see `get_confirmed_subscribers`. Those who unsubscribed stay unsubscribed.
+#[tracing::instrument(name = "Suppress a subscriber", skip(transaction, email))]
+async fn suppress_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    // Providers do not keep the case of the address we sent to
+    let suppressed = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'suppressed'
+        WHERE lower(email) = lower($1) AND status IN ('pending_confirmation', 'confirmed')
+        "#,
+        email,
+    )
+    .execute(transaction)
+    .await?
+    .rows_affected();
+    if suppressed > 0 {
+        tracing::info!("A subscriber has been suppressed.");
+    }
+    Ok(())
+}
+
+fn basic_credentials(headers: &HeaderMap) -> Result<(String, Secret<String>), anyhow::Error> {
+    let header_value = headers
+        .get(AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment.trim())
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("The 'Basic' credentials have no ':' separator.")?;
+    Ok((username.to_owned(), Secret::new(password.to_owned())))
+}
+
+fn unauthorized(e: anyhow::Error) -> actix_web::Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, r#"Basic realm="webhooks""#))
+        .finish();
+    InternalError::from_response(e, response).into()
+}
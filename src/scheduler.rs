@@ -0,0 +1,49 @@
+use crate::email_client::EmailClient;
+use crate::issue_renderer::IssueRenderer;
+use crate::newsletter_issues::{claim_due_issue, deliver_newsletter_issue};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Send the scheduled newsletter issues once they are due, checking every `interval`
+/// until `shutdown` is triggered. A delivery in progress stops between two subscribers.
+/// Safe to run in every replica: see `claim_due_issue`.
+pub async fn run_scheduler_until_stopped(
+    pool: PgPool,
+    email_client: Arc<EmailClient>,
+    renderer: Arc<IssueRenderer>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) {
+    loop {
+        if let Err(e) = deliver_due_issues(&pool, &email_client, &renderer, &shutdown).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver a scheduled newsletter issue",
+            );
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+}
+
+// Several issues can be due at once, e.g. after a downtime
+async fn deliver_due_issues(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    renderer: &IssueRenderer,
+    shutdown: &CancellationToken,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_cancelled() {
+        let Some(issue) = claim_due_issue(pool).await? else {
+            break;
+        };
+        // There is no request to tie the deliveries to
+        deliver_newsletter_issue(pool, email_client, renderer, &issue, None, shutdown).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use crate::configuration::{Environment, SecurityHeadersSettings};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE,
+    REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use actix_web::web::Data;
+use actix_web::HttpMessage;
+use actix_web_lab::middleware::Next;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+
+// Replaced by the nonce of the request in `content_security_policy`
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// A random value, different for every request, that lets the inline `<script>`
+/// and `<style>` tags of a page through the `Content-Security-Policy`,
+/// e.g. `<script nonce="{nonce}">`.
+#[derive(Clone, Debug)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate() -> Self {
+        let mut rng = thread_rng();
+        Self(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(char::from)
+                .take(22)
+                .collect(),
+        )
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The headers asking browsers to lock our pages down.
+pub struct SecurityHeaders {
+    settings: SecurityHeadersSettings,
+    // HSTS is only sent in production: it would make browsers refuse plain HTTP
+    // on `localhost` or a staging host for months.
+    strict_transport_security: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: SecurityHeadersSettings, environment: Environment) -> Self {
+        let strict_transport_security = (environment == Environment::Production).then(|| {
+            HeaderValue::from_str(&format!(
+                "max-age={}; includeSubDomains",
+                settings.hsts_max_age_seconds
+            ))
+            .unwrap()
+        });
+        Self {
+            settings,
+            strict_transport_security,
+        }
+    }
+
+    fn apply(&self, path: &str, nonce: &CspNonce, headers: &mut HeaderMap) {
+        if let Some(hsts) = &self.strict_transport_security {
+            headers.insert(STRICT_TRANSPORT_SECURITY, hsts.clone());
+        }
+        // Admin pages show subscribers, API keys and the like: keep them out of any cache
+        if path == "/admin" || path.starts_with("/admin/") {
+            headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        }
+        let is_html = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if !is_html {
+            return;
+        }
+        headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        let policy = self
+            .settings
+            .content_security_policy
+            .replace(NONCE_PLACEHOLDER, nonce.as_str());
+        for (name, value) in [
+            (CONTENT_SECURITY_POLICY, policy.as_str()),
+            (X_FRAME_OPTIONS, self.settings.frame_options.as_str()),
+            (REFERRER_POLICY, self.settings.referrer_policy.as_str()),
+            (
+                PERMISSIONS_POLICY,
+                self.settings.permissions_policy.as_str(),
+            ),
+        ] {
+            // An empty value in the configuration turns the header off,
+            // and handlers can send their own (e.g. the newsletter previews)
+            if value.is_empty() || headers.contains_key(&name) {
+                continue;
+            }
+            // `Settings::validate` checked every value at startup
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+        }
+    }
+}
+
+/// Add the security headers to every response, error pages included.
+/// Handlers find the nonce of the request in its extensions, see `CspNonce`.
+pub async fn set_security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let nonce = CspNonce::generate();
+    req.extensions_mut().insert(nonce.clone());
+    let security_headers = req.app_data::<Data<SecurityHeaders>>().cloned();
+    let path = req.path().to_owned();
+    let apply = |headers: &mut HeaderMap| {
+        if let Some(security_headers) = &security_headers {
+            security_headers.apply(&path, &nonce, headers);
+        }
+    };
+
+    match next.call(req).await {
+        Ok(mut response) => {
+            apply(response.headers_mut());
+            Ok(response)
+        }
+        // Errors are only turned into responses further up: we build the page of the error here
+        // to get to the headers of the pages built by `e500` and the like.
+        // The request cannot be kept around to do it: actix-web needs it to be unique to route it.
+        Err(e) => {
+            let mut response = e.error_response();
+            apply(response.headers_mut());
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CspNonce, SecurityHeaders};
+    use crate::configuration::{Environment, SecurityHeadersSettings};
+    use actix_web::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE, X_FRAME_OPTIONS};
+    use claims::{assert_none, assert_some};
+
+    fn settings() -> SecurityHeadersSettings {
+        SecurityHeadersSettings {
+            content_security_policy: "default-src 'self'; script-src 'self' 'nonce-{nonce}'".into(),
+            frame_options: "DENY".into(),
+            referrer_policy: "no-referrer".into(),
+            permissions_policy: "camera=()".into(),
+            hsts_max_age_seconds: 63072000,
+        }
+    }
+
+    fn headers_for(
+        security_headers: &SecurityHeaders,
+        path: &str,
+        content_type: &'static str,
+    ) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        security_headers.apply(path, &CspNonce("abc123".into()), &mut headers);
+        headers
+    }
+
+    #[test]
+    fn the_nonce_is_set_in_the_content_security_policy() {
+        let headers = headers_for(
+            &SecurityHeaders::new(settings(), Environment::Local),
+            "/",
+            "text/html; charset=utf-8",
+        );
+
+        assert_eq!(
+            headers.get("Content-Security-Policy").unwrap(),
+            "default-src 'self'; script-src 'self' 'nonce-abc123'"
+        );
+        assert_eq!(headers.get("X-Frame-Options").unwrap(), "DENY");
+        assert_eq!(headers.get("Referrer-Policy").unwrap(), "no-referrer");
+        assert_eq!(headers.get("Permissions-Policy").unwrap(), "camera=()");
+    }
+
+    #[test]
+    fn hsts_is_only_sent_in_production() {
+        for environment in [Environment::Local, Environment::Test, Environment::Staging] {
+            let security_headers = SecurityHeaders::new(settings(), environment);
+            assert_none!(
+                headers_for(&security_headers, "/", "text/html").get("Strict-Transport-Security")
+            );
+        }
+
+        let security_headers = SecurityHeaders::new(settings(), Environment::Production);
+        assert_eq!(
+            headers_for(&security_headers, "/", "text/html")
+                .get("Strict-Transport-Security")
+                .unwrap(),
+            "max-age=63072000; includeSubDomains"
+        );
+    }
+
+    #[test]
+    fn page_headers_are_not_sent_with_other_content_types() {
+        let headers = headers_for(
+            &SecurityHeaders::new(settings(), Environment::Local),
+            "/api/v1/subscribers",
+            "application/json",
+        );
+
+        assert_none!(headers.get("Content-Security-Policy"));
+        assert_none!(headers.get("X-Frame-Options"));
+    }
+
+    #[test]
+    fn admin_responses_are_never_cached() {
+        let security_headers = SecurityHeaders::new(settings(), Environment::Local);
+
+        for path in ["/admin", "/admin/dashboard", "/admin/api_keys/1/revoke"] {
+            assert_eq!(
+                headers_for(&security_headers, path, "text/html")
+                    .get("Cache-Control")
+                    .unwrap(),
+                "no-store"
+            );
+        }
+        assert_none!(
+            headers_for(&security_headers, "/administrator", "text/html").get("Cache-Control")
+        );
+    }
+
+    #[test]
+    fn empty_settings_turn_headers_off() {
+        let mut settings = settings();
+        settings.frame_options = "".into();
+
+        let headers = headers_for(
+            &SecurityHeaders::new(settings, Environment::Local),
+            "/",
+            "text/html",
+        );
+
+        assert_none!(headers.get("X-Frame-Options"));
+        assert_some!(headers.get("Content-Security-Policy"));
+    }
+
+    #[test]
+    fn headers_set_by_handlers_are_kept() {
+        let security_headers = SecurityHeaders::new(settings(), Environment::Local);
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"));
+
+        security_headers.apply("/", &CspNonce("abc123".into()), &mut headers);
+
+        assert_eq!(headers.get("X-Frame-Options").unwrap(), "SAMEORIGIN");
+        assert_eq!(headers.get("Referrer-Policy").unwrap(), "no-referrer");
+    }
+}
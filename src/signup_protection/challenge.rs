@@ -0,0 +1,139 @@
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use std::net::IpAddr;
+use std::time::Duration;
+
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks the token a challenge widget adds to the home page form with its provider.
+/// Cloudflare Turnstile, hCaptcha and reCAPTCHA share the same `siteverify` protocol:
+/// any of them can be plugged in through the configuration.
+pub struct ChallengeVerifier {
+    http_client: Client,
+    verify_url: String,
+    // We dont want to log this by accident
+    secret: Secret<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+impl ChallengeVerifier {
+    pub fn new(verify_url: String, secret: Secret<String>) -> Self {
+        let http_client = Client::builder().timeout(VERIFY_TIMEOUT).build().unwrap();
+        Self {
+            http_client,
+            verify_url,
+            secret,
+        }
+    }
+
+    /// `Ok(false)` when the challenge was failed, `Err` when the provider could not tell.
+    #[tracing::instrument(name = "Verify a signup challenge", skip(self, response))]
+    pub async fn verify(
+        &self,
+        response: &str,
+        remote_ip: Option<IpAddr>,
+    ) -> Result<bool, reqwest::Error> {
+        // The widget did not run: nothing to ask the provider about
+        if response.is_empty() {
+            return Ok(false);
+        }
+        let remote_ip = remote_ip.map(|ip| ip.to_string());
+        let mut form = vec![
+            ("secret", self.secret.expose_secret().as_str()),
+            ("response", response),
+        ];
+        if let Some(remote_ip) = &remote_ip {
+            form.push(("remoteip", remote_ip));
+        }
+        let outcome: SiteVerifyResponse = self
+            .http_client
+            .post(&self.verify_url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if !outcome.success {
+            tracing::info!(error_codes = ?outcome.error_codes, "The signup challenge was failed.");
+        }
+        Ok(outcome.success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChallengeVerifier;
+    use claims::{assert_err, assert_ok_eq};
+    use secrecy::Secret;
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn verifier(server: &MockServer) -> ChallengeVerifier {
+        ChallengeVerifier::new(server.uri(), Secret::new("challenge-secret".into()))
+    }
+
+    #[tokio::test]
+    async fn the_response_and_the_secret_are_sent_to_the_provider() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("secret=challenge-secret"))
+            .and(body_string_contains("response=a-token"))
+            .and(body_string_contains("remoteip=203.0.113.7"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "success": true
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let outcome = verifier(&server)
+            .verify("a-token", Some("203.0.113.7".parse().unwrap()))
+            .await;
+
+        assert_ok_eq!(outcome, true);
+    }
+
+    #[tokio::test]
+    async fn a_failed_challenge_is_reported() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "success": false,
+                "error-codes": ["invalid-input-response"]
+            })))
+            .mount(&server)
+            .await;
+
+        assert_ok_eq!(verifier(&server).verify("a-token", None).await, false);
+    }
+
+    #[tokio::test]
+    async fn an_empty_response_fails_without_calling_the_provider() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        assert_ok_eq!(verifier(&server).verify("", None).await, false);
+    }
+
+    #[tokio::test]
+    async fn a_provider_error_is_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        assert_err!(verifier(&server).verify("a-token", None).await);
+    }
+}
@@ -0,0 +1,145 @@
+mod challenge;
+mod rate_limit;
+
+pub use challenge::ChallengeVerifier;
+pub use rate_limit::{RateLimiter, Throttled};
+
+use crate::configuration::{RateLimit, SignupProtectionSettings};
+use crate::domain::SubscriberEmail;
+use actix_web::HttpRequest;
+use std::net::IpAddr;
+
+/// What stands between `POST /subscriptions` and our email provider:
+/// anyone can use it to have us send emails to a third party.
+pub struct SignupProtection {
+    rate_limiter: RateLimiter,
+    per_ip: RateLimit,
+    per_email_domain: RateLimit,
+    trust_forwarded_for: bool,
+    challenge_verifier: Option<ChallengeVerifier>,
+    challenge_widget: Option<ChallengeWidget>,
+}
+
+/// What the home page form needs to embed the challenge.
+pub struct ChallengeWidget {
+    pub script_url: String,
+    pub widget_class: String,
+    pub site_key: String,
+}
+
+impl SignupProtection {
+    pub fn new(settings: SignupProtectionSettings, redis_client: redis::Client) -> Self {
+        let challenge = settings.challenge;
+        let (challenge_verifier, challenge_widget) = if challenge.enabled {
+            let widget = ChallengeWidget {
+                script_url: challenge.script_url,
+                widget_class: challenge.widget_class,
+                site_key: challenge.site_key,
+            };
+            let verifier = ChallengeVerifier::new(challenge.verify_url, challenge.secret);
+            (Some(verifier), Some(widget))
+        } else {
+            (None, None)
+        };
+        Self {
+            rate_limiter: RateLimiter::new(redis_client, settings.key_prefix),
+            per_ip: settings.per_ip,
+            per_email_domain: settings.per_email_domain,
+            trust_forwarded_for: settings.trust_forwarded_for,
+            challenge_verifier,
+            challenge_widget,
+        }
+    }
+
+    /// The address requests are counted against.
+    pub fn client_ip(&self, request: &HttpRequest) -> Option<IpAddr> {
+        self.trust_forwarded_for
+            .then(|| forwarded_for(request))
+            .flatten()
+            .or_else(|| request.peer_addr().map(|address| address.ip()))
+    }
+
+    pub async fn check_ip(&self, ip: IpAddr) -> Result<(), Throttled> {
+        self.rate_limiter
+            .hit(&format!("ip:{}", ip_key(ip)), self.per_ip)
+            .await
+    }
+
+    pub async fn check_email_domain(&self, email: &SubscriberEmail) -> Result<(), Throttled> {
+        self.rate_limiter
+            .hit(
+                &format!("email_domain:{}", email.domain()),
+                self.per_email_domain,
+            )
+            .await
+    }
+
+    /// Always passes when no challenge is configured.
+    pub async fn verify_challenge(
+        &self,
+        response: &str,
+        remote_ip: Option<IpAddr>,
+    ) -> Result<bool, reqwest::Error> {
+        match &self.challenge_verifier {
+            Some(verifier) => verifier.verify(response, remote_ip).await,
+            None => Ok(true),
+        }
+    }
+
+    /// `None` when no challenge is configured.
+    pub fn challenge_widget(&self) -> Option<&ChallengeWidget> {
+        self.challenge_widget.as_ref()
+    }
+}
+
+// Our proxy appends the address it got the request from to `X-Forwarded-For`:
+// anything before it was sent by the client and cannot be trusted.
+fn forwarded_for(request: &HttpRequest) -> Option<IpAddr> {
+    request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .last()?
+        .to_str()
+        .ok()?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+// An IPv6 subscriber usually gets a whole /64 to pick addresses from.
+fn ip_key(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            format!(
+                "{:x}:{:x}:{:x}:{:x}::/64",
+                segments[0], segments[1], segments[2], segments[3]
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ip_key;
+
+    #[test]
+    fn ipv6_addresses_are_grouped_by_64_prefix() {
+        assert_eq!(
+            ip_key("2001:db8:1:2:aaaa::1".parse().unwrap()),
+            ip_key("2001:db8:1:2:bbbb::2".parse().unwrap())
+        );
+        assert_eq!(
+            ip_key("2001:db8:1:2::1".parse().unwrap()),
+            "2001:db8:1:2::/64"
+        );
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_are_keyed_as_ipv4() {
+        assert_eq!(ip_key("::ffff:203.0.113.7".parse().unwrap()), "203.0.113.7");
+    }
+}
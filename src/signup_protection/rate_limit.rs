@@ -0,0 +1,211 @@
+use crate::configuration::RateLimit;
+use redis::aio::MultiplexedConnection;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Rate limiting must not slow signups down when Redis struggles:
+// past this we count in memory instead.
+const REDIS_TIMEOUT: Duration = Duration::from_millis(500);
+
+// The in-memory counters only grow while Redis is down;
+// expired windows are dropped once there are this many of them.
+const MAX_IN_MEMORY_WINDOWS: usize = 10_000;
+
+/// The limit has been reached: the request can be retried after `retry_after`.
+#[derive(Clone, Copy, Debug)]
+pub struct Throttled {
+    pub retry_after: Duration,
+}
+
+impl Throttled {
+    /// Whole seconds, rounded up, as expected in a `Retry-After` header.
+    pub fn retry_after_seconds(&self) -> u64 {
+        let seconds = self.retry_after.as_secs() + u64::from(self.retry_after.subsec_nanos() > 0);
+        seconds.max(1)
+    }
+}
+
+/// Fixed-window counters stored in Redis, to be shared by every instance.
+/// While Redis is unreachable, each instance counts on its own in memory.
+pub struct RateLimiter {
+    redis_client: redis::Client,
+    key_prefix: String,
+    // Reused across requests, dropped on error to reconnect on the next one
+    connection: tokio::sync::Mutex<Option<MultiplexedConnection>>,
+    fallback: InMemoryCounters,
+}
+
+impl RateLimiter {
+    pub fn new(redis_client: redis::Client, key_prefix: String) -> Self {
+        Self {
+            redis_client,
+            key_prefix,
+            connection: tokio::sync::Mutex::new(None),
+            fallback: InMemoryCounters::default(),
+        }
+    }
+
+    /// Count one more request against `key` (e.g. `ip:203.0.113.7`).
+    pub async fn hit(&self, key: &str, limit: RateLimit) -> Result<(), Throttled> {
+        let key = format!("{}:{}", self.key_prefix, key);
+        let (count, window_ends_in) =
+            match tokio::time::timeout(REDIS_TIMEOUT, self.hit_redis(&key, limit)).await {
+                Ok(Ok(counter)) => counter,
+                Ok(Err(e)) => {
+                    tracing::warn!(
+                        error.message = %e,
+                        "Failed to rate limit with Redis, falling back to in-memory counters."
+                    );
+                    *self.connection.lock().await = None;
+                    self.fallback.hit(&key, limit, Instant::now())
+                }
+                Err(_) => {
+                    tracing::warn!(
+                        "Redis timed out while rate limiting, falling back to in-memory counters."
+                    );
+                    *self.connection.lock().await = None;
+                    self.fallback.hit(&key, limit, Instant::now())
+                }
+            };
+        if count > limit.max_requests.into() {
+            Err(Throttled {
+                retry_after: window_ends_in,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    // The number of requests in the current window, including this one,
+    // and how long until the window ends.
+    async fn hit_redis(
+        &self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<(u64, Duration), redis::RedisError> {
+        let mut connection = self.connection().await?;
+        let (count, ttl_milliseconds): (u64, i64) = redis::pipe()
+            .atomic()
+            // The first request of a window starts its countdown
+            .cmd("SET")
+            .arg(key)
+            .arg(0)
+            .arg("EX")
+            .arg(limit.window_seconds)
+            .arg("NX")
+            .ignore()
+            .cmd("INCR")
+            .arg(key)
+            .cmd("PTTL")
+            .arg(key)
+            .query_async(&mut connection)
+            .await?;
+        // `PTTL` is negative if the key has no expiry, which `SET ... EX` rules out
+        let window_ends_in = Duration::from_millis(ttl_milliseconds.max(0) as u64);
+        Ok((count, window_ends_in))
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection, redis::RedisError> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+        let new_connection = self.redis_client.get_multiplexed_tokio_connection().await?;
+        *connection = Some(new_connection.clone());
+        Ok(new_connection)
+    }
+}
+
+#[derive(Default)]
+struct InMemoryCounters {
+    windows: std::sync::Mutex<HashMap<String, Window>>,
+}
+
+struct Window {
+    ends_at: Instant,
+    count: u64,
+}
+
+impl InMemoryCounters {
+    fn hit(&self, key: &str, limit: RateLimit, now: Instant) -> (u64, Duration) {
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= MAX_IN_MEMORY_WINDOWS {
+            windows.retain(|_, window| window.ends_at > now);
+        }
+        let window = windows.entry(key.to_owned()).or_insert(Window {
+            ends_at: now,
+            count: 0,
+        });
+        if window.ends_at <= now {
+            *window = Window {
+                ends_at: now + limit.window(),
+                count: 0,
+            };
+        }
+        window.count += 1;
+        (window.count, window.ends_at - now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemoryCounters, Throttled};
+    use crate::configuration::RateLimit;
+    use std::time::{Duration, Instant};
+
+    const LIMIT: RateLimit = RateLimit {
+        max_requests: 2,
+        window_seconds: 60,
+    };
+
+    #[test]
+    fn requests_are_counted_within_a_window() {
+        let counters = InMemoryCounters::default();
+        let now = Instant::now();
+
+        assert_eq!(counters.hit("ip:1.2.3.4", LIMIT, now).0, 1);
+        assert_eq!(counters.hit("ip:1.2.3.4", LIMIT, now).0, 2);
+        let (count, window_ends_in) =
+            counters.hit("ip:1.2.3.4", LIMIT, now + Duration::from_secs(20));
+
+        assert_eq!(count, 3);
+        assert_eq!(window_ends_in, Duration::from_secs(40));
+    }
+
+    #[test]
+    fn keys_are_counted_separately() {
+        let counters = InMemoryCounters::default();
+        let now = Instant::now();
+
+        counters.hit("ip:1.2.3.4", LIMIT, now);
+
+        assert_eq!(counters.hit("ip:5.6.7.8", LIMIT, now).0, 1);
+    }
+
+    #[test]
+    fn the_count_starts_over_when_the_window_ends() {
+        let counters = InMemoryCounters::default();
+        let now = Instant::now();
+        counters.hit("ip:1.2.3.4", LIMIT, now);
+        counters.hit("ip:1.2.3.4", LIMIT, now);
+
+        let (count, window_ends_in) =
+            counters.hit("ip:1.2.3.4", LIMIT, now + Duration::from_secs(60));
+
+        assert_eq!(count, 1);
+        assert_eq!(window_ends_in, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn retry_after_is_rounded_up_to_the_second() {
+        let retry_after = |milliseconds| {
+            Throttled {
+                retry_after: Duration::from_millis(milliseconds),
+            }
+            .retry_after_seconds()
+        };
+        assert_eq!(retry_after(0), 1);
+        assert_eq!(retry_after(1500), 2);
+        assert_eq!(retry_after(3000), 3);
+    }
+}
@@ -1,49 +1,103 @@
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{default_credentials_active, reject_anonymous_users};
+use crate::configuration::{
+    get_environment, CorsSettings, DatabaseSettings, Environment, Settings,
+};
 use crate::email_client::EmailClient;
-use crate::routes::{admin_dashboard, change_password, change_password_form, log_out};
+use crate::idempotency::run_expiry_worker_until_stopped;
+use crate::issue_renderer::IssueRenderer;
+use crate::metrics::record_http_metrics;
+use crate::request_id::{propagate_request_id, TrustRequestIdHeader};
+use crate::routes::{
+    admin_dashboard, admin_form_config, api_keys_form, cancel_schedule, change_log_filter,
+    change_password, change_password_form, create_api_key, create_draft, create_layout,
+    edit_draft_form, edit_layout_form, edit_transactional_email_form, issue_stats,
+    layout_version_page, layouts_page, log_filter_form, log_out, preview_draft, preview_draft_html,
+    preview_draft_text, publish_draft, revoke_api_key, schedule_draft, send_draft_test_copy,
+    transactional_email_version_page, transactional_emails_page, update_archive_visibility,
+    update_draft, update_layout, update_transactional_email,
+};
+use crate::routes::{
+    api_json_config, api_path_config, archive_page, archived_issue, atom_feed, create_issue,
+    create_subscriber, delete_subscriber, get_issue, get_subscriber_by_id, json_feed,
+    list_issue_deliveries, list_subscribers, publish_issue, reject_invalid_api_keys,
+    update_subscriber,
+};
 use crate::routes::{
-    confirm, health_check, home, login, login_form, publish_newsletter, publish_newsletter_form,
-    subscribe,
+    check_inbox, confirm, health_check, home, login, login_form, metrics, openapi_document,
+    postmark_webhook, publish_newsletter, publish_newsletter_form, readiness_check, subscribe,
+    track_click, track_open, unsubscribe, unsubscribe_form, WebhookCredentials,
 };
+use crate::scheduler::run_scheduler_until_stopped;
+use crate::security_headers::{set_security_headers, SecurityHeaders};
+use crate::signup_protection::SignupProtection;
+use crate::telemetry::LogFilterHandle;
+use actix_cors::Cors;
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
+use actix_web::body::MessageBody;
 use actix_web::cookie::Key;
-use actix_web::dev::Server;
+use actix_web::dev::{Server, ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
 use actix_web::web::Data;
-use actix_web::{web, App, HttpServer};
+use actix_web::{web, App, FromRequest, Handler, HttpServer, Responder};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
-use actix_web_lab::middleware::from_fn;
+use actix_web_lab::middleware::{from_fn, Next};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
     port: u16,
     server: Server,
+    metrics_port: Option<u16>,
+    // Only set when `/metrics` is served from a dedicated port
+    metrics_server: Option<Server>,
+    db_pool: PgPool,
+    // Shared with the scheduler, which sends the scheduled newsletter issues
+    email_client: Arc<EmailClient>,
+    issue_renderer: Arc<IssueRenderer>,
+    scheduler_interval: Duration,
+    shutdown: CancellationToken,
+    shutdown_grace_period: Duration,
+    in_flight_requests: TaskTracker,
 }
 
 impl Application {
     // We have converted the build function into a constructor for application
-    pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+    pub async fn build(
+        configuration: Settings,
+        log_filter: LogFilterHandle,
+    ) -> Result<Self, anyhow::Error> {
+        // Fail fast, reporting every invalid value at once
+        configuration.validate()?;
         let connection_pool = get_connection_pool(&configuration.database);
+        check_default_credentials(&connection_pool, get_environment()).await?;
         // Build an `EmailClient` using `configuration`
         let sender_email = configuration
             .email_client
             .sender()
-            .expect("Invalid sender email address.");
+            .map_err(anyhow::Error::msg)?;
         let timeout = configuration.email_client.timeout();
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url,
+        let email_client = Arc::new(EmailClient::new(
+            configuration.email_client.base_url.clone(),
             sender_email,
             // Pass argument from configuration
-            configuration.email_client.authorization_token,
+            configuration.email_client.authorization_token.clone(),
             // Pass new argument from configuration
             timeout,
-        );
+        ));
+        let issue_renderer = Arc::new(IssueRenderer::new(
+            &configuration.application.base_url,
+            configuration.application.hmac_secret.clone(),
+            configuration.tracking.enabled,
+        )?);
         // We are reading address from Settings
         let address = format!(
             "{}:{}",
@@ -51,27 +105,158 @@ impl Application {
         );
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
+        let metrics_listener = match configuration.metrics.port {
+            Some(metrics_port) => Some(TcpListener::bind(format!(
+                "{}:{}",
+                configuration.application.host, metrics_port
+            ))?),
+            None => None,
+        };
+        let metrics_port = metrics_listener
+            .as_ref()
+            .map(|l| l.local_addr().unwrap().port());
+        let metrics_server = match metrics_listener {
+            Some(listener) => Some(run_metrics_server(listener, connection_pool.clone())?),
+            None => None,
+        };
+        let serve_metrics = metrics_server.is_none();
+        let shutdown_grace_period = configuration.application.shutdown_grace_period();
+        let scheduler_interval = configuration.application.scheduler_interval();
+        let in_flight_requests = TaskTracker::new();
+        let shutdown = CancellationToken::new();
         let server = run(
             listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
+            connection_pool.clone(),
+            email_client.clone(),
+            configuration,
+            log_filter,
+            ServerState {
+                serve_metrics,
+                in_flight_requests: in_flight_requests.clone(),
+                shutdown: shutdown.clone(),
+            },
         )
         .await?;
 
         // We "save" the bound port in one of `Application`'s fields
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            metrics_port,
+            metrics_server,
+            db_pool: connection_pool,
+            email_client,
+            issue_renderer,
+            scheduler_interval,
+            shutdown,
+            shutdown_grace_period,
+            in_flight_requests,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
+
+    /// The port `/metrics` is served from, if it is not the main one.
+    pub fn metrics_port(&self) -> Option<u16> {
+        self.metrics_port
+    }
+
+    /// Cancel it to stop the application the same way SIGTERM does.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
     // A more expressive name that makes it clear that
     // this fn only returns the application is stopped
+    //
+    // On SIGTERM (or Ctrl+C) we stop accepting connections and give in-flight requests
+    // and background workers the grace period to finish, then close the database pool.
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+        let workers = vec![
+            tokio::spawn(run_expiry_worker_until_stopped(
+                self.db_pool.clone(),
+                self.shutdown.clone(),
+            )),
+            tokio::spawn(run_scheduler_until_stopped(
+                self.db_pool.clone(),
+                self.email_client.clone(),
+                self.issue_renderer.clone(),
+                self.scheduler_interval,
+                self.shutdown.clone(),
+            )),
+        ];
+        let server_handle = self.server.handle();
+        let metrics_server_handle = self.metrics_server.as_ref().map(Server::handle);
+        let servers = async {
+            match self.metrics_server {
+                Some(metrics_server) => tokio::try_join!(self.server, metrics_server).map(|_| ()),
+                None => self.server.await,
+            }
+        };
+        tokio::pin!(servers);
+
+        let outcome = tokio::select! {
+            // The servers only stop on their own if something went wrong
+            outcome = &mut servers => outcome,
+            () = shutdown_requested(&self.shutdown) => {
+                tracing::info!("Shutting down: draining in-flight requests and background work.");
+                self.shutdown.cancel();
+                let deadline = tokio::time::Instant::now() + self.shutdown_grace_period;
+                // The servers must keep being polled for the commands to be processed.
+                let stop = async {
+                    // A worker of actix-server that sees the accept thread stop before it
+                    // gets its own stop command drops its connections: we wait for the
+                    // in-flight requests ourselves rather than relying on a graceful stop.
+                    server_handle.pause().await;
+                    self.in_flight_requests.close();
+                    let drained =
+                        tokio::time::timeout_at(deadline, self.in_flight_requests.wait())
+                            .await
+                            .is_ok();
+                    if let Some(handle) = metrics_server_handle {
+                        handle.stop(true).await;
+                    }
+                    server_handle.stop(drained).await;
+                };
+                let ((), outcome) = tokio::join!(stop, &mut servers);
+                outcome
+            }
+        };
+
+        // Workers only check for the signal between two units of work
+        self.shutdown.cancel();
+        let deadline = tokio::time::Instant::now() + self.shutdown_grace_period;
+        for worker in workers {
+            match tokio::time::timeout_at(deadline, worker).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::error!(error.message = %e, "A background worker panicked."),
+                Err(_) => {
+                    tracing::warn!("A background worker did not stop within the grace period.")
+                }
+            }
+        }
+        self.db_pool.close().await;
+        outcome
+    }
+}
+
+// Resolves on SIGTERM, Ctrl+C or when `token` is cancelled.
+async fn shutdown_requested(token: &CancellationToken) {
+    let terminate = async {
+        #[cfg(unix)]
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+        _ = token.cancelled() => {}
     }
 }
 
@@ -82,6 +267,36 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
         .connect_lazy_with(configuration.with_db())
 }
 
+// The `seed_user` migration ships `admin` with a well-known password.
+// We nag about it locally and refuse to serve traffic with it in production.
+async fn check_default_credentials(
+    pool: &PgPool,
+    environment: Environment,
+) -> Result<(), anyhow::Error> {
+    match default_credentials_active(pool).await {
+        Ok(false) => Ok(()),
+        Ok(true) if environment == Environment::Production => Err(anyhow::anyhow!(
+            "The seeded default admin credentials are still active. \
+            Run `zero2prod admin reset-password --username admin` before starting in production."
+        )),
+        Ok(true) => {
+            tracing::warn!(
+                "The seeded default admin credentials are still active. \
+                Run `zero2prod admin reset-password --username admin` to rotate them."
+            );
+            Ok(())
+        }
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Could not check whether the seeded default admin credentials are still active."
+            );
+            Ok(())
+        }
+    }
+}
+
 // We need to define a wrapper type in order to retrieve the URL
 // in the `subscribe` handler.
 // Retrieval from the context, in actix-web, is type-based: using
@@ -93,19 +308,63 @@ pub struct ApplicationBaseUrl(pub String);
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+// What `run` shares with `Application`, which drives the shutdown
+struct ServerState {
+    // `false` when `/metrics` is served from a dedicated port
+    serve_metrics: bool,
+    in_flight_requests: TaskTracker,
+    // Publishing an issue stops sending it once shutdown is triggered
+    shutdown: CancellationToken,
+}
+
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
+    email_client: Arc<EmailClient>,
+    // What is left of the configuration once the pool, the email client
+    // and the listeners have been built out of it
+    configuration: Settings,
+    log_filter: LogFilterHandle,
+    state: ServerState,
     // Returning anyhow error instead od std::Error
 ) -> Result<Server, anyhow::Error> {
+    let shutdown_grace_period = configuration.application.shutdown_grace_period();
+    let hmac_secret = configuration.application.hmac_secret;
+    let redis_uri = configuration.redis_uri;
     // Wrap the pool using web::Data, which boils down to an Arc smart pointer
     let db_pool = Data::new(db_pool);
-    let email_client = Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let email_client = Data::from(email_client);
+    let issue_renderer = Data::new(IssueRenderer::new(
+        &configuration.application.base_url,
+        hmac_secret.clone(),
+        configuration.tracking.enabled,
+    )?);
+    let base_url = Data::new(ApplicationBaseUrl(configuration.application.base_url));
+    let webhook_credentials = Data::new(WebhookCredentials::new(
+        configuration.email_client.webhook_username,
+        configuration.email_client.webhook_password,
+    ));
+    let cors_settings = configuration.application.cors;
+    let health_check_settings = Data::new(configuration.health_check);
+    let security_headers = Data::new(SecurityHeaders::new(
+        configuration.security_headers,
+        get_environment(),
+    ));
+    let log_filter = Data::new(log_filter);
+    let serve_metrics = state.serve_metrics;
+    let in_flight_requests = Data::new(state.in_flight_requests);
+    let shutdown = Data::new(state.shutdown);
+    let trust_request_id_header = Data::new(TrustRequestIdHeader(
+        configuration.application.trust_request_id_header,
+    ));
+    // Used by the readiness check and the rate limiting of signups -
+    // sessions go through `RedisSessionStore`
+    let redis_client = redis::Client::open(redis_uri.expose_secret().as_str())?;
+    let signup_protection = Data::new(SignupProtection::new(
+        configuration.signup_protection,
+        redis_client.clone(),
+    ));
+    let redis_client = Data::new(redis_client);
     // CookieMessageStore enforces that cookies be signed (HMAC)
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
@@ -114,6 +373,8 @@ async fn run(
     // Capture connection from the surrounding environment
     let server = HttpServer::new(move || {
         App::new()
+            // Also picks the nonce of the inline scripts and styles of our pages
+            .wrap(from_fn(set_security_headers))
             // Middlewares are added using the `wrap` method on `App`
             .wrap(message_framework.clone())
             // Provides session management (takes care of loading session data,
@@ -122,32 +383,290 @@ async fn run(
                 redis_store.clone(),
                 secret_key.clone(),
             ))
+            .wrap(from_fn(record_http_metrics))
+            // Reads the request id picked by `TracingLogger`, so it must be wrapped by it
+            .wrap(from_fn(propagate_request_id))
             // Instead of `Logger::Default` use TracingLogger - injects unique identifier in wrapping all span
             .wrap(TracingLogger::default())
-            .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
-            .route("/health_check", web::get().to(health_check))
-            // A new entry in out routing table for POST /subscriptions requests
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
-            // .route("/newsletters", web::post().to(publish_newsletter))
-            .service(
-                web::scope("/admin")
-                    .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/logout", web::post().to(log_out))
-                    .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter)),
-            )
+            .wrap(from_fn(track_in_flight_requests))
+            .configure(|cfg| {
+                configure(cfg, &cors_settings, serve_metrics);
+            })
             // Register the connection as part of the application state
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
+            .app_data(issue_renderer.clone())
             .app_data(base_url.clone())
+            .app_data(webhook_credentials.clone())
+            .app_data(redis_client.clone())
+            .app_data(health_check_settings.clone())
+            .app_data(log_filter.clone())
+            .app_data(in_flight_requests.clone())
+            .app_data(shutdown.clone())
+            .app_data(trust_request_id_header.clone())
+            .app_data(signup_protection.clone())
+            .app_data(security_headers.clone())
             .app_data(Data::new(HmacSecret(hmac_secret.clone())))
     })
+    // `Application::run_until_stopped` handles the signals, to stop the workers too
+    .disable_signals()
+    .shutdown_timeout(shutdown_grace_period.as_secs())
+    .listen(listener)?
+    .run();
+
+    Ok(server)
+}
+
+/// The method and full path of every route registered by `configure`.
+pub type RouteTable = Vec<(Method, String)>;
+
+/// Register every route of the application, with the middlewares and settings of their scopes.
+/// The OpenAPI document is checked against the returned table, see `tests/api/openapi.rs`.
+pub fn configure(
+    cfg: &mut web::ServiceConfig,
+    cors_settings: &CorsSettings,
+    serve_metrics: bool,
+) -> RouteTable {
+    let mut table = RouteTable::new();
+    Routes::new(cfg, "", &mut table)
+        .route("/", Method::GET, home)
+        .route("/login", Method::GET, login_form)
+        .route("/login", Method::POST, login)
+        // Kept for the probes configured before `/health/live` existed
+        .route("/health_check", Method::GET, health_check)
+        .route("/health/live", Method::GET, health_check)
+        .route("/health/ready", Method::GET, readiness_check)
+        .route("/openapi.json", Method::GET, openapi_document)
+        // A new entry in out routing table for POST /subscriptions requests
+        .cors_route(
+            "/subscriptions",
+            Method::POST,
+            subscribe,
+            subscription_cors(cors_settings),
+        )
+        .route("/subscriptions/check_inbox", Method::GET, check_inbox)
+        .cors_route(
+            "/subscriptions/confirm",
+            Method::GET,
+            confirm,
+            subscription_cors(cors_settings),
+        )
+        .route("/subscriptions/unsubscribe", Method::GET, unsubscribe_form)
+        .route("/subscriptions/unsubscribe", Method::POST, unsubscribe)
+        // The sent issues, for everyone to read
+        .route("/archive", Method::GET, archive_page)
+        .route("/archive/{id}", Method::GET, archived_issue)
+        .route("/feed.atom", Method::GET, atom_feed)
+        .route("/feed.json", Method::GET, json_feed)
+        // The open pixel and click redirects of tracked issues
+        .route("/tracking/open", Method::GET, track_open)
+        .route("/tracking/click", Method::GET, track_click)
+        // Bounces, spam complaints... reported by the email provider
+        .route("/webhooks/postmark", Method::POST, postmark_webhook);
+    cfg.service(
+        web::scope("/admin")
+            .wrap(from_fn(reject_anonymous_users))
+            .app_data(admin_form_config())
+            .configure(|cfg| {
+                Routes::new(cfg, "/admin", &mut table)
+                    .route("/dashboard", Method::GET, admin_dashboard)
+                    .route("/password", Method::GET, change_password_form)
+                    .route("/password", Method::POST, change_password)
+                    .route("/logout", Method::POST, log_out)
+                    .route("/log_filter", Method::GET, log_filter_form)
+                    .route("/log_filter", Method::POST, change_log_filter)
+                    .route("/newsletters", Method::GET, publish_newsletter_form)
+                    .route("/newsletters", Method::POST, publish_newsletter)
+                    .route("/newsletters/drafts", Method::POST, create_draft)
+                    .route("/newsletters/drafts/{id}", Method::GET, edit_draft_form)
+                    .route("/newsletters/drafts/{id}", Method::POST, update_draft)
+                    .route(
+                        "/newsletters/drafts/{id}/preview",
+                        Method::GET,
+                        preview_draft,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/preview/html",
+                        Method::GET,
+                        preview_draft_html,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/preview/text",
+                        Method::GET,
+                        preview_draft_text,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/test",
+                        Method::POST,
+                        send_draft_test_copy,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/publish",
+                        Method::POST,
+                        publish_draft,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/schedule",
+                        Method::POST,
+                        schedule_draft,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/cancel",
+                        Method::POST,
+                        cancel_schedule,
+                    )
+                    .route(
+                        "/newsletters/drafts/{id}/archive",
+                        Method::POST,
+                        update_archive_visibility,
+                    )
+                    .route("/newsletters/{id}/stats", Method::GET, issue_stats)
+                    .route("/api_keys", Method::GET, api_keys_form)
+                    .route("/api_keys", Method::POST, create_api_key)
+                    .route("/api_keys/{key_id}/revoke", Method::POST, revoke_api_key)
+                    .route("/layouts", Method::GET, layouts_page)
+                    .route("/layouts", Method::POST, create_layout)
+                    .route("/layouts/{id}", Method::GET, edit_layout_form)
+                    .route("/layouts/{id}", Method::POST, update_layout)
+                    .route(
+                        "/layouts/{id}/versions/{version}",
+                        Method::GET,
+                        layout_version_page,
+                    )
+                    .route("/emails", Method::GET, transactional_emails_page)
+                    .route("/emails/{kind}", Method::GET, edit_transactional_email_form)
+                    .route("/emails/{kind}", Method::POST, update_transactional_email)
+                    .route(
+                        "/emails/{kind}/versions/{version}",
+                        Method::GET,
+                        transactional_email_version_page,
+                    );
+            }),
+    );
+    // JSON API, authenticated with the API keys managed from the dashboard
+    cfg.service(
+        web::scope("/api/v1")
+            .wrap(from_fn(reject_invalid_api_keys))
+            .app_data(api_json_config())
+            .app_data(api_path_config())
+            .configure(|cfg| {
+                Routes::new(cfg, "/api/v1", &mut table)
+                    .route("/subscribers", Method::GET, list_subscribers)
+                    .route("/subscribers", Method::POST, create_subscriber)
+                    .route("/subscribers/{id}", Method::GET, get_subscriber_by_id)
+                    .route("/subscribers/{id}", Method::PATCH, update_subscriber)
+                    .route("/subscribers/{id}", Method::DELETE, delete_subscriber)
+                    .route("/issues", Method::POST, create_issue)
+                    .route("/issues/{id}", Method::GET, get_issue)
+                    .route("/issues/{id}/publish", Method::POST, publish_issue)
+                    .route(
+                        "/issues/{id}/deliveries",
+                        Method::GET,
+                        list_issue_deliveries,
+                    );
+            }),
+    );
+    if serve_metrics {
+        Routes::new(cfg, "", &mut table).route("/metrics", Method::GET, metrics);
+    }
+    table
+}
+
+// Registers routes in `cfg`, recording them in a `RouteTable` on the way.
+// actix-web does not let us list the routes of an application once it is built.
+struct Routes<'a> {
+    cfg: &'a mut web::ServiceConfig,
+    // The path of the scope `cfg` belongs to
+    prefix: &'static str,
+    table: &'a mut RouteTable,
+}
+
+impl<'a> Routes<'a> {
+    fn new(
+        cfg: &'a mut web::ServiceConfig,
+        prefix: &'static str,
+        table: &'a mut RouteTable,
+    ) -> Self {
+        Self { cfg, prefix, table }
+    }
+
+    fn route<F, Args>(&mut self, path: &str, method: Method, handler: F) -> &mut Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.record(path, &method);
+        self.cfg.route(path, web::method(method).to(handler));
+        self
+    }
+
+    // A route with a resource of its own, so that `cors` only applies to it
+    fn cors_route<F, Args>(
+        &mut self,
+        path: &str,
+        method: Method,
+        handler: F,
+        cors: Cors,
+    ) -> &mut Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.record(path, &method);
+        self.cfg.service(
+            web::resource(path)
+                .wrap(cors)
+                .route(web::method(method).to(handler)),
+        );
+        self
+    }
+
+    fn record(&mut self, path: &str, method: &Method) {
+        self.table
+            .push((method.clone(), format!("{}{}", self.prefix, path)));
+    }
+}
+
+// Lets partner sites call the public subscription routes from their own pages.
+// Requests from other origins are still served, browsers just do not let their pages read the answer.
+fn subscription_cors(settings: &CorsSettings) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(settings.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(settings.allowed_headers.iter().map(String::as_str))
+        .max_age(settings.max_age_seconds);
+    for origin in &settings.allowed_origins {
+        cors = if origin == "*" {
+            cors.allow_any_origin()
+        } else {
+            // Browsers never send the trailing slash
+            cors.allowed_origin(origin.trim_end_matches('/'))
+        };
+    }
+    cors
+}
+
+// Lets `Application::run_until_stopped` wait for the requests being served.
+async fn track_in_flight_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let _token = req
+        .app_data::<Data<TaskTracker>>()
+        .map(|in_flight_requests| in_flight_requests.token());
+    next.call(req).await
+}
+
+// A bare server exposing `/metrics` only, to be kept away from public traffic.
+fn run_metrics_server(listener: TcpListener, db_pool: PgPool) -> Result<Server, anyhow::Error> {
+    let db_pool = Data::new(db_pool);
+    let server = HttpServer::new(move || {
+        App::new()
+            .route("/metrics", web::get().to(metrics))
+            .app_data(db_pool.clone())
+    })
+    .disable_signals()
     .listen(listener)?
     .run();
 
@@ -1,12 +1,26 @@
+use crate::configuration::{LogFormat, OpenTelemetrySettings};
+use opentelemetry::propagation::Injector;
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::Resource;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use tokio::task::JoinHandle;
 use tracing::{subscriber::set_global_default, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::fmt::MakeWriter;
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::fmt::{self, MakeWriter};
+use tracing_subscriber::{layer::SubscriberExt, reload, EnvFilter, Layer, Registry};
 
 /// Compose multiple layers into a `tracing`'s subscriber
 ///
+/// Spans are also exported to an OpenTelemetry collector when a `tracer` is provided.
+/// The returned handle changes `env_filter` once the subscriber is installed.
+///
 /// # Implementation notes
 ///
 /// We are using `impl Subscriber ` as a return type to avoid having to
@@ -18,8 +32,10 @@ use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
+    format: LogFormat,
     sink: Sink,
-) -> impl Subscriber + Send + Sync
+    tracer: Option<Tracer>,
+) -> (impl Subscriber + Send + Sync, LogFilterHandle)
 where
     // AThis `weird` syntax is a higer-rranked-trait-bound (HRTB)
     // It basically means that Sink implements the `MakeWriter1
@@ -31,15 +47,105 @@ where
     // if the RUST_LOG environment variable has not been set
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let (env_filter, reload_handle) = reload::Layer::new(env_filter);
 
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    // Each format is a different type: boxing them lets us pick one at runtime
+    let formatting_layer = match format {
+        LogFormat::Bunyan => BunyanFormattingLayer::new(name, sink).boxed(),
+        LogFormat::Pretty => fmt::layer().pretty().with_writer(sink).boxed(),
+        LogFormat::Compact => fmt::layer().compact().with_writer(sink).boxed(),
+    };
+    // `Option<Layer>` is a layer too: it is a no-op when `None`
+    let opentelemetry_layer = tracer.map(|t| tracing_opentelemetry::layer().with_tracer(t));
 
     // The `with` method is provided by `SubscriberExt` , an extension
     // trait for `Subscriber` exposed by `tracing_subscriber`
-    Registry::default()
+    let subscriber = Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
+        .with(opentelemetry_layer);
+    (subscriber, LogFilterHandle(reload_handle))
+}
+
+/// Changes which spans and events are recorded while the application is running,
+/// e.g. to trace `sqlx` statements for a while without a restart.
+#[derive(Clone)]
+pub struct LogFilterHandle(reload::Handle<EnvFilter, Registry>);
+
+#[derive(thiserror::Error, Debug)]
+pub enum LogFilterError {
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(#[from] ParseError),
+    #[error("The subscriber is gone")]
+    Unavailable(#[from] reload::Error),
+}
+
+impl LogFilterHandle {
+    /// The directives currently in use, e.g. `info,sqlx=debug`.
+    pub fn current(&self) -> Result<String, LogFilterError> {
+        Ok(self.0.with_current(|filter| filter.to_string())?)
+    }
+
+    pub fn reload(&self, directives: &str) -> Result<(), LogFilterError> {
+        let filter = EnvFilter::try_new(directives)?;
+        self.0.reload(filter)?;
+        Ok(())
+    }
+}
+
+/// Build a tracer provider exporting spans in batches to an OTLP/HTTP collector.
+///
+/// It must be called from within a tokio runtime: batches are sent from a background task.
+pub fn get_tracer_provider(
+    name: String,
+    settings: &OpenTelemetrySettings,
+) -> Result<TracerProvider, TraceError> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(&settings.endpoint)
+        .build_span_exporter()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_config(
+            opentelemetry_sdk::trace::config()
+                .with_resource(Resource::new(vec![KeyValue::new("service.name", name)])),
+        )
+        .build();
+    Ok(provider)
+}
+
+/// Register `provider` globally and return the tracer to pass to `get_subscriber`.
+///
+/// It also makes W3C `traceparent` the propagation format, both for incoming
+/// requests (see `TracingLogger`) and for outgoing ones (see `inject_trace_context`).
+pub fn init_tracer(name: String, provider: TracerProvider) -> Tracer {
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer = provider.tracer(name);
+    opentelemetry::global::set_tracer_provider(provider);
+    tracer
+}
+
+/// Add the trace context of the current span to the headers of an outgoing request.
+pub fn inject_trace_context(headers: &mut HeaderMap) {
+    let context = tracing::Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+// `opentelemetry-http` ships an injector for a more recent `http` crate than the one `reqwest` uses
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
 }
 
 /// Register a subscriber as global to process span data.
@@ -63,3 +169,52 @@ where
     let current_span = tracing::Span::current();
     actix_web::rt::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::{LogFormat, OpenTelemetrySettings};
+    use crate::telemetry::{get_subscriber, get_tracer_provider};
+    use opentelemetry::trace::TracerProvider as _;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // The batch exporter runs in a background task: `force_flush` blocks
+    // until it is done, so we need more than one worker thread.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spans_are_exported_to_the_collector() {
+        // Arrange
+        // A mock server stands in for the collector OTLP/HTTP receiver
+        let collector = MockServer::start().await;
+        Mock::given(path("/v1/traces"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1..)
+            .mount(&collector)
+            .await;
+        let settings = OpenTelemetrySettings {
+            enabled: true,
+            endpoint: collector.uri(),
+        };
+        let provider = get_tracer_provider("test".into(), &settings).unwrap();
+        let (subscriber, _) = get_subscriber(
+            "test".into(),
+            "info".into(),
+            LogFormat::Bunyan,
+            std::io::sink,
+            Some(provider.tracer("test")),
+        );
+
+        // Act
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("A span to export").in_scope(|| {});
+        });
+        provider.force_flush();
+
+        // Assert
+        let requests = collector.received_requests().await.unwrap();
+        let span_name = b"A span to export";
+        assert!(requests
+            .iter()
+            .any(|r| r.body.windows(span_name.len()).any(|w| w == span_name)));
+    }
+}
@@ -0,0 +1,230 @@
+use hmac::{Hmac, Mac};
+use kuchikiki::traits::TendrilSink;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// The open pixel and the click redirects of tracked newsletter issues.
+/// Like `UnsubscribeLinks`, they are signed with the HMAC secret of the application:
+/// they cannot be forged to record somebody else's activity, nor to redirect
+/// to a page that is not linked from the issue.
+pub struct TrackingLinks {
+    base_url: String,
+    hmac_secret: Secret<String>,
+}
+
+impl TrackingLinks {
+    pub fn new(base_url: String, hmac_secret: Secret<String>) -> Self {
+        Self {
+            base_url,
+            hmac_secret,
+        }
+    }
+
+    pub fn open_url(&self, newsletter_issue_id: Uuid, subscriber_id: Uuid) -> String {
+        let signature = self.sign(b"open:", newsletter_issue_id, subscriber_id, "");
+        format!(
+            "{}/tracking/open?newsletter_issue_id={}&subscriber_id={}&signature={}",
+            self.base_url, newsletter_issue_id, subscriber_id, signature
+        )
+    }
+
+    pub fn click_url(&self, newsletter_issue_id: Uuid, subscriber_id: Uuid, url: &str) -> String {
+        let signature = self.sign(b"click:", newsletter_issue_id, subscriber_id, url);
+        format!(
+            "{}/tracking/click?newsletter_issue_id={}&subscriber_id={}&url={}&signature={}",
+            self.base_url,
+            newsletter_issue_id,
+            subscriber_id,
+            urlencoding::encode(url),
+            signature
+        )
+    }
+
+    pub fn verify_open(
+        &self,
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        signature: &str,
+    ) -> bool {
+        self.verify(b"open:", newsletter_issue_id, subscriber_id, "", signature)
+    }
+
+    pub fn verify_click(
+        &self,
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        url: &str,
+        signature: &str,
+    ) -> bool {
+        self.verify(
+            b"click:",
+            newsletter_issue_id,
+            subscriber_id,
+            url,
+            signature,
+        )
+    }
+
+    /// Redirects the web links of a personalised HTML body through `/tracking/click`,
+    /// and adds the open pixel at the end.
+    /// Unsubscribe links are left alone: leaving is not a click.
+    pub fn track(&self, html: &str, newsletter_issue_id: Uuid, subscriber_id: Uuid) -> String {
+        let unsubscribe_prefix = format!("{}/subscriptions/unsubscribe", self.base_url);
+        let document = kuchikiki::parse_html().one(html);
+        if let Ok(links) = document.select("a[href]") {
+            for link in links {
+                let mut attributes = link.attributes.borrow_mut();
+                let Some(href) = attributes.get("href").map(str::to_owned) else {
+                    continue;
+                };
+                let is_web_link = href.starts_with("https://") || href.starts_with("http://");
+                if is_web_link && !href.starts_with(&unsubscribe_prefix) {
+                    attributes.insert(
+                        "href",
+                        self.click_url(newsletter_issue_id, subscriber_id, &href),
+                    );
+                }
+            }
+        }
+        // The body was a fragment before parsing, see `EmailHtml::prepare`
+        let mut output = String::with_capacity(html.len());
+        if let Ok(body) = document.select_first("body") {
+            for child in body.as_node().children() {
+                output.push_str(&child.to_string());
+            }
+        }
+        output.push_str(&format!(
+            r#"<img src="{}" width="1" height="1" alt="" style="display: block; border: 0">"#,
+            self.open_url(newsletter_issue_id, subscriber_id)
+                .replace('&', "&amp;")
+        ));
+        output
+    }
+
+    fn sign(
+        &self,
+        purpose: &[u8],
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        url: &str,
+    ) -> String {
+        let mac = self.mac(purpose, newsletter_issue_id, subscriber_id, url);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn verify(
+        &self,
+        purpose: &[u8],
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        url: &str,
+        signature: &str,
+    ) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        // In constant time
+        self.mac(purpose, newsletter_issue_id, subscriber_id, url)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+
+    fn mac(
+        &self,
+        purpose: &[u8],
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        url: &str,
+    ) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.hmac_secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        // The secret signs other things: the purpose is part of what is signed
+        mac.update(purpose);
+        mac.update(newsletter_issue_id.as_bytes());
+        mac.update(subscriber_id.as_bytes());
+        mac.update(url.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackingLinks;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn links(secret: &str) -> TrackingLinks {
+        TrackingLinks::new("https://example.com".into(), Secret::new(secret.into()))
+    }
+
+    fn signature(url: &str) -> &str {
+        url.split("signature=").nth(1).unwrap()
+    }
+
+    #[test]
+    fn click_links_are_verified_for_their_issue_subscriber_and_destination_only() {
+        let links = links("secret");
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let url = links.click_url(issue_id, subscriber_id, "https://example.org/?a=1&b=2");
+
+        assert!(url.starts_with(&format!(
+            "https://example.com/tracking/click?newsletter_issue_id={}&subscriber_id={}&url=https%3A%2F%2Fexample.org%2F%3Fa%3D1%26b%3D2&signature=",
+            issue_id, subscriber_id
+        )));
+        let signature = signature(&url);
+        assert!(links.verify_click(
+            issue_id,
+            subscriber_id,
+            "https://example.org/?a=1&b=2",
+            signature
+        ));
+        assert!(!links.verify_click(issue_id, subscriber_id, "https://evil.example/", signature));
+        assert!(!links.verify_click(
+            issue_id,
+            Uuid::new_v4(),
+            "https://example.org/?a=1&b=2",
+            signature
+        ));
+        assert!(!links.verify_click(
+            Uuid::new_v4(),
+            subscriber_id,
+            "https://example.org/?a=1&b=2",
+            signature
+        ));
+        // Nor as an open
+        assert!(!links.verify_open(issue_id, subscriber_id, signature));
+    }
+
+    #[test]
+    fn links_signed_with_another_secret_are_rejected() {
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let url = links("another secret").open_url(issue_id, subscriber_id);
+
+        assert!(!links("secret").verify_open(issue_id, subscriber_id, signature(&url)));
+        assert!(links("another secret").verify_open(issue_id, subscriber_id, signature(&url)));
+    }
+
+    #[test]
+    fn web_links_are_redirected_and_a_pixel_is_added() {
+        let links = links("secret");
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = r##"<p>Read <a href="https://example.org/post" rel="noopener noreferrer">the post</a>, <a href="#top">go up</a> or <a href="mailto:editor@example.com">reply</a>.</p><p><a href="https://example.com/subscriptions/unsubscribe?subscriber_id=1&amp;signature=2">Unsubscribe</a></p>"##;
+
+        let tracked = links.track(html, issue_id, subscriber_id);
+
+        let click_url = links
+            .click_url(issue_id, subscriber_id, "https://example.org/post")
+            .replace('&', "&amp;");
+        let open_url = links
+            .open_url(issue_id, subscriber_id)
+            .replace('&', "&amp;");
+        assert_eq!(
+            tracked,
+            format!(
+                r##"<p>Read <a href="{}" rel="noopener noreferrer">the post</a>, <a href="#top">go up</a> or <a href="mailto:editor@example.com">reply</a>.</p><p><a href="https://example.com/subscriptions/unsubscribe?subscriber_id=1&amp;signature=2">Unsubscribe</a></p><img src="{}" width="1" height="1" alt="" style="display: block; border: 0">"##,
+                click_url, open_url
+            )
+        );
+    }
+}
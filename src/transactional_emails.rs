@@ -0,0 +1,228 @@
+use crate::merge_tags::{EmailTemplates, MergeField, CONFIRMATION_TAGS};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Emails sent by the application itself, rather than written for an issue.
+/// Admins can edit them; the built-in default is used until they do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionalEmail {
+    Confirmation,
+}
+
+impl TransactionalEmail {
+    pub const ALL: &'static [TransactionalEmail] = &[TransactionalEmail::Confirmation];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionalEmail::Confirmation => "confirmation",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "confirmation" => Ok(TransactionalEmail::Confirmation),
+            other => Err(anyhow::anyhow!("`{}` is not a transactional email", other)),
+        }
+    }
+
+    /// Shown to admins.
+    pub fn description(&self) -> &'static str {
+        match self {
+            TransactionalEmail::Confirmation => "Sent to new subscribers to confirm their address",
+        }
+    }
+
+    pub fn known_tags(&self) -> &'static [MergeField] {
+        match self {
+            TransactionalEmail::Confirmation => CONFIRMATION_TAGS,
+        }
+    }
+
+    /// Without it, the email would be useless.
+    pub fn required_tag(&self) -> MergeField {
+        match self {
+            TransactionalEmail::Confirmation => MergeField::ConfirmationUrl,
+        }
+    }
+
+    pub fn default_template(&self) -> EmailTemplate {
+        match self {
+            TransactionalEmail::Confirmation => EmailTemplate {
+                subject: "Welcome!".into(),
+                html_template: "Welcome to our newsletter!<br />\
+                    Click <a href=\"{{ confirmation_url }}\">here</a> to confirm your subscription."
+                    .into(),
+                text_template:
+                    "Welcome to our newsletter!\nVisit {{ confirmation_url }} to confirm your subscription."
+                        .into(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionalEmail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailTemplate {
+    pub subject: String,
+    pub html_template: String,
+    pub text_template: String,
+}
+
+impl EmailTemplate {
+    /// Only the tags of `kind` are known, and its required tag must be in both bodies.
+    pub fn parse(&self, kind: TransactionalEmail) -> Result<EmailTemplates, String> {
+        let templates = EmailTemplates::parse(
+            &self.subject,
+            &self.html_template,
+            &self.text_template,
+            kind.known_tags(),
+        )
+        .map_err(|e| e.to_string())?;
+        let required = kind.required_tag();
+        if !templates.html_body.uses(required) || !templates.text_body.uses(required) {
+            return Err(format!(
+                "Both versions of the email must contain {}.",
+                required
+            ));
+        }
+        Ok(templates)
+    }
+}
+
+/// For the history of a transactional email.
+pub struct TemplateVersionSummary {
+    pub version: i32,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The latest version, or the built-in default if none has been saved.
+#[tracing::instrument(name = "Get a transactional email template", skip(pool))]
+pub async fn get_current_template(
+    pool: &PgPool,
+    kind: TransactionalEmail,
+) -> Result<EmailTemplate, anyhow::Error> {
+    let template = sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT subject, html_template, text_template
+        FROM transactional_email_versions
+        WHERE kind = $1
+        ORDER BY version DESC
+        LIMIT 1
+        "#,
+        kind.as_str(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a transactional email template.")?;
+    Ok(template.unwrap_or_else(|| kind.default_template()))
+}
+
+#[tracing::instrument(name = "Get a version of a transactional email template", skip(pool))]
+pub async fn get_template_version(
+    pool: &PgPool,
+    kind: TransactionalEmail,
+    version: i32,
+) -> Result<Option<EmailTemplate>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT subject, html_template, text_template
+        FROM transactional_email_versions
+        WHERE kind = $1 AND version = $2
+        "#,
+        kind.as_str(),
+        version,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a version of a transactional email template.")?;
+    Ok(template)
+}
+
+/// Most recent first.
+#[tracing::instrument(
+    name = "List the versions of a transactional email template",
+    skip(pool)
+)]
+pub async fn list_template_versions(
+    pool: &PgPool,
+    kind: TransactionalEmail,
+) -> Result<Vec<TemplateVersionSummary>, anyhow::Error> {
+    let versions = sqlx::query_as!(
+        TemplateVersionSummary,
+        r#"
+        SELECT version, subject, created_at
+        FROM transactional_email_versions
+        WHERE kind = $1
+        ORDER BY version DESC
+        "#,
+        kind.as_str(),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the versions of a transactional email template.")?;
+    Ok(versions)
+}
+
+/// Returns the number of the new version.
+#[tracing::instrument(name = "Save a transactional email template", skip(pool, template))]
+pub async fn save_template(
+    pool: &PgPool,
+    kind: TransactionalEmail,
+    template: &EmailTemplate,
+) -> Result<i32, anyhow::Error> {
+    let version = sqlx::query_scalar!(
+        r#"
+        INSERT INTO transactional_email_versions (kind, version, subject, html_template, text_template, created_at)
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4, now()
+        FROM transactional_email_versions
+        WHERE kind = $1
+        RETURNING version
+        "#,
+        kind.as_str(),
+        template.subject,
+        template.html_template,
+        template.text_template,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to store a new version of a transactional email template.")?;
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmailTemplate, TransactionalEmail};
+
+    #[test]
+    fn the_defaults_are_valid() {
+        for kind in TransactionalEmail::ALL {
+            assert!(kind.default_template().parse(*kind).is_ok());
+        }
+    }
+
+    #[test]
+    fn the_confirmation_email_needs_its_link() {
+        let template = EmailTemplate {
+            subject: "Welcome {{ name }}".into(),
+            html_template: "<a href=\"{{ confirmation_url }}\">Confirm</a>".into(),
+            text_template: "Welcome!".into(),
+        };
+
+        assert_eq!(
+            template
+                .parse(TransactionalEmail::Confirmation)
+                .err()
+                .unwrap(),
+            "Both versions of the email must contain `{{ confirmation_url }}`."
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// The links in newsletter issues that let subscribers leave without logging in.
+/// They are signed with the HMAC secret of the application: they cannot be forged
+/// to unsubscribe somebody else.
+pub struct UnsubscribeLinks {
+    base_url: String,
+    hmac_secret: Secret<String>,
+}
+
+impl UnsubscribeLinks {
+    pub fn new(base_url: String, hmac_secret: Secret<String>) -> Self {
+        Self {
+            base_url,
+            hmac_secret,
+        }
+    }
+
+    pub fn url_for(&self, subscriber_id: Uuid) -> String {
+        let signature = hex::encode(self.mac(subscriber_id).finalize().into_bytes());
+        format!(
+            "{}/subscriptions/unsubscribe?subscriber_id={}&signature={}",
+            self.base_url, subscriber_id, signature
+        )
+    }
+
+    /// The link of tracked issues: unsubscribing is recorded against `newsletter_issue_id`.
+    /// Only the subscriber is signed, the issue is informative.
+    pub fn url_for_issue(&self, subscriber_id: Uuid, newsletter_issue_id: Uuid) -> String {
+        format!(
+            "{}&newsletter_issue_id={}",
+            self.url_for(subscriber_id),
+            newsletter_issue_id
+        )
+    }
+
+    /// The link of test copies: it is valid, but unsubscribes nobody.
+    pub fn sample_url(&self) -> String {
+        self.url_for(Uuid::nil())
+    }
+
+    pub fn verify(&self, subscriber_id: Uuid, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        // In constant time
+        self.mac(subscriber_id).verify_slice(&signature).is_ok()
+    }
+
+    fn mac(&self, subscriber_id: Uuid) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.hmac_secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        // The secret signs other things: the purpose is part of what is signed
+        mac.update(b"unsubscribe:");
+        mac.update(subscriber_id.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnsubscribeLinks;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn links(secret: &str) -> UnsubscribeLinks {
+        UnsubscribeLinks::new("https://example.com".into(), Secret::new(secret.into()))
+    }
+
+    fn signature(url: &str) -> &str {
+        url.split("signature=").nth(1).unwrap()
+    }
+
+    #[test]
+    fn links_are_verified_for_their_subscriber_only() {
+        let links = links("secret");
+        let subscriber_id = Uuid::new_v4();
+        let url = links.url_for(subscriber_id);
+
+        assert!(url.starts_with(&format!(
+            "https://example.com/subscriptions/unsubscribe?subscriber_id={}&signature=",
+            subscriber_id
+        )));
+        assert!(links.verify(subscriber_id, signature(&url)));
+        assert!(!links.verify(Uuid::new_v4(), signature(&url)));
+    }
+
+    #[test]
+    fn links_signed_with_another_secret_are_rejected() {
+        let subscriber_id = Uuid::new_v4();
+        let url = links("another secret").url_for(subscriber_id);
+
+        assert!(!links("secret").verify(subscriber_id, signature(&url)));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let links = links("secret");
+
+        for signature in ["", "not-hex", "abcd"] {
+            assert!(!links.verify(Uuid::new_v4(), signature));
+        }
+    }
+}
@@ -1,25 +1,64 @@
-use actix_web::http::header::LOCATION;
+use crate::request_id::RequestId;
+use actix_web::error::InternalError;
+use actix_web::http::header::{ContentType, LOCATION};
 use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorPage {
+    request_id: Option<String>,
+}
 
 // Return an opaque 500 while preserving the error root's cause for logging.
+// The page shows the request id, for support to find the matching log lines.
 pub fn e500<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
-    actix_web::error::ErrorInternalServerError(e)
+    let page = ErrorPage {
+        request_id: RequestId::current().map(|request_id| request_id.to_string()),
+    };
+    let response = HttpResponse::InternalServerError()
+        .content_type(ContentType::html())
+        // Only a failing `Display` of a field could make the rendering fail
+        .body(page.render().unwrap_or_default());
+    InternalError::from_response(e, response).into()
 }
 
 // Return a 400 with the user-representation of the validation error as body.
 // The error root cause is preserved for logging purposes.
-pub fn e400<T: std::fmt::Debug + std::fmt::Display>(e: T) -> actix_web::Error
+pub fn e400<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
     actix_web::error::ErrorBadRequest(e)
 }
 
+// Return a 404 with the message as body, e.g. for pages that are not public.
+pub fn e404<T>(e: T) -> actix_web::Error
+where
+    T: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    actix_web::error::ErrorNotFound(e)
+}
+
 pub fn see_other(location: &str) -> HttpResponse {
     HttpResponse::SeeOther()
         .insert_header((LOCATION, location))
         .finish()
 }
+
+// Return a 200 with the rendered page as body, or the 500 page if the rendering fails.
+pub fn html_page<T: Template>(page: T) -> Result<HttpResponse, actix_web::Error> {
+    let body = page.render().map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+// The contents of the incoming flash messages, as the `flash_messages` partial expects them.
+pub fn flash_messages(incoming: &IncomingFlashMessages) -> Vec<String> {
+    incoming.iter().map(|m| m.content().to_owned()).collect()
+}
@@ -0,0 +1,101 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use secrecy::Secret;
+use uuid::Uuid;
+use zero2prod::authentication::{create_user, default_credentials_active, set_user_disabled};
+
+#[tokio::test]
+async fn a_created_user_can_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+    let password = Uuid::new_v4().to_string();
+    create_user(&username, Secret::new(password.clone()), &app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &username,
+            "password": &password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_disabled_user_cannot_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let found = set_user_disabled(&app.test_user.username, true, &app.db_pool)
+        .await
+        .unwrap();
+    assert!(found);
+
+    // Act - Part 1 - Login
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Authentication failed"));
+}
+
+#[tokio::test]
+async fn disabling_a_user_ends_their_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Disable the logged in user
+    set_user_disabled(&app.test_user.username, true, &app.db_pool)
+        .await
+        .unwrap();
+
+    // Act - Part 2 - Try to load the admin panel
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Enabling them again does not bring the session back
+    set_user_disabled(&app.test_user.username, false, &app.db_pool)
+        .await
+        .unwrap();
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn disabling_an_unknown_user_reports_it() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let found = set_user_disabled(&Uuid::new_v4().to_string(), true, &app.db_pool)
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!found);
+}
+
+#[tokio::test]
+async fn seeded_default_credentials_are_detected_until_disabled() {
+    // Arrange
+    let app = spawn_app().await;
+    assert!(default_credentials_active(&app.db_pool).await.unwrap());
+
+    // Act
+    set_user_disabled("admin", true, &app.db_pool)
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!default_credentials_active(&app.db_pool).await.unwrap());
+}
@@ -0,0 +1,107 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use reqwest::Method;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_api_keys() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_api_keys(&[("name", "CRM sync"), ("scope", "subscribers:read")])
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_new_api_key_is_shown_once_and_can_be_used() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Create the key
+    let response = app
+        .post_api_keys(&[("name", "CRM sync"), ("scope", "subscribers:read")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let key = html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|s| s.split("</code>").next())
+        .unwrap();
+    assert!(key.starts_with("z2p_"));
+
+    // Act - Part 2 - Use it
+    let response = app
+        .api_v1(Method::GET, "/subscribers", key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 3 - The listing only shows its prefix
+    let html_page = app.get_api_keys_html().await;
+    assert!(html_page.contains("CRM sync"));
+    assert!(html_page.contains(&key[..12]));
+    assert!(!html_page.contains(key));
+}
+
+#[tokio::test]
+async fn an_api_key_needs_a_name_and_a_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - No scope
+    let response = app.post_api_keys(&[("name", "CRM sync")]).await;
+    assert_is_redirect_to(&response, "/admin/api_keys");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_api_keys_html().await;
+    assert!(html_page.contains("<p><i>The API key must have at least one scope.</i></p>"));
+
+    // Act - Part 3 - No name
+    let response = app
+        .post_api_keys(&[("name", " "), ("scope", "issues:read")])
+        .await;
+    assert_is_redirect_to(&response, "/admin/api_keys");
+
+    // Act - Part 4 - Follow the redirect
+    let html_page = app.get_api_keys_html().await;
+    assert!(html_page.contains("<p><i>The API key must have a name.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_revoked_api_key_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let key = app
+        .create_api_key(&[zero2prod::authentication::ApiScope::SubscribersRead])
+        .await;
+    let key_id = sqlx::query!("SELECT key_id FROM api_keys")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .key_id;
+
+    // Act - Part 1 - Revoke the key
+    let response = app.post_revoke_api_key(&key_id.to_string()).await;
+    assert_is_redirect_to(&response, "/admin/api_keys");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_api_keys_html().await;
+    assert!(html_page.contains("<p><i>The API key has been revoked.</i></p>"));
+    assert!(html_page.contains("Revoked on"));
+
+    // Act - Part 3 - Use the key
+    let response = app
+        .api_v1(Method::GET, "/subscribers", &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,319 @@
+use crate::helpers::spawn_app;
+use reqwest::Method;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::authentication::ApiScope;
+
+#[tokio::test]
+async fn requests_without_a_valid_api_key_are_rejected_with_a_json_error() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for (authorization, description) in [
+        (None, "no Authorization header"),
+        (Some("Bearer z2p_unknown"), "an unknown key"),
+        (Some("Basic dXNlcjpwYXNz"), "the Basic scheme"),
+    ] {
+        // Act
+        let mut request = app
+            .api_client
+            .get(format!("{}/api/v1/subscribers", &app.address));
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", authorization);
+        }
+        let response = request.send().await.unwrap();
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "The API did not reject a request with {}.",
+            description
+        );
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Bearer realm="api""#
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unauthorized");
+        assert_eq!(body["error"]["message"], "Authentication failed");
+    }
+}
+
+#[tokio::test]
+async fn api_keys_are_limited_to_their_scopes() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.create_api_key(&[ApiScope::SubscribersRead]).await;
+
+    // Act
+    let response = app
+        .api_v1(Method::POST, "/subscribers", &key)
+        .json(&serde_json::json!({"name": "le guin", "email": "ursula_le_guin@gmail.com"}))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "forbidden");
+    assert_eq!(
+        body["error"]["message"],
+        "This API key is missing the `subscribers:write` scope"
+    );
+}
+
+#[tokio::test]
+async fn subscribers_can_be_created_read_updated_and_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app
+        .create_api_key(&[ApiScope::SubscribersRead, ApiScope::SubscribersWrite])
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Create
+    let response = app
+        .api_v1(Method::POST, "/subscribers", &key)
+        .json(&serde_json::json!({"name": "le guin", "email": "ursula_le_guin@gmail.com"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let created: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(created["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(created["status"], "pending_confirmation");
+    let subscriber_path = format!("/subscribers/{}", created["id"].as_str().unwrap());
+
+    // Act - Read
+    let response = app
+        .api_v1(Method::GET, "/subscribers", &key)
+        .send()
+        .await
+        .unwrap();
+    let listed: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(listed["subscribers"].as_array().unwrap().len(), 1);
+
+    // Act - Update
+    let response = app
+        .api_v1(Method::PATCH, &subscriber_path, &key)
+        .json(&serde_json::json!({"name": "Ursula K. Le Guin"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .api_v1(Method::GET, &subscriber_path, &key)
+        .send()
+        .await
+        .unwrap();
+    let updated: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(updated["name"], "Ursula K. Le Guin");
+
+    // Act - Delete
+    let response = app
+        .api_v1(Method::DELETE, &subscriber_path, &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .api_v1(Method::GET, &subscriber_path, &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn invalid_subscribers_are_rejected_with_a_json_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.create_api_key(&[ApiScope::SubscribersWrite]).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "", "email": "ursula_le_guin@gmail.com"}),
+            "empty name",
+        ),
+        (
+            serde_json::json!({"name": "Ursula", "email": "definitely-not-an-email"}),
+            "invalid email",
+        ),
+        (serde_json::json!({"name": "Ursula"}), "missing email"),
+    ];
+
+    for (body, description) in test_cases {
+        // Act
+        let response = app
+            .api_v1(Method::POST, "/subscribers", &key)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not return a 400 Bad Request when the payload had an {}.",
+            description
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "validation_error");
+    }
+}
+
+#[tokio::test]
+async fn subscribing_the_same_email_twice_is_a_conflict() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.create_api_key(&[ApiScope::SubscribersWrite]).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({"name": "le guin", "email": "ursula_le_guin@gmail.com"});
+    app.api_v1(Method::POST, "/subscribers", &key)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .api_v1(Method::POST, "/subscribers", &key)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn issues_can_be_created_published_once_and_tracked() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app
+        .create_api_key(&[ApiScope::IssuesRead, ApiScope::IssuesWrite])
+        .await;
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, 'ursula_le_guin@gmail.com', 'le guin', now(), 'confirmed')",
+        uuid::Uuid::new_v4(),
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Create the issue
+    let response = app
+        .api_v1(Method::POST, "/issues", &key)
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert!(issue["published_at"].is_null());
+    let issue_path = format!("/issues/{}", issue["newsletter_issue_id"].as_str().unwrap());
+
+    // Act - Part 2 - Publish it
+    let response = app
+        .api_v1(Method::POST, &format!("{}/publish", issue_path), &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert!(!issue["published_at"].is_null());
+    assert_eq!(issue["deliveries"]["succeeded"], 1);
+    assert_eq!(issue["deliveries"]["failed"], 0);
+
+    // Act - Part 3 - Publishing it again is a conflict
+    let response = app
+        .api_v1(Method::POST, &format!("{}/publish", issue_path), &key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 409);
+
+    // Act - Part 4 - List its deliveries
+    let response = app
+        .api_v1(Method::GET, &format!("{}/deliveries", issue_path), &key)
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    let deliveries = body["deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["recipient"], "ursula_le_guin@gmail.com");
+    assert_eq!(deliveries[0]["succeeded"], true);
+}
+
+#[tokio::test]
+async fn unknown_issues_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.create_api_key(&[ApiScope::IssuesRead]).await;
+
+    // Act
+    let response = app
+        .api_v1(
+            Method::GET,
+            &format!("/issues/{}", uuid::Uuid::new_v4()),
+            &key,
+        )
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn issues_with_unknown_merge_tags_are_rejected_with_a_json_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.create_api_key(&[ApiScope::IssuesWrite]).await;
+
+    // Act
+    let response = app
+        .api_v1(Method::POST, "/issues", &key)
+        .json(&serde_json::json!({
+            "title": "Hi {{ nmae }}",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "validation_error");
+}
@@ -0,0 +1,188 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::create_confirmed_subscriber;
+use crate::newsletter_drafts::create_draft;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn publish_draft(app: &TestApp, draft_id: &str) {
+    let response = app
+        .post_draft(draft_id, "/publish", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+async fn last_email_body(app: &TestApp) -> serde_json::Value {
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    serde_json::from_slice(&email_request.body).unwrap()
+}
+
+async fn json_feed(app: &TestApp) -> serde_json::Value {
+    app.get_public("/feed.json").await.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn sent_issues_are_in_the_public_archive_and_its_feeds() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let draft_id = create_draft(&app, "News for {{ name | default: everyone }}").await;
+    let permalink = format!("http://127.0.0.1/archive/{}", draft_id);
+
+    // Act - Part 1 - Publish: the email links to the archive
+    publish_draft(&app, &draft_id).await;
+    let body = last_email_body(&app).await;
+    assert!(body["HtmlBody"].as_str().unwrap().contains(&format!(
+        r#"<a href="{}" rel="noopener noreferrer">View this email in your browser</a>"#,
+        permalink
+    )));
+    assert!(body["TextBody"].as_str().unwrap().starts_with(&format!(
+        "View this email in your browser: {}\n\n",
+        permalink
+    )));
+
+    // Act - Part 2 - The archive lists it, for nobody in particular
+    let html_page = app.get_public("/archive").await.text().await.unwrap();
+    assert!(html_page.contains(&format!(
+        r#"<a href="/archive/{}">News for everyone</a>"#,
+        draft_id
+    )));
+
+    // Act - Part 3 - Its permalink shows it as sent, without a link to itself
+    let response = app.get_public(&format!("/archive/{}", draft_id)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Security-Policy"]
+        .to_str()
+        .unwrap()
+        .starts_with("sandbox "));
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<title>News for everyone</title>"));
+    assert!(html_page.contains("<p>Newsletter body as <b>HTML</b></p>"));
+    assert!(!html_page.contains("View this email in your browser"));
+
+    // Act - Part 4 - Both feeds have it
+    let response = app.get_public("/feed.atom").await;
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("application/atom+xml"));
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains(&format!("<id>{}</id>", permalink)));
+    assert!(feed.contains("<title>News for everyone</title>"));
+    assert!(feed.contains("&lt;p&gt;Newsletter body as &lt;b&gt;HTML&lt;/b&gt;&lt;/p&gt;"));
+    let feed = json_feed(&app).await;
+    assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(feed["items"][0]["url"], permalink.as_str());
+    assert_eq!(feed["items"][0]["title"], "News for everyone");
+    assert_eq!(
+        feed["items"][0]["content_text"],
+        "Newsletter body as plain text"
+    );
+}
+
+#[tokio::test]
+async fn drafts_and_issues_hidden_from_the_archive_are_not_public() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let draft_id = create_draft(&app, "A private issue").await;
+
+    // Act - Part 1 - Drafts are not public
+    let response = app.get_public(&format!("/archive/{}", draft_id)).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Act - Part 2 - Opt out, then publish: the email has no link to the archive
+    let response = app
+        .post_draft(
+            &draft_id,
+            "/archive",
+            &serde_json::json!({"hidden_from_archive": true}),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}/preview", draft_id),
+    );
+    publish_draft(&app, &draft_id).await;
+    let body = last_email_body(&app).await;
+    assert!(!body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("View this email in your browser"));
+
+    // Assert
+    let response = app.get_public(&format!("/archive/{}", draft_id)).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let html_page = app.get_public("/archive").await.text().await.unwrap();
+    assert!(!html_page.contains("A private issue"));
+    assert_eq!(json_feed(&app).await["items"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn sent_issues_can_be_taken_out_of_the_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let draft_id = create_draft(&app, "An issue").await;
+    publish_draft(&app, &draft_id).await;
+    let archive_path = format!("/archive/{}", draft_id);
+    let response = app.get_public(&archive_path).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 1 - Hide it
+    let response = app
+        .post_draft(
+            &draft_id,
+            "/archive",
+            &serde_json::json!({"hidden_from_archive": true}),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}/preview", draft_id),
+    );
+    let html_page = app
+        .get_draft(&draft_id, "/preview")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        html_page.contains("<p><i>The newsletter issue is hidden from the public archive.</i></p>")
+    );
+    let response = app.get_public(&archive_path).await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(json_feed(&app).await["items"], serde_json::json!([]));
+
+    // Act - Part 2 - Show it again: an unchecked checkbox is not submitted
+    app.post_draft(&draft_id, "/archive", &serde_json::json!({}))
+        .await;
+    let response = app.get_public(&archive_path).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,171 @@
+use crate::helpers::{spawn_app_with, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const PARTNER_ORIGIN: &str = "https://partner.example.com";
+
+async fn spawn_app_with_a_partner() -> TestApp {
+    spawn_app_with(|c| c.application.cors.allowed_origins = vec![PARTNER_ORIGIN.into()]).await
+}
+
+// What a browser sends before letting a page from `origin` call `path`
+async fn send_preflight(
+    app: &TestApp,
+    path: &str,
+    origin: &str,
+    method: &str,
+) -> reqwest::Response {
+    app.api_client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}{}", &app.address, path),
+        )
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", method)
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn preflights_from_an_allowed_origin_are_accepted_on_the_subscription_routes() {
+    // Arrange
+    let app = spawn_app_with_a_partner().await;
+
+    for (path, method) in [
+        ("/subscriptions", "POST"),
+        ("/subscriptions/confirm", "GET"),
+    ] {
+        // Act
+        let response = send_preflight(&app, path, PARTNER_ORIGIN, method).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200, "Preflight to {}", path);
+        let headers = response.headers();
+        assert_eq!(headers["Access-Control-Allow-Origin"], PARTNER_ORIGIN);
+        assert!(headers["Access-Control-Allow-Methods"]
+            .to_str()
+            .unwrap()
+            .contains(method));
+        assert!(headers["Access-Control-Allow-Headers"]
+            .to_str()
+            .unwrap()
+            .to_lowercase()
+            .contains("content-type"));
+        assert_eq!(headers["Access-Control-Max-Age"], "3600");
+    }
+}
+
+#[tokio::test]
+async fn preflights_from_other_origins_are_not_allowed() {
+    // Arrange
+    let app = spawn_app_with_a_partner().await;
+
+    // Act
+    let response = send_preflight(&app, "/subscriptions", "https://evil.example.com", "POST").await;
+
+    // Assert
+    assert!(!response.status().is_success());
+    assert!(!response
+        .headers()
+        .contains_key("Access-Control-Allow-Origin"));
+}
+
+#[tokio::test]
+async fn preflights_for_a_method_that_is_not_allowed_are_rejected() {
+    // Arrange
+    let app = spawn_app_with_a_partner().await;
+
+    // Act
+    let response = send_preflight(&app, "/subscriptions", PARTNER_ORIGIN, "DELETE").await;
+
+    // Assert
+    assert!(!response.status().is_success());
+}
+
+#[tokio::test]
+async fn no_origin_is_allowed_by_default() {
+    // Arrange
+    let app = spawn_app_with(|_| {}).await;
+
+    // Act
+    let response = send_preflight(&app, "/subscriptions", PARTNER_ORIGIN, "POST").await;
+
+    // Assert
+    assert!(!response
+        .headers()
+        .contains_key("Access-Control-Allow-Origin"));
+}
+
+#[tokio::test]
+async fn admin_routes_never_get_cors_headers() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.cors.allowed_origins = vec!["*".into()]).await;
+
+    for path in ["/admin/dashboard", "/admin/newsletters", "/login"] {
+        // Act
+        let response = send_preflight(&app, path, PARTNER_ORIGIN, "POST").await;
+
+        // Assert
+        assert!(
+            !response
+                .headers()
+                .contains_key("Access-Control-Allow-Origin"),
+            "{} answered a preflight",
+            path
+        );
+    }
+}
+
+#[tokio::test]
+async fn partner_sites_can_read_the_answer_to_a_subscription() {
+    // Arrange
+    let app = spawn_app_with_a_partner().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Origin", PARTNER_ORIGIN)
+        .json(&serde_json::json!({"name": "le guin", "email": "ursula_le_guin@gmail.com"}))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Access-Control-Allow-Origin"],
+        PARTNER_ORIGIN
+    );
+}
+
+#[tokio::test]
+async fn the_home_page_form_still_works_with_its_own_origin() {
+    // Arrange
+    let app = spawn_app_with_a_partner().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - browsers send an `Origin` header with same-origin form submissions too
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Origin", &app.address)
+        .form(&[("name", "le guin"), ("email", "ursula_le_guin@gmail.com")])
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,222 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::create_confirmed_subscriber;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn layout_body(name: &str, header: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "html_template": format!(
+            r#"<header>{}</header>{{{{ content }}}}<footer><a href="{{{{ unsubscribe_url }}}}">Unsubscribe</a></footer>"#,
+            header
+        ),
+        "text_template": format!("{}\n{{{{ content }}}}\nUnsubscribe: {{{{ unsubscribe_url }}}}", header),
+    })
+}
+
+// Create a layout and return its id, taken from the redirect to its page
+async fn create_layout(app: &TestApp, name: &str, header: &str) -> String {
+    let response = app.post_create_layout(&layout_body(name, header)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    location.strip_prefix("/admin/layouts/").unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_layouts() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_create_layout(&layout_body("Weekly", "Weekly"))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn issues_are_wrapped_in_their_layout_and_keep_the_version_they_were_sent_with() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let layout_id = create_layout(&app, "Weekly", "Weekly v1").await;
+
+    // Act - Part 1 - Publish an issue with the layout
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "layout_id": layout_id,
+            // Without the "view in browser" link on top
+            "hidden_from_archive": true,
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Part 1 - The body is wrapped, with a working unsubscribe link
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.starts_with(
+        r#"<header>Weekly v1</header><p>Newsletter body as HTML</p><footer><a href="http://127.0.0.1/subscriptions/unsubscribe?subscriber_id="#
+    ));
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(text_body.starts_with("Weekly v1\nNewsletter body as plain text\nUnsubscribe: http://127.0.0.1/subscriptions/unsubscribe?subscriber_id="));
+
+    // Act - Part 2 - Edit the layout
+    let response = app
+        .post_layout(&layout_id, &layout_body("", "Weekly v2"))
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/layouts/{}", layout_id));
+    let html_page = app.get_admin_html(&format!("/layouts/{}", layout_id)).await;
+    assert!(html_page.contains("<p><i>Version 2 of the layout has been saved.</i></p>"));
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/layouts/{}/versions/1">1</a>"#,
+        layout_id
+    )));
+
+    // Assert - Part 2 - The sent issue still shows the version it was sent with
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues",)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+        .to_string();
+    let preview = app
+        .get_draft(&newsletter_issue_id, "/preview/html")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(preview.contains("<header>Weekly v1</header>"));
+    let html_page = app
+        .get_draft(&newsletter_issue_id, "/preview")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Weekly, version 1"));
+    let html_page = app
+        .get_admin_html(&format!("/layouts/{}/versions/1", layout_id))
+        .await;
+    assert!(html_page.contains("&lt;header&gt;Weekly v1&lt;/header&gt;"));
+}
+
+#[tokio::test]
+async fn drafts_use_the_latest_version_of_their_layout() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let layout_id = create_layout(&app, "Weekly", "Weekly v1").await;
+    let response = app
+        .post_create_draft(&serde_json::json!({
+            "title": "A draft",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "layout_id": layout_id,
+            // Without the "view in browser" link on top
+            "hidden_from_archive": true,
+        }))
+        .await;
+    let draft_id = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .strip_prefix("/admin/newsletters/drafts/")
+        .unwrap()
+        .to_owned();
+
+    // Act
+    app.post_layout(&layout_id, &layout_body("", "Weekly v2"))
+        .await;
+
+    // Assert
+    let text = app
+        .get_draft(&draft_id, "/preview/text")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(text.starts_with("Weekly v2\nNewsletter body as plain text\n"));
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains(&format!(
+        r#"<option value="{}" selected>Weekly</option>"#,
+        layout_id
+    )));
+}
+
+#[tokio::test]
+async fn layouts_without_content_or_unsubscribe_link_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "name": "Weekly",
+                "html_template": r#"<a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+                "text_template": "{{ content }} {{ unsubscribe_url }}",
+            }),
+            "The HTML version of the layout must contain `{{ content }}` exactly once.",
+        ),
+        (
+            serde_json::json!({
+                "name": "Weekly",
+                "html_template": r#"{{ content }}<a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+                "text_template": "{{ content }}",
+            }),
+            "The plain text version of the layout must contain `{{ unsubscribe_url }}`.",
+        ),
+        (
+            serde_json::json!({
+                "name": " ",
+                "html_template": r#"{{ content }}<a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+                "text_template": "{{ content }} {{ unsubscribe_url }}",
+            }),
+            "The layout must have a name.",
+        ),
+    ];
+
+    for (body, error) in test_cases {
+        // Act
+        let response = app.post_create_layout(&body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/layouts");
+        let html_page = app.get_admin_html("/layouts").await;
+        assert!(
+            html_page.contains(&format!("<p><i>{}</i></p>", error)),
+            "Missing the error: {}",
+            error
+        );
+    }
+    let layouts = sqlx::query!("SELECT layout_id FROM email_layouts",)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(layouts.is_empty());
+}
@@ -1,4 +1,6 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
+use sqlx::{Connection, Executor, PgConnection};
+use zero2prod::configuration::get_configuration;
 
 #[tokio::test]
 async fn health_check_works() {
@@ -10,7 +12,7 @@ async fn health_check_works() {
 
     // Act
     let response = client
-        .get(&format!("{}/health_check", &app.address))
+        .get(format!("{}/health_check", &app.address))
         .send()
         .await
         .expect("Failed to execute request");
@@ -19,3 +21,107 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn liveness_check_works() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_health("live").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(Some(0), response.content_length());
+}
+
+#[tokio::test]
+async fn readiness_check_reports_every_required_component() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_health("ready").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "up");
+    let components = report["components"].as_array().unwrap();
+    assert_eq!(components.len(), 2);
+    for (component, name) in components.iter().zip(["postgres", "redis"]) {
+        assert_eq!(component["name"], name);
+        assert_eq!(component["status"], "up");
+        assert_eq!(component["required"], true);
+        assert!(component["latency_ms"].is_u64());
+    }
+}
+
+#[tokio::test]
+async fn readiness_check_fails_when_the_database_is_unavailable() {
+    // Arrange
+    let app = spawn_app().await;
+    let database_name: String = sqlx::query_scalar("SELECT current_database()")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    app.db_pool.close().await;
+    let mut connection =
+        PgConnection::connect_with(&get_configuration().unwrap().database.without_db())
+            .await
+            .unwrap();
+    connection
+        .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, database_name).as_str())
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.get_health("ready").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 503);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "down");
+    assert_eq!(report["components"][0]["name"], "postgres");
+    assert_eq!(report["components"][0]["status"], "down");
+    assert!(report["components"][0]["error"].is_string());
+    assert_eq!(report["components"][1]["status"], "up");
+}
+
+#[tokio::test]
+async fn an_unreachable_email_provider_does_not_fail_the_readiness_check() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.health_check.probe_email_provider = true;
+        // Nothing is listening there
+        c.email_client.base_url = "http://127.0.0.1:1".into();
+    })
+    .await;
+
+    // Act
+    let response = app.get_health("ready").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "up");
+    let email_provider = &report["components"][2];
+    assert_eq!(email_provider["name"], "email_provider");
+    assert_eq!(email_provider["status"], "down");
+    assert_eq!(email_provider["required"], false);
+}
+
+#[tokio::test]
+async fn a_reachable_email_provider_is_reported_up() {
+    // Arrange
+    let app = spawn_app_with(|c| c.health_check.probe_email_provider = true).await;
+
+    // Act
+    let response = app.get_health("ready").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["components"][2]["name"], "email_provider");
+    assert_eq!(report["components"][2]["status"], "up");
+}
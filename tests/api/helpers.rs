@@ -1,12 +1,16 @@
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
 use once_cell::sync::Lazy;
+use secrecy::ExposeSecret;
 use sqlx::{migrate, Connection, Executor, PgConnection, PgPool};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 use wiremock::MockServer;
-use zero2prod::configuration::{get_configuration, DatabaseSettings};
+use zero2prod::authentication::{create_api_key, ApiScope};
+use zero2prod::configuration::{get_configuration, DatabaseSettings, LogFormat, Settings};
 use zero2prod::startup::{get_connection_pool, Application};
-use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::telemetry::{get_subscriber, init_subscriber, LogFilterHandle};
 
 pub struct TestUser {
     pub user_id: Uuid,
@@ -63,7 +67,7 @@ impl TestUser {
 // When you want to see all the logs coming out of a certain test case to debug you can use
 // `TEST_LOG=true cargo test health_check_works | bunyan`
 // Note above we are using `bunyan` to prettify logs
-static TRACING: Lazy<()> = Lazy::new(|| {
+static TRACING: Lazy<LogFilterHandle> = Lazy::new(|| {
     let default_filter_level = "info".into();
     let subscriber_name = "test".into();
     // We cannot assign the output of `get_subscriber` to a variable based on the
@@ -71,21 +75,39 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     // `get_subscriber`, therefore they are not the same type. We could work around
     // it, but this is the most straight-forward way of moving forward
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let (subscriber, log_filter) = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            LogFormat::Bunyan,
+            std::io::stdout,
+            None,
+        );
         init_subscriber(subscriber);
+        log_filter
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let (subscriber, log_filter) = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            LogFormat::Bunyan,
+            std::io::sink,
+            None,
+        );
         init_subscriber(subscriber);
+        log_filter
     }
 });
 
 pub struct TestApp {
     pub port: u16,
     pub address: String,
+    pub metrics_port: Option<u16>,
     pub db_pool: PgPool,
     pub email_server: MockServer,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    // Cancel it to stop the application as SIGTERM would
+    pub shutdown: CancellationToken,
+    pub server: JoinHandle<Result<(), std::io::Error>>,
 }
 // Confirmation links embedded in the request to the email API.
 pub struct ConfirmationLinks {
@@ -94,9 +116,17 @@ pub struct ConfirmationLinks {
 }
 
 impl TestApp {
+    pub async fn get_health(&self, probe: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health/{}", &self.address, probe))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -104,18 +134,41 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_newsletters(&self, body: serde_json::Value) -> reqwest::Response {
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Submit the home page form the way a browser does.
+    pub async fn post_subscriptions_from_browser(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/newsletters", &self.address))
-            // Random credentials
-            // `reqwest` does all the encoding/formatting heavy-lifting for us.
-            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
-            .json(&body)
+            .post(format!("{}/subscriptions", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header(
+                "Accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            )
+            .body(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_home_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
     // Extract the confirmation links embedded in the request to the email API
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
@@ -149,7 +202,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             // This `reqwest` method makes sure that the body is URL-encoded
             // and the `Content-Type` header is set accordingly
             .form(body)
@@ -162,7 +215,7 @@ impl TestApp {
     // we do not expose the underlying reqwest::Response
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", self.address))
+            .get(format!("{}/login", self.address))
             .send()
             .await
             .expect("Failed to execute reqwest.")
@@ -173,7 +226,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -185,7 +238,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -196,7 +249,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -207,9 +260,30 @@ impl TestApp {
         self.get_change_password().await.text().await.unwrap()
     }
 
+    pub async fn get_log_filter(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/log_filter", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_log_filter_html(&self) -> String {
+        self.get_log_filter().await.text().await.unwrap()
+    }
+
+    pub async fn post_log_filter(&self, filter: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/log_filter", &self.address))
+            .form(&[("filter", filter)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -217,7 +291,7 @@ impl TestApp {
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -227,25 +301,212 @@ impl TestApp {
         self.get_publish_newsletter().await.text().await.unwrap()
     }
 
+    pub async fn get_metrics(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/metrics", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_keys_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/api_keys", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_api_keys<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/api_keys", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_api_key(&self, key_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/api_keys/{}/revoke",
+                &self.address, key_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Create a key for the test user, bypassing the dashboard
+    pub async fn create_api_key(&self, scopes: &[ApiScope]) -> String {
+        create_api_key("test", scopes, self.test_user.user_id, &self.db_pool)
+            .await
+            .expect("Failed to create an API key.")
+            .expose_secret()
+            .to_owned()
+    }
+
+    // A request to the JSON API, authenticated with `api_key`
+    pub fn api_v1(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        api_key: &str,
+    ) -> reqwest::RequestBuilder {
+        self.api_client
+            .request(method, format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(api_key)
+    }
+
     pub async fn post_publish_newsletter<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
+
+    pub async fn post_create_draft<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters/drafts", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // `path` is relative to the draft, e.g. `/preview`
+    pub async fn get_draft(&self, draft_id: &str, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/drafts/{}{}",
+                &self.address, draft_id, path
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_draft_html(&self, draft_id: &str) -> String {
+        self.get_draft(draft_id, "").await.text().await.unwrap()
+    }
+
+    // `path` is relative to the draft, e.g. `/publish`
+    pub async fn post_draft<Body>(
+        &self,
+        draft_id: &str,
+        path: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/drafts/{}{}",
+                &self.address, draft_id, path
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_create_layout<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/layouts", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_layout<Body>(&self, layout_id: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/layouts/{}", &self.address, layout_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_transactional_email<Body>(&self, kind: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/emails/{}", &self.address, kind))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // The pages anyone can see, e.g. `/archive` or `/feed.atom`
+    pub async fn get_public(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Postmark sends the credentials set in its webhook URL, see `configuration/base.yaml`
+    pub async fn post_postmark_webhook(&self, event: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/postmark", &self.address))
+            .basic_auth("postmark", Some("my-webhook-password"))
+            .json(event)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // `path` is relative to the admin dashboard, e.g. `/layouts`
+    pub async fn get_admin_html(&self, path: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
 }
 
 // Spins up an instance of our application
 // and returns its address and pool connection
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+// Same as `spawn_app`, letting the test tweak the configuration
+// before the application is built
+pub async fn spawn_app_with(customise: impl FnOnce(&mut Settings)) -> TestApp {
     // The first time `initialize` is invoked the code in `TRACING` is executed.
     // All other invocations will instead skip execution
-    Lazy::force(&TRACING);
+    // Every app shares the global subscriber, hence its filter
+    let log_filter = Lazy::force(&TRACING).clone();
 
     // Launch a mock server to stand in for Postmark's API
     let email_server = MockServer::start().await;
@@ -259,6 +520,9 @@ pub async fn spawn_app() -> TestApp {
         c.application.port = 0;
         // Use the mockServer as email API
         c.email_client.base_url = email_server.uri();
+        // Rate limiting counters are kept in the Redis instance shared by every test
+        c.signup_protection.key_prefix = Uuid::new_v4().to_string();
+        customise(&mut c);
         c
     };
 
@@ -266,15 +530,17 @@ pub async fn spawn_app() -> TestApp {
     configure_database(&configuration.database).await;
 
     // Launch the application as a background task
-    let application = Application::build(configuration.clone())
+    let application = Application::build(configuration.clone(), log_filter)
         .await
         .expect("Failed to build application");
     let application_port = application.port();
+    let metrics_port = application.metrics_port();
     // Get the port before spawning the application
     let address = format!("http://127.0.0.1:{}", application_port);
-    // tokio::spawn returns a handle to the spawned future
-    // but we have no use for it here, hence the non-binding let
-    let _ = tokio::spawn(application.run_until_stopped());
+    let shutdown = application.shutdown_token();
+    // tokio::spawn returns a handle to the spawned future:
+    // awaiting it tells us when the application has stopped
+    let server = tokio::spawn(application.run_until_stopped());
 
     let client = reqwest::Client::builder()
         // We do not want reqwest to follow redirects automatically
@@ -287,10 +553,13 @@ pub async fn spawn_app() -> TestApp {
     let test_app = TestApp {
         address,
         port: application_port,
+        metrics_port,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
         test_user: TestUser::generate(),
         api_client: client,
+        shutdown,
+        server,
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
@@ -304,7 +573,7 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
         .await
         .expect("Failed to connect to Postgres");
     connection
-        .execute(&*format!(r#"CREATE DATABASE "{}";"#, config.database_name).as_str())
+        .execute(format!(r#"CREATE DATABASE "{}";"#, config.database_name).as_str())
         .await
         .expect("Failed to create database.");
 
@@ -0,0 +1,77 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_log_filter_form() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_log_filter().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_the_log_filter() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_log_filter("trace").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_log_filter_can_be_changed_at_runtime() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Change the filter
+    // Every test shares the same subscriber: we pick a filter that does not
+    // change what the other tests log
+    let response = app.post_log_filter("info,sqlx=warn").await;
+    assert_is_redirect_to(&response, "/admin/log_filter");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_log_filter_html().await;
+    assert!(html_page.contains("<p><i>The log filter has been changed.</i></p>"));
+    assert!(html_page.contains("sqlx=warn"));
+
+    // Clean up
+    app.post_log_filter("info").await;
+}
+
+#[tokio::test]
+async fn an_invalid_log_filter_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Submit an invalid filter
+    let response = app.post_log_filter("sqlx=loud").await;
+    assert_is_redirect_to(&response, "/admin/log_filter");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_log_filter_html().await;
+    assert!(html_page.contains("Invalid log filter"));
+    assert!(!html_page.contains("sqlx=loud"));
+}
+
+#[tokio::test]
+async fn an_empty_log_filter_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Submit an empty filter
+    let response = app.post_log_filter("  ").await;
+    assert_is_redirect_to(&response, "/admin/log_filter");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_log_filter_html().await;
+    assert!(html_page.contains("<p><i>The log filter cannot be empty.</i></p>"));
+}
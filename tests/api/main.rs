@@ -1,8 +1,27 @@
 mod admin_dashboard;
+mod admin_users;
+mod api_keys;
+mod api_v1;
+mod archive;
 mod change_password;
+mod cors;
+mod email_layouts;
 mod health_check;
 mod helpers;
+mod log_filter;
 mod login;
+mod metrics;
 mod newsletter;
+mod newsletter_drafts;
+mod newsletter_scheduling;
+mod openapi;
+mod request_id;
+mod security_headers;
+mod shutdown;
+mod signup_protection;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
+mod transactional_emails;
+mod unsubscribe;
+mod webhooks;
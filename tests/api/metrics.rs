@@ -0,0 +1,94 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn metrics_are_exposed_in_the_prometheus_text_format() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_metrics().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/plain; version=0.0.4"
+    );
+    let body = response.text().await.unwrap();
+    assert!(body.contains("zero2prod_db_pool_connections"));
+    assert!(body.contains("zero2prod_db_pool_idle_connections"));
+}
+
+#[tokio::test]
+async fn http_requests_are_recorded_by_route_pattern() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+    reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=unknown",
+        &app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    let metrics = app.get_metrics().await.text().await.unwrap();
+    assert!(metrics.contains(
+        r#"zero2prod_http_request_duration_seconds_count{method="POST",route="/subscriptions",status="200"}"#
+    ));
+    assert!(metrics.contains(
+        r#"zero2prod_http_request_duration_seconds_count{method="GET",route="/subscriptions/confirm",status="401"}"#
+    ));
+    assert!(metrics.contains(r#"zero2prod_emails_sent_total{outcome="success"}"#));
+    assert!(metrics.contains("zero2prod_subscriptions_created_total"));
+}
+
+#[tokio::test]
+async fn metrics_can_be_served_from_a_dedicated_port() {
+    // Arrange
+    let app = spawn_app_with(|c| c.metrics.port = Some(0)).await;
+    let metrics_port = app.metrics_port.unwrap();
+
+    // Act - Part 1 - The main application does not expose them
+    let response = app.get_metrics().await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Act - Part 2 - The dedicated port does
+    let response = reqwest::get(format!("http://127.0.0.1:{}/metrics", metrics_port))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("zero2prod_db_pool_connections"));
+}
+
+#[tokio::test]
+async fn password_verifications_are_timed() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "not-the-password",
+    }))
+    .await;
+
+    // Assert
+    let metrics = app.get_metrics().await.text().await.unwrap();
+    assert!(metrics
+        .contains(r#"zero2prod_password_verification_duration_seconds_count{outcome="failure"}"#));
+}
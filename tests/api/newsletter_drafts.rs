@@ -0,0 +1,335 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::create_confirmed_subscriber;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+pub fn draft_body(title: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": title,
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as <b>HTML</b></p>",
+    })
+}
+
+// Create a draft and return its id, taken from the redirect to its page
+pub async fn create_draft(app: &TestApp, title: &str) -> String {
+    let response = app.post_create_draft(&draft_body(title)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    location
+        .strip_prefix("/admin/newsletters/drafts/")
+        .unwrap()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_drafts() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_create_draft(&draft_body("A draft")).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn drafts_are_saved_listed_and_edited_without_being_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Save a draft
+    let draft_id = create_draft(&app, "First draft").await;
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html_page.contains(r#"value="First draft""#));
+    // The HTML content is escaped in the textarea
+    assert!(html_page.contains("&lt;p&gt;Newsletter body as &lt;b&gt;HTML&lt;/b&gt;&lt;/p&gt;"));
+
+    // Act - Part 3 - Edit it
+    let response = app
+        .post_draft(&draft_id, "", &draft_body("Edited draft"))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", draft_id),
+    );
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains(r#"value="Edited draft""#));
+
+    // Act - Part 4 - It is listed with its status
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<td>Edited draft</td>"));
+    assert!(html_page.contains("<td>draft</td>"));
+    // Mock verifies on Drop that nothing has been sent
+}
+
+#[tokio::test]
+async fn drafts_can_be_previewed_as_html_and_plain_text() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+
+    // Act - Part 1 - The preview page
+    let response = app.get_draft(&draft_id, "/preview").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&format!(
+        r#"src="/admin/newsletters/drafts/{}/preview/html""#,
+        draft_id
+    )));
+    assert!(html_page.contains("Newsletter body as plain text</pre>"));
+
+    // Act - Part 2 - The HTML body, as sent
+    let response = app.get_draft(&draft_id, "/preview/html").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let policy = response.headers()["Content-Security-Policy"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(policy.starts_with("sandbox;"));
+    assert_eq!(response.headers()["X-Frame-Options"], "SAMEORIGIN");
+    // With a link to the public archive, filled in once the issue is sent
+    assert_eq!(
+        response.text().await.unwrap(),
+        "<p><a href=\"{{ view_in_browser_url }}\" rel=\"noopener noreferrer\">View this email in your browser</a></p>\n\
+        <p>Newsletter body as <b>HTML</b></p>"
+    );
+
+    // Act - Part 3 - The plain-text body
+    let response = app.get_draft(&draft_id, "/preview/text").await;
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    assert_eq!(
+        response.text().await.unwrap(),
+        "View this email in your browser: {{ view_in_browser_url }}\n\nNewsletter body as plain text"
+    );
+}
+
+#[tokio::test]
+async fn drafts_can_be_written_in_markdown() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_create_draft(&serde_json::json!({
+            "title": "A draft",
+            "markdown_content": "# Hello\n\n*Everyone*",
+            // Without the "view in browser" link on top
+            "hidden_from_archive": true,
+        }))
+        .await;
+    let draft_id = response.headers()["Location"]
+        .to_str()
+        .unwrap()
+        .strip_prefix("/admin/newsletters/drafts/")
+        .unwrap()
+        .to_owned();
+
+    // Act - Part 1 - Both versions are generated
+    let html = app
+        .get_draft(&draft_id, "/preview/html")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(html, "<h1>Hello</h1>\n<p><em>Everyone</em></p>\n");
+    let text = app
+        .get_draft(&draft_id, "/preview/text")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(text, "Hello\n\nEveryone");
+
+    // Act - Part 2 - The Markdown is kept to edit the draft
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains("# Hello\n\n*Everyone*</textarea>"));
+
+    // Act - Part 3 - Edit it in raw HTML mode instead
+    app.post_draft(&draft_id, "", &draft_body("A draft")).await;
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains(r#"name="markdown_content" rows="20" cols="50"></textarea>"#));
+}
+
+#[tokio::test]
+async fn a_test_copy_is_only_sent_to_the_admin() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Send a test copy
+    let response = app
+        .post_draft(
+            &draft_id,
+            "/test",
+            &serde_json::json!({"email": "editor@example.com"}),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", draft_id),
+    );
+
+    // Assert
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "editor@example.com");
+    assert_eq!(body["Subject"], "[Test] A draft");
+
+    // Act - Part 2 - The address is remembered, the issue is still a draft
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains("<p><i>A test copy has been sent to editor@example.com.</i></p>"));
+    assert!(html_page.contains(r#"value="editor@example.com""#));
+    // Mock verifies on Drop that the subscriber did not get it
+}
+
+#[tokio::test]
+async fn a_draft_is_published_once() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish it
+    let response = app
+        .post_draft(&draft_id, "/publish", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+    assert!(html_page.contains("<td>sent</td>"));
+
+    // Act - Part 2 - Publish it again
+    let response = app
+        .post_draft(&draft_id, "/publish", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>This newsletter issue has already been published</i></p>"));
+
+    // Act - Part 3 - It cannot be edited anymore
+    let response = app.post_draft(&draft_id, "", &draft_body("Too late")).await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}/preview", draft_id),
+    );
+    let html_page = app
+        .get_draft(&draft_id, "/preview")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>Only drafts can be edited.</i></p>"));
+    assert!(html_page.contains("<h1>A draft</h1>"));
+    // Mock verifies on Drop that the subscriber got a single email
+}
+
+#[tokio::test]
+async fn drafts_with_unknown_merge_tags_are_saved_but_not_published() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let draft_id = create_draft(&app, "A draft").await;
+
+    // Act - Part 1 - Save the typo
+    let response = app
+        .post_draft(&draft_id, "", &draft_body("Hi {{ nmae }}"))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", draft_id),
+    );
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html_page.contains("is not a known merge tag"));
+
+    // Act - Part 2 - Try to publish it
+    let response = app
+        .post_draft(&draft_id, "/publish", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", draft_id),
+    );
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains("is not a known merge tag"));
+    assert!(html_page.contains(r#"value="Hi {{ nmae }}""#));
+    // Mock verifies on Drop that nothing has been sent
+}
+
+#[tokio::test]
+async fn drafts_over_the_gmail_clipping_size_get_a_warning() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    let warning = "Gmail clips messages over 102 KB";
+
+    // Act - Part 1 - A short draft
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(!html_page.contains(warning));
+
+    // Act - Part 2 - A long one
+    let paragraph = format!("<p>{}</p>", "Lorem ipsum dolor sit amet. ".repeat(40));
+    let body = serde_json::json!({
+        "title": "A draft",
+        "text_content": "Newsletter body as plain text",
+        "html_content": paragraph.repeat(100),
+    });
+    app.post_draft(&draft_id, "", &body).await;
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page.contains(warning));
+    let html_page = app
+        .get_draft(&draft_id, "/preview")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(warning));
+}
@@ -0,0 +1,361 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use crate::newsletter::create_confirmed_subscriber;
+use crate::newsletter_drafts::create_draft;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::time::Instant;
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::newsletter_issues::claim_due_issue;
+
+// A week from now, as `<input type="datetime-local">` sends it
+fn next_week() -> String {
+    (Utc::now() + Duration::days(7))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
+
+async fn issue_status(db_pool: &PgPool, draft_id: &str) -> String {
+    sqlx::query!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        Uuid::parse_str(draft_id).unwrap(),
+    )
+    .fetch_one(db_pool)
+    .await
+    .expect("Failed to fetch the newsletter issue.")
+    .status
+}
+
+// Bring the send time of a scheduled issue forward, as if we had waited for it
+async fn make_due(app: &TestApp, draft_id: &str) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_for = now() - interval '1 minute' WHERE newsletter_issue_id = $1",
+        Uuid::parse_str(draft_id).unwrap(),
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to update the newsletter issue.");
+}
+
+#[tokio::test]
+async fn an_issue_can_be_scheduled_from_the_publish_form() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "scheduled_for": "2100-07-06T09:00",
+            "timezone": "Europe/Paris",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been scheduled for 2100-07-06 09:00 Europe/Paris.</i></p>"
+    ));
+    assert!(html_page.contains("<td>scheduled</td>"));
+    assert!(html_page.contains("<td>2100-07-06 09:00 Europe/Paris</td>"));
+    // Mock verifies on Drop that nothing has been sent yet
+}
+
+#[tokio::test]
+async fn invalid_send_times_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    let test_cases = vec![
+        (
+            "2000-01-01T09:00",
+            "UTC",
+            "The send time must be in the future.",
+        ),
+        (
+            "next tuesday",
+            "UTC",
+            "next tuesday is not a valid date and time",
+        ),
+        (
+            "2100-07-06T09:00",
+            "Europe/Atlantis",
+            "Europe/Atlantis is not a known timezone, e.g. Europe/Paris",
+        ),
+    ];
+
+    for (scheduled_for, timezone, message) in test_cases {
+        // Act
+        let response = app
+            .post_draft(
+                &draft_id,
+                "/schedule",
+                &serde_json::json!({"scheduled_for": scheduled_for, "timezone": timezone}),
+            )
+            .await;
+
+        // Assert
+        assert_is_redirect_to(
+            &response,
+            &format!("/admin/newsletters/drafts/{}", draft_id),
+        );
+        let html_page = app.get_draft_html(&draft_id).await;
+        assert!(html_page.contains(&format!("<p><i>{}</i></p>", message)));
+        assert_eq!(issue_status(&app.db_pool, &draft_id).await, "draft");
+    }
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_rescheduled_then_cancelled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    let preview = format!("/admin/newsletters/drafts/{}/preview", draft_id);
+
+    // Act - Part 1 - Schedule it
+    let response = app
+        .post_draft(
+            &draft_id,
+            "/schedule",
+            &serde_json::json!({"scheduled_for": next_week(), "timezone": "UTC"}),
+        )
+        .await;
+    assert_is_redirect_to(&response, &preview);
+    assert_eq!(issue_status(&app.db_pool, &draft_id).await, "scheduled");
+
+    // Act - Part 2 - Reschedule it
+    let response = app
+        .post_draft(
+            &draft_id,
+            "/schedule",
+            &serde_json::json!({"scheduled_for": "2100-07-06T09:00", "timezone": "America/New_York"}),
+        )
+        .await;
+    assert_is_redirect_to(&response, &preview);
+    let html_page = app
+        .get_draft(&draft_id, "/preview")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p>Scheduled for: 2100-07-06 09:00 America/New_York</p>"));
+    assert!(html_page.contains(r#"value="2100-07-06T09:00""#));
+
+    // Act - Part 3 - Cancel it
+    let response = app
+        .post_draft(&draft_id, "/cancel", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", draft_id),
+    );
+    let html_page = app.get_draft_html(&draft_id).await;
+    assert!(html_page
+        .contains("<p><i>The schedule has been cancelled: the issue is a draft again.</i></p>"));
+    assert_eq!(issue_status(&app.db_pool, &draft_id).await, "draft");
+
+    // Act - Part 4 - Cancel it again
+    let response = app
+        .post_draft(&draft_id, "/cancel", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, &preview);
+}
+
+#[tokio::test]
+async fn due_issues_are_sent_by_the_scheduler() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.scheduler_interval_seconds = 1).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    app.post_draft(
+        &draft_id,
+        "/schedule",
+        &serde_json::json!({"scheduled_for": next_week(), "timezone": "UTC"}),
+    )
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    make_due(&app, &draft_id).await;
+
+    // Assert
+    let started = Instant::now();
+    while issue_status(&app.db_pool, &draft_id).await != "sent" {
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(10),
+            "The scheduler did not send the due issue."
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    // It cannot be cancelled anymore
+    let response = app
+        .post_draft(&draft_id, "/cancel", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}/preview", draft_id),
+    );
+    // Mock verifies on Drop that the subscriber got a single email
+}
+
+#[tokio::test]
+async fn a_due_issue_is_claimed_by_a_single_replica() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    app.post_draft(
+        &draft_id,
+        "/schedule",
+        &serde_json::json!({"scheduled_for": next_week(), "timezone": "UTC"}),
+    )
+    .await;
+    make_due(&app, &draft_id).await;
+
+    // Act - as many schedulers as replicas, at the same time
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let pool = app.db_pool.clone();
+            tokio::spawn(async move { claim_due_issue(&pool).await })
+        })
+        .collect();
+    let mut claimed = Vec::new();
+    for handle in handles {
+        if let Some(issue) = handle.await.unwrap().unwrap() {
+            claimed.push(issue);
+        }
+    }
+
+    // Assert
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].newsletter_issue_id.to_string(), draft_id);
+}
+
+#[tokio::test]
+async fn a_delivery_stops_between_two_subscribers_on_shutdown() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.scheduler_interval_seconds = 1).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    app.post_draft(
+        &draft_id,
+        "/schedule",
+        &serde_json::json!({"scheduled_for": next_week(), "timezone": "UTC"}),
+    )
+    .await;
+    let confirmation_emails = app.email_server.received_requests().await.unwrap().len();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Keeps the first email in flight while we shut down
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_millis(500)))
+        .mount(&app.email_server)
+        .await;
+    make_due(&app, &draft_id).await;
+    while app.email_server.received_requests().await.unwrap().len() == confirmation_emails {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    // Act
+    let server = app.server;
+    app.shutdown.cancel();
+    server.await.unwrap().unwrap();
+
+    // Assert
+    let emails = app.email_server.received_requests().await.unwrap().len();
+    assert_eq!(emails - confirmation_emails, 1);
+    assert_eq!(issue_status(&app.db_pool, &draft_id).await, "sending");
+}
+
+#[tokio::test]
+async fn an_interrupted_delivery_is_resumed_by_the_scheduler() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.scheduler_interval_seconds = 1).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let draft_id = create_draft(&app, "A draft").await;
+    app.post_draft(
+        &draft_id,
+        "/schedule",
+        &serde_json::json!({"scheduled_for": next_week(), "timezone": "UTC"}),
+    )
+    .await;
+    // A replica claimed the issue an hour ago and went away after its first subscriber
+    let issue_id = Uuid::parse_str(&draft_id).unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO email_deliveries (delivery_id, recipient, subject, newsletter_issue_id, succeeded, sent_at)
+        SELECT $1, email, 'A draft', $2, true, now()
+        FROM subscriptions
+        LIMIT 1
+        "#,
+        Uuid::new_v4(),
+        issue_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to record a delivery.");
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = now() - interval '1 hour', layout_version = (
+            SELECT MAX(version) FROM email_layout_versions v
+            WHERE v.layout_id = newsletter_issues.layout_id
+        )
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to update the newsletter issue.");
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - wait for the scheduler
+    let started = Instant::now();
+    while issue_status(&app.db_pool, &draft_id).await != "sent" {
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(10),
+            "The scheduler did not resume the interrupted issue."
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    // Assert
+    // Mock verifies on Drop that only the remaining subscriber got an email
+    let recipients = sqlx::query!(
+        "SELECT COUNT(DISTINCT recipient) AS count FROM email_deliveries WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(recipients.count, Some(2));
+}
@@ -0,0 +1,116 @@
+use crate::helpers::spawn_app;
+use actix_web::App;
+use std::collections::BTreeSet;
+use utoipa::OpenApi;
+use zero2prod::configuration::get_configuration;
+use zero2prod::routes::ApiDoc;
+use zero2prod::startup::{configure, RouteTable};
+
+async fn get_openapi_document() -> serde_json::Value {
+    let app = spawn_app().await;
+    let response = app
+        .api_client
+        .get(format!("{}/openapi.json", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn the_openapi_document_describes_the_subscription_form() {
+    // Act
+    let document = get_openapi_document().await;
+
+    // Assert
+    assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+    let request_body = &document["paths"]["/subscriptions"]["post"]["requestBody"]["content"]
+        ["application/x-www-form-urlencoded"]["schema"]["$ref"];
+    assert_eq!(request_body, "#/components/schemas/SubscriptionForm");
+    let form = &document["components"]["schemas"]["SubscriptionForm"];
+    assert_eq!(form["required"], serde_json::json!(["email", "name"]));
+}
+
+#[test]
+fn every_route_is_documented_and_every_documented_route_exists() {
+    // Arrange
+    let configuration = get_configuration().expect("Failed to read configuration.");
+    let mut table = RouteTable::new();
+
+    // Act - the routes of `startup::run`
+    App::new().configure(|cfg| {
+        table = configure(cfg, &configuration.application.cors, true);
+    });
+
+    // Assert
+    let registered: BTreeSet<(String, String)> = table
+        .into_iter()
+        .map(|(method, path)| (method.as_str().to_lowercase(), path))
+        .collect();
+    let documented = documented_routes();
+    let undocumented: Vec<_> = registered.difference(&documented).collect();
+    assert!(
+        undocumented.is_empty(),
+        "These routes are missing from the OpenAPI document (see `src/routes/openapi.rs`): {:?}",
+        undocumented
+    );
+    let unregistered: Vec<_> = documented.difference(&registered).collect();
+    assert!(
+        unregistered.is_empty(),
+        "These routes are documented but not registered: {:?}",
+        unregistered
+    );
+}
+
+#[tokio::test]
+async fn every_schema_reference_resolves() {
+    // Act
+    let document = get_openapi_document().await;
+
+    // Assert
+    let mut references = Vec::new();
+    collect_references(&document, &mut references);
+    assert!(!references.is_empty());
+    for reference in references {
+        let name = reference.strip_prefix("#/components/schemas/").unwrap();
+        assert!(
+            document["components"]["schemas"].get(name).is_some(),
+            "{} is referenced but not listed in the components of the document.",
+            reference
+        );
+    }
+}
+
+fn collect_references(value: &serde_json::Value, references: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value.as_str()) {
+                    ("$ref", Some(reference)) => references.push(reference.to_owned()),
+                    _ => collect_references(value, references),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values
+            .iter()
+            .for_each(|v| collect_references(v, references)),
+        _ => {}
+    }
+}
+
+// The `(method, path)` of every operation in the OpenAPI document
+fn documented_routes() -> BTreeSet<(String, String)> {
+    let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    document["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| {
+            item.as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| (method.clone(), path.clone()))
+        })
+        .collect()
+}
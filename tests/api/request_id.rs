@@ -0,0 +1,174 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn every_response_carries_a_request_id() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let first = app.get_health("live").await;
+    let second = app.get_health("live").await;
+
+    // Assert
+    let first = first.headers().get("X-Request-Id").unwrap();
+    let second = second.headers().get("X-Request-Id").unwrap();
+    assert!(uuid::Uuid::parse_str(first.to_str().unwrap()).is_ok());
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn an_inbound_request_id_is_ignored_by_default() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/health/live", &app.address))
+        .header("X-Request-Id", "from-the-client")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_ne!(
+        response.headers().get("X-Request-Id").unwrap(),
+        "from-the-client"
+    );
+}
+
+#[tokio::test]
+async fn a_trusted_inbound_request_id_is_echoed() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.trust_request_id_header = true).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/health/live", &app.address))
+        .header("X-Request-Id", "from-the-proxy")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(
+        response.headers().get("X-Request-Id").unwrap(),
+        "from-the-proxy"
+    );
+}
+
+#[tokio::test]
+async fn an_invalid_trusted_request_id_is_replaced() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.trust_request_id_header = true).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/health/live", &app.address))
+        .header("X-Request-Id", "<script>alert(1)</script>")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    let request_id = response.headers().get("X-Request-Id").unwrap();
+    assert!(uuid::Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+}
+
+#[tokio::test]
+async fn the_request_id_follows_a_new_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    let request_id = response
+        .headers()
+        .get("X-Request-Id")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let subscriber = sqlx::query!("SELECT request_id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.request_id.as_deref(), Some(request_id));
+
+    let delivery = sqlx::query!("SELECT recipient, succeeded, request_id FROM email_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.recipient, "ursula_le_guin@gmail.com");
+    assert!(delivery.succeeded);
+    assert_eq!(delivery.request_id.as_deref(), Some(request_id));
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Metadata"]["request_id"], request_id);
+}
+
+#[tokio::test]
+async fn a_failed_delivery_is_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 500);
+    let delivery = sqlx::query!("SELECT succeeded FROM email_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!delivery.succeeded);
+}
+
+#[tokio::test]
+async fn error_pages_show_the_request_id() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // Sabotage the database
+    sqlx::query!("ALTER TABLE users DROP COLUMN username;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 500);
+    let request_id = response
+        .headers()
+        .get("X-Request-Id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&format!("<code>{}</code>", request_id)));
+    // The cause is logged, not shown
+    assert!(!html_page.contains("username"));
+}
@@ -0,0 +1,109 @@
+use crate::helpers::spawn_app;
+
+// The value of `'nonce-...'` in the `Content-Security-Policy` header
+fn csp_nonce(response: &reqwest::Response) -> String {
+    let policy = response.headers()["Content-Security-Policy"]
+        .to_str()
+        .unwrap();
+    let start = policy.find("'nonce-").unwrap() + "'nonce-".len();
+    let end = start + policy[start..].find('\'').unwrap();
+    policy[start..end].to_owned()
+}
+
+#[tokio::test]
+async fn html_pages_are_sent_with_security_headers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for path in ["/", "/login", "/subscriptions/check_inbox"] {
+        // Act
+        let response = app
+            .api_client
+            .get(format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        let headers = response.headers();
+        assert!(headers["Content-Security-Policy"]
+            .to_str()
+            .unwrap()
+            .contains("default-src 'self'"));
+        assert_eq!(headers["X-Frame-Options"], "DENY", "{}", path);
+        assert_eq!(
+            headers["Referrer-Policy"],
+            "strict-origin-when-cross-origin"
+        );
+        assert!(headers.contains_key("Permissions-Policy"));
+        assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+        // Tests do not run in production
+        assert!(!headers.contains_key("Strict-Transport-Security"));
+    }
+}
+
+#[tokio::test]
+async fn inline_styles_carry_the_nonce_of_the_request() {
+    // Arrange
+    let app = spawn_app().await;
+    let get_home = || app.api_client.get(format!("{}/", &app.address)).send();
+
+    // Act
+    let first = get_home().await.unwrap();
+    let second = get_home().await.unwrap();
+
+    // Assert
+    let nonce = csp_nonce(&first);
+    assert_ne!(nonce, csp_nonce(&second));
+    let html_page = first.text().await.unwrap();
+    assert!(html_page.contains(&format!(r#"<style nonce="{}">"#, nonce)));
+}
+
+#[tokio::test]
+async fn admin_responses_are_not_cached() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Anonymous users are redirected
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+
+    // Act - Part 2 - Logged in
+    app.test_user.login(&app).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let headers = response.headers();
+    assert_eq!(headers["Cache-Control"], "no-store");
+    assert!(headers.contains_key("Content-Security-Policy"));
+}
+
+#[tokio::test]
+async fn public_pages_can_be_cached() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!response.headers().contains_key("Cache-Control"));
+}
+
+#[tokio::test]
+async fn json_responses_do_not_get_a_content_security_policy() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_health("ready").await;
+
+    // Assert
+    assert!(!response.headers().contains_key("Content-Security-Policy"));
+}
@@ -0,0 +1,106 @@
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+use std::time::{Duration, Instant};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const BODY: &str = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+// The request is in flight once it has reached the email provider
+async fn wait_until_in_flight(app: &TestApp) {
+    while app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty()
+    {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn in_flight_requests_complete_before_the_application_stops() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Keeps the request in flight while we shut down
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .mount(&app.email_server)
+        .await;
+    let in_flight = {
+        let url = format!("{}/subscriptions", &app.address);
+        // A client of its own: an idle keep-alive connection would delay the shutdown
+        tokio::spawn(async move {
+            reqwest::Client::new()
+                .post(url)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(BODY)
+                .send()
+                .await
+        })
+    };
+    wait_until_in_flight(&app).await;
+
+    // Act
+    app.shutdown.cancel();
+
+    // Assert
+    let response = in_flight.await.unwrap().expect("The request was cut off");
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome = tokio::time::timeout(Duration::from_secs(5), app.server)
+        .await
+        .expect("The application did not stop");
+    assert!(outcome.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn new_connections_are_refused_once_stopped() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    app.shutdown.cancel();
+    app.server.await.unwrap().unwrap();
+
+    // Assert
+    let outcome = reqwest::Client::new()
+        .get(format!("{}/health/live", &app.address))
+        .send()
+        .await;
+    assert!(outcome.unwrap_err().is_connect());
+}
+
+#[tokio::test]
+async fn slow_requests_are_cut_off_after_the_grace_period() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.shutdown_grace_period_seconds = 1).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Longer than the grace period, shorter than the email client timeout
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&app.email_server)
+        .await;
+    let in_flight = {
+        let url = format!("{}/subscriptions", &app.address);
+        // A client of its own: an idle keep-alive connection would delay the shutdown
+        tokio::spawn(async move {
+            reqwest::Client::new()
+                .post(url)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(BODY)
+                .send()
+                .await
+        })
+    };
+    wait_until_in_flight(&app).await;
+
+    // Act
+    let start = Instant::now();
+    app.shutdown.cancel();
+    app.server.await.unwrap().unwrap();
+
+    // Assert
+    assert!(start.elapsed() < Duration::from_secs(3));
+    assert!(in_flight.await.unwrap().is_err());
+}
@@ -0,0 +1,175 @@
+use crate::helpers::spawn_app_with;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn too_many_attempts_from_the_same_address_get_a_429() {
+    // Arrange
+    let app = spawn_app_with(|c| c.signup_protection.per_ip.max_requests = 2).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    for email in ["ursula%40gmail.com", "le_guin%40yahoo.com"] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={}", email))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=600).contains(&retry_after));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limited");
+    let metrics = app.get_metrics().await.text().await.unwrap();
+    assert!(metrics.contains(r#"zero2prod_signups_blocked_total{reason="ip_rate_limit"}"#));
+}
+
+#[tokio::test]
+async fn too_many_attempts_for_the_same_email_domain_get_a_429() {
+    // Arrange
+    let app = spawn_app_with(|c| c.signup_protection.per_email_domain.max_requests = 1).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let first = app
+        .post_subscriptions("name=le%20guin&email=ursula%40victim.com".into())
+        .await;
+    let second = app
+        .post_subscriptions("name=le%20guin&email=le_guin%40VICTIM.com".into())
+        .await;
+    let other_domain = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 429);
+    assert!(second.headers().contains_key("Retry-After"));
+    assert_eq!(other_domain.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn browsers_get_a_429_page() {
+    // Arrange
+    let app = spawn_app_with(|c| c.signup_protection.per_ip.max_requests = 1).await;
+    let body = "name=le%20guin&email=ursula%40";
+
+    // Act
+    app.post_subscriptions_from_browser(body.into()).await;
+    let response = app.post_subscriptions_from_browser(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Too many subscription attempts"));
+}
+
+#[tokio::test]
+async fn a_filled_in_honeypot_is_answered_as_a_success_but_dropped() {
+    // Arrange
+    let app = spawn_app_with(|_| {}).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula%40gmail.com&website=http%3A%2F%2Fspam.example".into(),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+    let metrics = app.get_metrics().await.text().await.unwrap();
+    assert!(metrics.contains(r#"zero2prod_signups_blocked_total{reason="honeypot"}"#));
+}
+
+#[tokio::test]
+async fn the_home_page_form_has_a_honeypot_field() {
+    // Arrange
+    let app = spawn_app_with(|_| {}).await;
+
+    // Act
+    let html_page = app.get_home_html().await;
+
+    // Assert
+    assert!(html_page.contains(r#"name="website""#));
+    assert!(!html_page.contains("data-sitekey"));
+}
+
+#[tokio::test]
+async fn an_enabled_challenge_must_be_passed_to_subscribe() {
+    // Arrange
+    let challenge_server = MockServer::start().await;
+    let verify_url = challenge_server.uri();
+    let app = spawn_app_with(|c| {
+        let challenge = &mut c.signup_protection.challenge;
+        challenge.enabled = true;
+        challenge.verify_url = verify_url;
+        challenge.secret = secrecy::Secret::new("challenge-secret".into());
+        challenge.site_key = "site-key".into();
+    })
+    .await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("response=a-valid-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": true
+        })))
+        .mount(&challenge_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - The widget is on the form
+    let html_page = app.get_home_html().await;
+    assert!(html_page.contains(r#"<div class="cf-turnstile" data-sitekey="site-key"></div>"#));
+
+    // Act - Part 2 - Without a token
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmail.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "challenge_failed");
+
+    // Act - Part 3 - With the token added by the widget
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula%40gmail.com&cf-turnstile-response=a-valid-token".into(),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
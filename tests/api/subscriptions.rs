@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{assert_is_redirect_to, spawn_app};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -164,3 +164,193 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_succeeds_even_if_the_confirmation_email_cannot_be_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    // Sabotage the audit trail only
+    sqlx::query!("ALTER TABLE email_deliveries DROP COLUMN subject;",)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscribe_accepts_json_and_answers_with_json() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com"
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+}
+
+#[tokio::test]
+async fn subscribe_reports_an_error_for_every_invalid_field() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "", "email": "definitely-not-an-email"}),
+            vec!["email", "name"],
+            "invalid name and email",
+        ),
+        (
+            serde_json::json!({"name": "le guin", "email": ""}),
+            vec!["email"],
+            "empty email",
+        ),
+        (
+            serde_json::json!({"email": "ursula_le_guin@gmail.com"}),
+            vec!["name"],
+            "missing name",
+        ),
+        (serde_json::json!({}), vec!["email", "name"], "empty object"),
+    ];
+
+    for (body, invalid_fields, description) in test_cases {
+        // Act
+        let response = app.post_subscriptions_json(&body).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not return a 400 Bad Request when the payload was {}.",
+            description
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "validation_error");
+        let fields = body["error"]["fields"].as_object().unwrap();
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            invalid_fields,
+            "Unexpected invalid fields when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn subscribe_rejects_malformed_json() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"name": "le guin""#)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_body");
+}
+
+#[tokio::test]
+async fn subscribe_rejects_unsupported_content_types() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "text/plain")
+        .body("le guin <ursula_le_guin@gmail.com>")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 415);
+}
+
+#[tokio::test]
+async fn browsers_are_redirected_to_check_their_inbox() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Submit the home page form
+    let response = app.post_subscriptions_from_browser(body.into()).await;
+    assert_is_redirect_to(&response, "/subscriptions/check_inbox");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .api_client
+        .get(format!("{}/subscriptions/check_inbox", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<h1>Check your inbox</h1>"));
+}
+
+#[tokio::test]
+async fn browsers_are_sent_back_to_the_form_with_escaped_errors() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=%3Cb%3Eursula%3C%2Fb%3E";
+
+    // Act - Part 1 - Submit the home page form
+    let response = app.post_subscriptions_from_browser(body.into()).await;
+    assert_is_redirect_to(&response, "/");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_home_html().await;
+    assert!(html_page
+        .contains("<p><i>&lt;b&gt;ursula&lt;/b&gt; is not a valid subscriber email</i></p>"));
+    assert!(html_page.contains(r#"<form action="/subscriptions" method="post">"#));
+
+    // Act - Part 3 - Reload the page
+    let html_page = app.get_home_html().await;
+    assert!(!html_page.contains("is not a valid subscriber email"));
+}
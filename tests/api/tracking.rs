@@ -0,0 +1,159 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use crate::newsletter::create_confirmed_subscriber;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+// Publish a tracked issue with a link, and return the bodies of the email received
+async fn receive_tracked_issue(app: &TestApp) -> (String, serde_json::Value) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_create_draft(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "{{ unsubscribe_url }}",
+            "html_content": r#"<p>Read <a href="https://example.com/post">the post</a>, or <a href="{{ unsubscribe_url }}">unsubscribe</a></p>"#,
+            // Without the "view in browser" link on top
+            "hidden_from_archive": true,
+            "tracked": true,
+        }))
+        .await;
+    let draft_id = response.headers()["Location"]
+        .to_str()
+        .unwrap()
+        .strip_prefix("/admin/newsletters/drafts/")
+        .unwrap()
+        .to_owned();
+    let response = app
+        .post_draft(&draft_id, "/publish", &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    (
+        draft_id,
+        serde_json::from_slice(&email_request.body).unwrap(),
+    )
+}
+
+// The URLs of the HTML body that start with `prefix`, pointed at the test server
+fn urls_starting_with(app: &TestApp, html: &str, prefix: &str) -> Vec<reqwest::Url> {
+    html.split('"')
+        .filter(|value| value.starts_with(&format!("http://127.0.0.1{}", prefix)))
+        .map(|value| {
+            let mut url = reqwest::Url::parse(&value.replace("&amp;", "&")).unwrap();
+            url.set_port(Some(app.port)).unwrap();
+            url
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn opens_clicks_and_unsubscribes_of_tracked_issues_are_counted() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let (draft_id, body) = receive_tracked_issue(&app).await;
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(!html_body.contains(r#"href="https://example.com/post""#));
+    let opens = urls_starting_with(&app, html_body, "/tracking/open");
+    let clicks = urls_starting_with(&app, html_body, "/tracking/click");
+    assert_eq!((opens.len(), clicks.len()), (1, 1));
+    // Leaving is not a click
+    let unsubscribe_links = urls_starting_with(&app, html_body, "/subscriptions/unsubscribe");
+    assert_eq!(unsubscribe_links.len(), 1);
+
+    // Act - Part 1 - Open, twice
+    for _ in 0..2 {
+        let response = app.api_client.get(opens[0].clone()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+    }
+
+    // Act - Part 2 - Click
+    let response = app.api_client.get(clicks[0].clone()).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(response.headers()["Location"], "https://example.com/post");
+
+    // Act - Part 3 - Unsubscribe
+    let parameters: Vec<(String, String)> =
+        unsubscribe_links[0].query_pairs().into_owned().collect();
+    assert!(parameters
+        .iter()
+        .any(|(name, value)| name == "newsletter_issue_id" && value == &draft_id));
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/unsubscribe", app.address))
+        .form(&parameters)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Assert - Each recipient is counted once
+    let html_page = app
+        .get_admin_html(&format!("/newsletters/{}/stats", draft_id))
+        .await;
+    assert!(html_page.contains("<tr><th>Total</th><th>1</th><th>1</th><th>1</th><th>1</th></tr>"));
+    assert!(html_page.contains(
+        r#"<tr><td><a href="https://example.com/post">https://example.com/post</a></td><td>1</td><td>1</td></tr>"#
+    ));
+}
+
+#[tokio::test]
+async fn tracking_links_cannot_be_tampered_with() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let (_, body) = receive_tracked_issue(&app).await;
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    let click = urls_starting_with(&app, html_body, "/tracking/click").remove(0);
+
+    // Act - Somewhere else than the page linked from the issue
+    let mut tampered = click.clone();
+    let parameters: Vec<(String, String)> = click
+        .query_pairs()
+        .into_owned()
+        .map(|(name, value)| match name.as_str() {
+            "url" => (name, "https://phishing.example.com/".to_owned()),
+            _ => (name, value),
+        })
+        .collect();
+    tampered.query_pairs_mut().clear().extend_pairs(parameters);
+    let response = app.api_client.get(tampered).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.headers().get("Location").is_none());
+}
+
+#[tokio::test]
+async fn nothing_is_tracked_while_tracking_is_disabled() {
+    // Arrange
+    let app = spawn_app_with(|c| c.tracking.enabled = false).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let (draft_id, body) = receive_tracked_issue(&app).await;
+
+    // Assert
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.contains(r#"href="https://example.com/post""#));
+    assert!(urls_starting_with(&app, html_body, "/tracking/").is_empty());
+    let html_page = app
+        .get_admin_html(&format!("/newsletters/{}/stats", draft_id))
+        .await;
+    assert!(html_page.contains("Tracking is disabled: new opens and clicks are not recorded."));
+    assert!(html_page.contains("<tr><th>Total</th><th>1</th><th>0</th><th>0</th><th>0</th></tr>"));
+}
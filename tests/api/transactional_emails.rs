@@ -0,0 +1,114 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_edit_transactional_emails() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_transactional_email(
+            "confirmation",
+            &serde_json::json!({
+                "subject": "Welcome",
+                "html_template": "{{ confirmation_url }}",
+                "text_template": "{{ confirmation_url }}",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_edited_confirmation_email_is_sent_to_the_next_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Edit the email
+    let response = app
+        .post_transactional_email(
+            "confirmation",
+            &serde_json::json!({
+                "subject": "Please confirm, {{ name }}",
+                "html_template": r#"<p>Hi {{ name }}!</p><script>alert(1)</script><a href="{{ confirmation_url }}">Confirm</a>"#,
+                "text_template": "Hi {{ name }}! Confirm at {{ confirmation_url }}",
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/emails/confirmation");
+    let html_page = app.get_admin_html("/emails/confirmation").await;
+    assert!(html_page.contains("<p><i>Version 1 of the email has been saved.</i></p>"));
+    assert!(html_page.contains(r#"<a href="/admin/emails/confirmation/versions/1">1</a>"#));
+
+    // Act - Part 2 - Subscribe
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Please confirm, le guin");
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.starts_with("<p>Hi le guin!</p><a href="));
+    assert!(!html_body.contains("<script"));
+    let confirmation_links = app.get_confirmation_links(email_request);
+    assert_eq!(confirmation_links.html, confirmation_links.plain_text);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn confirmation_emails_without_their_link_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_transactional_email(
+            "confirmation",
+            &serde_json::json!({
+                "subject": "Welcome",
+                "html_template": r#"<a href="{{ confirmation_url }}">Confirm</a>"#,
+                "text_template": "Welcome aboard!",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/emails/confirmation");
+    let html_page = app.get_admin_html("/emails/confirmation").await;
+    assert!(html_page.contains(
+        "<p><i>Both versions of the email must contain `{{ confirmation_url }}`.</i></p>"
+    ));
+    // The built-in default is still sent
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Welcome!");
+}
@@ -0,0 +1,125 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::create_confirmed_subscriber;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+// Publish an issue whose plain-text body is the unsubscribe link, and return the link received
+async fn receive_unsubscribe_link(app: &TestApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "{{ unsubscribe_url }}",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            // Without the "view in browser" link on top
+            "hidden_from_archive": true,
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let mut link = reqwest::Url::parse(body["TextBody"].as_str().unwrap()).unwrap();
+    assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+    link.set_port(Some(app.port)).unwrap();
+    link
+}
+
+#[tokio::test]
+async fn subscribers_can_unsubscribe_with_the_link_of_a_newsletter_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let link = receive_unsubscribe_link(&app).await;
+
+    // Act - Part 1 - Following the link asks for a confirmation
+    let response = app.api_client.get(link.clone()).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<form action="/subscriptions/unsubscribe" method="post">"#));
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+
+    // Act - Part 2 - Confirm
+    let parameters: Vec<(String, String)> = link.query_pairs().into_owned().collect();
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/unsubscribe", app.address))
+        .form(&parameters)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("You have been unsubscribed"));
+
+    // Assert
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn forged_unsubscribe_links_are_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let mut link = receive_unsubscribe_link(&app).await;
+    let subscriber_id = uuid::Uuid::new_v4().to_string();
+    let signature = link
+        .query_pairs()
+        .find(|(key, _)| key == "signature")
+        .unwrap()
+        .1
+        .into_owned();
+    // Somebody else's id, with the signature of ours
+    link.query_pairs_mut()
+        .clear()
+        .append_pair("subscriber_id", &subscriber_id)
+        .append_pair("signature", &signature);
+
+    // Act - Part 1 - Follow the link
+    let response = app.api_client.get(link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    // Act - Part 2 - Submit the form
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/unsubscribe", app.address))
+        .form(&serde_json::json!({
+            "subscriber_id": subscriber_id,
+            "signature": "not-even-hex",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    // Assert
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+}
@@ -0,0 +1,255 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::newsletter::{create_confirmed_subscriber, create_unconfirmed_subscriber};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+// The id Postmark gives the newsletter issue sent by `deliver_issue`
+const MESSAGE_ID: &str = "b7bc2f4a-e38e-4336-af7d-e6c392c2f817";
+
+// A payload recorded from Postmark, about the message sent to `email` by `deliver_issue`
+fn postmark_event(fixture: &str, email: &str) -> serde_json::Value {
+    let mut event: serde_json::Value = serde_json::from_str(fixture).unwrap();
+    event["MessageID"] = MESSAGE_ID.into();
+    // Bounces and spam complaints name the recipient `Email`, the other events `Recipient`
+    let recipient = if event.get("Email").is_some() {
+        "Email"
+    } else {
+        "Recipient"
+    };
+    event[recipient] = email.into();
+    event
+}
+
+// Send an issue to a new confirmed subscriber, and return their email
+async fn deliver_issue(app: &TestApp) -> String {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "SubmittedAt": "2023-08-15T16:33:52.0000000Z",
+            "MessageID": MESSAGE_ID,
+            "ErrorCode": 0,
+            "Message": "OK"
+        })))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+// The kind and details of the events stored with the delivery of `MESSAGE_ID`
+async fn delivery_events(app: &TestApp) -> Vec<(String, Option<String>)> {
+    sqlx::query!(
+        r#"
+        SELECT e.kind, e.details
+        FROM email_delivery_events e
+        JOIN email_deliveries d ON d.delivery_id = e.delivery_id
+        WHERE d.message_id = $1
+        ORDER BY e.occurred_at
+        "#,
+        MESSAGE_ID,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.kind, r.details))
+    .collect()
+}
+
+#[tokio::test]
+async fn hard_bounced_subscribers_are_suppressed_and_not_sent_issues_anymore() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = deliver_issue(&app).await;
+
+    // Act - Part 1 - Postmark reports the bounce
+    let response = app
+        .post_postmark_webhook(&postmark_event(
+            include_str!("fixtures/postmark/hard_bounce.json"),
+            &email,
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "suppressed");
+    assert_eq!(
+        delivery_events(&app).await,
+        vec![("bounce".to_owned(), Some("HardBounce".to_owned()))]
+    );
+
+    // Act - Part 2 - The next issue
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    // Mock verifies on Drop that the suppressed subscriber was not sent it
+}
+
+#[tokio::test]
+async fn subscribers_who_complain_about_spam_are_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = deliver_issue(&app).await;
+
+    // Act
+    let response = app
+        .post_postmark_webhook(&postmark_event(
+            include_str!("fixtures/postmark/spam_complaint.json"),
+            // Providers do not keep the case of the address
+            &email.to_uppercase(),
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "suppressed");
+    assert_eq!(
+        delivery_events(&app).await,
+        vec![(
+            "spam_complaint".to_owned(),
+            Some("SpamComplaint".to_owned())
+        )]
+    );
+}
+
+#[tokio::test]
+async fn suppressed_subscribers_are_not_confirmed_by_their_confirmation_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    // The confirmation email itself is reported as spam
+    let response = app
+        .post_postmark_webhook(&postmark_event(
+            include_str!("fixtures/postmark/spam_complaint.json"),
+            &email,
+        ))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - e.g. a mail scanner following the links of the email
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "suppressed");
+}
+
+#[tokio::test]
+async fn deliveries_opens_and_soft_bounces_are_stored_without_suppressing_anybody() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = deliver_issue(&app).await;
+
+    // Act
+    for fixture in [
+        include_str!("fixtures/postmark/delivery.json"),
+        // Retried by Postmark
+        include_str!("fixtures/postmark/delivery.json"),
+        include_str!("fixtures/postmark/soft_bounce.json"),
+        include_str!("fixtures/postmark/open.json"),
+        // Not asked for: acknowledged, and ignored
+        include_str!("fixtures/postmark/click.json"),
+    ] {
+        let response = app
+            .post_postmark_webhook(&postmark_event(fixture, &email))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Assert
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    assert_eq!(
+        delivery_events(&app).await,
+        vec![
+            ("delivery".to_owned(), None),
+            ("bounce".to_owned(), Some("SoftBounce".to_owned())),
+            ("open".to_owned(), None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn webhooks_without_valid_credentials_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = deliver_issue(&app).await;
+    let event = postmark_event(include_str!("fixtures/postmark/hard_bounce.json"), &email);
+
+    for (username, password) in [
+        (None, None),
+        (Some("postmark"), Some("not-the-password")),
+        (Some("someone-else"), Some("my-webhook-password")),
+    ] {
+        // Act
+        let mut request = app
+            .api_client
+            .post(format!("{}/webhooks/postmark", &app.address))
+            .json(&event);
+        if let Some(username) = username {
+            request = request.basic_auth(username, password);
+        }
+        let response = request.send().await.expect("Failed to execute request.");
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Basic realm="webhooks""#
+        );
+    }
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    assert!(delivery_events(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn a_body_that_is_not_a_postmark_event_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({ "RecordType": "Bounce" }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}